@@ -10,6 +10,26 @@ fn main() {
             format!("-I{}", path.to_str().expect("non-Unicode include path"))))
         .constified_enum_module("pool_state")
         .constified_enum_module("zfs_type_t")
+        .constified_enum_module("zfs_prop_t")
+        .constified_enum_module("zpool_prop_t")
+        .constified_enum_module("zprop_source_t")
+        .constified_enum_module("zfs_canmount_type_t")
+        .constified_enum_module("zpool_status_t")
+        .constified_enum_module("vdev_state")
+        .constified_enum_module("vdev_aux")
+        .constified_enum_module("pool_scan_func")
+        .constified_enum_module("pool_scrub_cmd")
+        .constified_enum_module("dsl_scan_state")
+        .constified_enum_module("pool_trim_func")
+        .constified_enum_module("vdev_trim_state")
+        .constified_enum_module("pool_initialize_func")
+        .constified_enum_module("vdev_initializing_state")
+        .constified_enum_module("checkpoint_state")
+        .constified_enum_module("spa_feature")
+        .constified_enum_module("zpool_compat_status_t")
+        .constified_enum_module("zfs_ioc")
+        .constified_enum_module("diff_flags")
+        .constified_enum_module("dmu_objset_type")
         //.constified_enum_module(".*_t")
         .rustified_enum("zfs_error")
         .bitfield_enum("lzc_send_flags")
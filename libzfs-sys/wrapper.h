@@ -1,2 +1,4 @@
 #define _LARGEFILE64_SOURCE
 #include <libzfs.h>
+#include <zfeature_common.h>
+#include <sys/zfs_ioctl.h>
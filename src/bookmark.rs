@@ -0,0 +1,179 @@
+use libzfs_sys as sys;
+
+use std::collections::BTreeMap;
+use std::io;
+use std::os::raw::c_int;
+use std::ptr;
+
+use crate::nvlist::OwnedNvList;
+use crate::{
+    Dataset, DatasetType, DatasetTypeMask, Error, ErrorMark, LibZfs, Result, SafeString, ZfsError,
+};
+
+// ZFS_MAX_DATASET_NAME_LEN, not including the terminating NUL
+const MAX_NAME_LEN: usize = 255;
+
+impl Dataset {
+    /// Create a bookmark of this snapshot (like `zfs bookmark`), named `pool/fs#name` after the
+    /// snapshot's dataset, and open it. `name` is the part after the `#`.
+    ///
+    /// A bookmark remembers a snapshot's place in its dataset's history, without keeping its
+    /// data, so it can still be the source of incremental sends after the snapshot is destroyed.
+    ///
+    /// Fails with:
+    /// * `EZFS_BADTYPE` if this isn't a snapshot.
+    /// * `EZFS_NAMETOOLONG` if the bookmark's full name would be too long, or `EZFS_INVALIDNAME`
+    ///   if `name` has characters other than letters, digits, and `_-.: `.
+    /// * `EZFS_EXISTS` if there's already a bookmark with the name.
+    /// * `EZFS_BADVERSION` if the pool doesn't have the `bookmarks` feature enabled.
+    pub fn bookmark(&self, name: &str) -> Result<Dataset> {
+        let snap_name = self.get_name();
+        let fs = match AsRef::<str>::as_ref(&snap_name).split_once('@') {
+            Some((fs, _)) if self.get_type() == DatasetType::Snapshot => fs,
+            _ => return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADTYPE,
+                "only snapshots can be bookmarked",
+            ))),
+        };
+        let full_name = SafeString::from(format!("{}#{}", fs, name));
+        if AsRef::<str>::as_ref(&full_name).len() > MAX_NAME_LEN {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_NAMETOOLONG,
+                format!("bookmark name '{}' is longer than {} bytes", full_name, MAX_NAME_LEN),
+            )));
+        }
+        if name.is_empty() || !LibZfs::is_valid_name(&full_name, DatasetType::Bookmark) {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_INVALIDNAME,
+                format!("invalid bookmark name '{}'", full_name),
+            )));
+        }
+
+        // Maps each new bookmark to the snapshot it's of.
+        let mut bookmarks = OwnedNvList::new();
+        bookmarks.add_string(full_name.as_ref(), snap_name.as_ref());
+        let mut errors: *mut sys::nvlist_t = ptr::null_mut();
+        let err = unsafe { sys::lzc_bookmark(bookmarks.as_ptr(), &mut errors) };
+        if !errors.is_null() {
+            drop(unsafe { OwnedNvList::from_raw(errors) });
+        }
+        // libzfs_core reports errors as errno values, without a libzfs error to go with them.
+        let code = match err {
+            0 => None,
+            libc::EEXIST => Some((sys::zfs_error::EZFS_EXISTS, "bookmark already exists")),
+            libc::EXDEV => Some((
+                sys::zfs_error::EZFS_CROSSTARGET,
+                "bookmark is not in the same dataset as the snapshot",
+            )),
+            libc::ENOTSUP => Some((
+                sys::zfs_error::EZFS_BADVERSION,
+                "pool must be upgraded to use bookmarks",
+            )),
+            _ => return Err(Error::Sys(io::Error::from_raw_os_error(err))),
+        };
+        if let Some((code, msg)) = code {
+            return Err(Error::Zfs(ZfsError::new(
+                code,
+                format!("cannot create bookmark '{}': {}", full_name, msg),
+            )));
+        }
+
+        let types: DatasetTypeMask = DatasetType::Bookmark.into();
+        let mark = ErrorMark::new(self.libzfs);
+        let handle = unsafe { sys::zfs_open(self.libzfs, full_name.as_ptr(), types.0 as c_int) };
+        if handle.is_null() {
+            return Err(mark.error(self.libzfs));
+        }
+        Ok(Dataset { libzfs: self.libzfs, handle })
+    }
+}
+
+impl Dataset {
+    /// Destroy this bookmark. Fails with `EZFS_BADTYPE` if this isn't a bookmark.
+    pub fn destroy_bookmark(self) -> Result<()> {
+        if self.get_type() != DatasetType::Bookmark {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADTYPE,
+                "not a bookmark",
+            )));
+        }
+        let name = self.get_name();
+        match destroy_bookmarks(self.libzfs, &[name]).into_iter().next() {
+            Some((_, e)) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl LibZfs {
+    /// Destroy bookmarks, given by their full names, in as few operations as possible. Returns
+    /// the errors for the bookmarks which couldn't be destroyed, keyed by name, which is empty if
+    /// all of them were. A failure to destroy one doesn't stop the others from being destroyed.
+    ///
+    /// Names which aren't bookmarks fail with `EZFS_BADTYPE`, and bookmarks which don't exist
+    /// fail with `EZFS_NOENT`.
+    pub fn destroy_bookmarks(&self, names: &[SafeString]) -> Result<BTreeMap<SafeString, Error>> {
+        Ok(destroy_bookmarks(self.handle, names))
+    }
+}
+
+fn destroy_bookmarks(libzfs: *mut sys::libzfs_handle_t, names: &[SafeString])
+    -> BTreeMap<SafeString, Error>
+{
+    let mut errors = BTreeMap::new();
+    let mut remaining = vec![];
+    for name in names {
+        if !AsRef::<str>::as_ref(name).contains('#') {
+            errors.insert(name.clone(), Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADTYPE,
+                format!("'{}' is not a bookmark", name),
+            )));
+        } else if 0 == unsafe {
+            sys::zfs_dataset_exists(libzfs, name.as_ptr(), sys::zfs_type_t::ZFS_TYPE_BOOKMARK)
+        } {
+            // The kernel would silently skip it.
+            errors.insert(name.clone(), Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_NOENT,
+                format!("bookmark '{}' does not exist", name),
+            )));
+        } else {
+            remaining.push(name);
+        }
+    }
+
+    // The kernel destroys all of the bookmarks or none of them, so leave out the ones it says
+    // failed, and try again with the rest.
+    while !remaining.is_empty() {
+        let mut bookmarks = OwnedNvList::new();
+        for name in &remaining {
+            bookmarks.add_boolean(name.as_ref());
+        }
+        let mut failures: *mut sys::nvlist_t = ptr::null_mut();
+        let err = unsafe { sys::lzc_destroy_bookmarks(bookmarks.as_ptr(), &mut failures) };
+        let failures = (!failures.is_null()).then(|| unsafe { OwnedNvList::from_raw(failures) });
+        if err == 0 {
+            break;
+        }
+
+        // Each failed bookmark maps to its errno.
+        let before = remaining.len();
+        if let Some(failures) = &failures {
+            let failures = failures.borrow();
+            remaining.retain(|name| match failures.lookup_i32(name.as_ref()) {
+                Some(errno) => {
+                    let e = Error::Sys(io::Error::from_raw_os_error(errno));
+                    errors.insert((*name).clone(), e);
+                    false
+                }
+                None => true,
+            });
+        }
+        // If the failure wasn't down to particular bookmarks, it applies to all of them.
+        if remaining.len() == before {
+            for name in remaining.drain(..) {
+                errors.insert(name.clone(), Error::Sys(io::Error::from_raw_os_error(err)));
+            }
+        }
+    }
+    errors
+}
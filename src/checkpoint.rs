@@ -0,0 +1,76 @@
+use libzfs_sys as sys;
+
+use std::mem;
+
+use crate::nvlist::NvList;
+use crate::{ztry, Result, ZPool};
+
+/// A pool's checkpoint, as shown by `zpool status`.
+///
+/// Times are in seconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointStats {
+    /// When the checkpoint was created.
+    pub start_time: u64,
+    /// Space used by the checkpoint, in bytes: blocks which have been freed since it was taken,
+    /// but are kept so the pool can be rewound to it.
+    pub space: u64,
+    /// Whether the checkpoint is being discarded, which happens in the background. A new
+    /// checkpoint can't be created until this is done.
+    pub discarding: bool,
+}
+
+impl ZPool {
+    /// Take a checkpoint of the pool (like `zpool checkpoint`), which the pool can be rewound
+    /// to when it's next imported, undoing everything since, including changes to its devices.
+    ///
+    /// Only one checkpoint can exist at a time. Creating another fails with
+    /// `EZFS_CHECKPOINT_EXISTS`, or `EZFS_DISCARDING_CHECKPOINT` while the old one is still
+    /// being discarded. While a checkpoint exists, operations which can't be rewound, like
+    /// removing, attaching or expanding devices, fail with `EZFS_CHECKPOINT_EXISTS`.
+    pub fn checkpoint(&self) -> Result<()> {
+        ztry!(unsafe { sys::zpool_checkpoint(self.handle) }, self.libzfs);
+        Ok(())
+    }
+
+    /// Discard the pool's checkpoint (like `zpool checkpoint -d`), freeing the space it uses in
+    /// the background. Fails with `EZFS_NO_CHECKPOINT` if there isn't one, or
+    /// `EZFS_DISCARDING_CHECKPOINT` if it's already being discarded.
+    pub fn discard_checkpoint(&self) -> Result<()> {
+        ztry!(unsafe { sys::zpool_discard_checkpoint(self.handle) }, self.libzfs);
+        Ok(())
+    }
+
+    /// Get information about the pool's checkpoint, or `None` if it doesn't have one.
+    ///
+    /// This refreshes the pool's statistics first, so the numbers are current.
+    pub fn checkpoint_info(&self) -> Result<Option<CheckpointStats>> {
+        let mut missing: sys::boolean_t = 0;
+        ztry!(unsafe { sys::zpool_refresh_stats(self.handle, &mut missing) }, self.libzfs);
+
+        Ok(self.config().lookup_nvlist("vdev_tree").and_then(checkpoint_stats))
+    }
+}
+
+/// Read the checkpoint's stats from the pool's vdev tree, or `None` if there's no checkpoint.
+pub(crate) fn checkpoint_stats(tree: NvList<'_>) -> Option<CheckpointStats> {
+    let stats = match tree.lookup_u64_array("checkpoint_stats") {
+        Some(stats) if mem::size_of_val(stats) >= mem::size_of::<sys::pool_checkpoint_stat_t>() => {
+            // The array is the pool_checkpoint_stat_t struct itself, which is all u64s.
+            unsafe { &*(stats.as_ptr() as *const sys::pool_checkpoint_stat_t) }
+        }
+        _ => return None,
+    };
+
+    use sys::checkpoint_state::*;
+    let discarding = match stats.pcs_state as sys::checkpoint_state_t {
+        CS_CHECKPOINT_EXISTS => false,
+        CS_CHECKPOINT_DISCARDING => true,
+        _ => return None,
+    };
+    Some(CheckpointStats {
+        start_time: stats.pcs_start_time,
+        space: stats.pcs_space,
+        discarding,
+    })
+}
@@ -0,0 +1,392 @@
+use libzfs_sys as sys;
+
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::os::fd::AsRawFd;
+use std::os::raw::c_int;
+use std::os::unix::ffi::OsStringExt;
+use std::path::PathBuf;
+use std::ptr;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::send::{pipe, SendPtr};
+use crate::{ztry, Dataset, Error, ErrorMark, Result, SafeString, ZfsError};
+
+/// How many files and directories changed between two snapshots, as counted by `zfs diff`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct DiffStats {
+    pub added: u64,
+    pub removed: u64,
+    /// Changed in content or metadata. A directory counts as modified when entries are added
+    /// to or removed from it.
+    pub modified: u64,
+    pub renamed: u64,
+}
+
+impl Dataset {
+    /// Count what changed in this filesystem between the snapshot `from` and the snapshot
+    /// `to`, or the filesystem as it is now if `to` is `None`, like summarizing `zfs diff`. The
+    /// snapshots are given by their full names, and must be of this filesystem.
+    ///
+    /// Only the counts are kept, so this is cheap in memory however much changed, but it still
+    /// has to walk all the changes. It needs the same privileges as `zfs diff`; without them,
+    /// this fails with `EZFS_DIFF`.
+    pub fn diff_stats(&self, from: &SafeString, to: Option<&SafeString>) -> Result<DiffStats> {
+        let mut stats = DiffStats::default();
+        show_diff(self, from, to, 0, |line| {
+            match line.first() {
+                Some(b'+') => stats.added += 1,
+                Some(b'-') => stats.removed += 1,
+                Some(b'M') => stats.modified += 1,
+                Some(b'R') => stats.renamed += 1,
+                _ => (),
+            }
+            Ok(())
+        })?;
+        Ok(stats)
+    }
+
+    /// List what changed between this snapshot and `other`, which is either a later snapshot of
+    /// the same filesystem, or the filesystem itself to compare with how it is now (like
+    /// `zfs diff`). The changes are listed in the order `zfs diff` prints them.
+    ///
+    /// This needs the same privileges as `zfs diff`; without them, or if `other` isn't a later
+    /// snapshot of the same filesystem or the filesystem itself, this fails with `EZFS_DIFF`.
+    pub fn diff(&self, other: &Dataset, opts: DiffOpts) -> Result<Vec<DiffEntry>> {
+        let from = self.get_name();
+        let fs_name = match AsRef::<str>::as_ref(&from).split_once('@') {
+            Some((fs, _)) => SafeString::from(fs),
+            None => return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADTYPE,
+                "only a snapshot can be diffed from",
+            ))),
+        };
+        // Like the CLI, run the diff on the snapshot's filesystem.
+        let types = sys::zfs_type_t::ZFS_TYPE_FILESYSTEM as c_int;
+        let mark = ErrorMark::new(self.libzfs);
+        let handle = unsafe { sys::zfs_open(self.libzfs, fs_name.as_ptr(), types) };
+        if handle.is_null() {
+            return Err(mark.error(self.libzfs));
+        }
+        let fs = Dataset { libzfs: self.libzfs, handle };
+
+        let mut flags = 0;
+        if opts.file_types {
+            flags |= sys::diff_flags::ZFS_DIFF_CLASSIFY;
+        }
+        if opts.timestamps {
+            flags |= sys::diff_flags::ZFS_DIFF_TIMESTAMP;
+        }
+
+        let mut entries = vec![];
+        show_diff(&fs, &from, Some(&other.get_name()), flags as c_int, |line| {
+            let entry = DiffEntry::parse(&line, &opts).ok_or_else(|| {
+                Error::Zfs(ZfsError::new(
+                    sys::zfs_error::EZFS_DIFFDATA,
+                    format!("unexpected zfs diff output: {:?}", String::from_utf8_lossy(&line)),
+                ))
+            })?;
+            entries.push(entry);
+            Ok(())
+        })?;
+        Ok(entries)
+    }
+}
+
+/// Run `zfs_show_diff` in parseable form on a separate thread, and call `on_line` with each line
+/// of its output, without the newline. If `on_line` fails, the diff is stopped, and its error is
+/// returned.
+fn show_diff<F>(
+    fs: &Dataset,
+    from: &SafeString,
+    to: Option<&SafeString>,
+    flags: c_int,
+    mut on_line: F,
+) -> Result<()>
+    where F: FnMut(Vec<u8>) -> Result<()>,
+{
+    let (read_fd, write_fd) = pipe()?;
+    let libzfs = SendPtr(fs.libzfs);
+    let zhp = SendPtr(fs.handle);
+    let flags = flags | sys::diff_flags::ZFS_DIFF_PARSEABLE as c_int;
+
+    thread::scope(|scope| {
+        let diff = scope.spawn(move || -> Result<()> {
+            // The write end is closed when this returns, which ends the output.
+            ztry!(unsafe {
+                sys::zfs_show_diff(
+                    zhp.ptr(),
+                    write_fd.as_raw_fd(),
+                    from.as_ptr(),
+                    to.map(|s| s.as_ptr()).unwrap_or(ptr::null()),
+                    flags,
+                )
+            }, libzfs.ptr());
+            Ok(())
+        });
+
+        // Each change is a line of tab-separated fields; names are escaped, so they never
+        // contain newlines or tabs.
+        let mut line_result = Ok(());
+        let mut read_result = Ok(());
+        for line in BufReader::new(File::from(read_fd)).split(b'\n') {
+            match line {
+                Ok(line) => {
+                    line_result = on_line(line);
+                    if line_result.is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    read_result = Err(e);
+                    break;
+                }
+            }
+        }
+        // Breaking out of the loop closes the read end, which makes the diff fail if it's not
+        // done yet, rather than block forever.
+
+        let diff_result = diff.join()
+            .unwrap_or_else(|_| Err(Error::Sys(io::Error::other("zfs diff thread panicked"))));
+        line_result?;
+        diff_result?;
+        read_result.map_err(Error::Sys)
+    })
+}
+
+impl DiffStats {
+    /// The total number of changes.
+    pub fn total(&self) -> u64 {
+        self.added + self.removed + self.modified + self.renamed
+    }
+}
+
+/// Options for [`Dataset::diff`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DiffOpts {
+    /// Include the type of each file (`zfs diff -F`).
+    pub file_types: bool,
+    /// Include the time of each change (`zfs diff -t`).
+    pub timestamps: bool,
+}
+
+/// How a file changed, in a [`DiffEntry`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DiffChange {
+    Added,
+    Removed,
+    /// Changed in content or metadata. A directory counts as modified when entries are added
+    /// to or removed from it.
+    Modified,
+    /// Moved from one path to another, which may also have changed it.
+    Renamed { from: PathBuf, to: PathBuf },
+}
+
+/// The type of a file, in a [`DiffEntry`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DiffFileType {
+    Regular,
+    Directory,
+    Symlink,
+    BlockDevice,
+    CharDevice,
+    Fifo,
+    Socket,
+    Door,
+    EventPort,
+    Unknown,
+}
+
+/// A file or directory which changed, as listed by [`Dataset::diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffEntry {
+    pub change: DiffChange,
+    /// The full path of the file, under the filesystem's mountpoint. For a rename, this is the
+    /// new path.
+    pub path: PathBuf,
+    /// The type of the file, if [`DiffOpts::file_types`] was set.
+    pub file_type: Option<DiffFileType>,
+    /// When the file changed, if [`DiffOpts::timestamps`] was set. This is its change time
+    /// (ctime) in the later snapshot, or in the earlier one if it was removed.
+    pub time: Option<SystemTime>,
+}
+
+impl DiffEntry {
+    // A line is the optional time, the kind of change, the optional file type, the path, and
+    // for a rename, the new path, separated by tabs. A modified directory whose link count
+    // changed also gets the change, like "(+1)", at the end.
+    fn parse(line: &[u8], opts: &DiffOpts) -> Option<Self> {
+        let mut fields = line.split(|&b| b == b'\t');
+
+        let time = if opts.timestamps {
+            let field = std::str::from_utf8(fields.next()?).ok()?.trim();
+            let (secs, nanos) = field.split_once('.')?;
+            let since_epoch = Duration::new(secs.parse().ok()?, nanos.parse().ok()?);
+            Some(UNIX_EPOCH + since_epoch)
+        } else {
+            None
+        };
+
+        let kind = match fields.next()? {
+            [kind] => *kind,
+            _ => return None,
+        };
+
+        let file_type = if opts.file_types {
+            Some(match fields.next()? {
+                b"F" => DiffFileType::Regular,
+                b"/" => DiffFileType::Directory,
+                b"@" => DiffFileType::Symlink,
+                b"B" => DiffFileType::BlockDevice,
+                b"C" => DiffFileType::CharDevice,
+                b"|" => DiffFileType::Fifo,
+                b"=" => DiffFileType::Socket,
+                b">" => DiffFileType::Door,
+                b"P" => DiffFileType::EventPort,
+                _ => DiffFileType::Unknown,
+            })
+        } else {
+            None
+        };
+
+        let path = unescape(fields.next()?)?;
+        let (change, path) = match kind {
+            b'+' => (DiffChange::Added, path),
+            b'-' => (DiffChange::Removed, path),
+            b'M' => (DiffChange::Modified, path),
+            b'R' => {
+                let to = unescape(fields.next()?)?;
+                (DiffChange::Renamed { from: path, to: to.clone() }, to)
+            }
+            _ => return None,
+        };
+        Some(DiffEntry { change, path, file_type, time })
+    }
+}
+
+/// Undo libzfs's escaping of names: bytes which aren't printable ASCII, along with spaces and
+/// backslashes, are written as a backslash and four octal digits.
+fn unescape(field: &[u8]) -> Option<PathBuf> {
+    let mut bytes = Vec::with_capacity(field.len());
+    let mut rest = field;
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'\\' {
+            let digits = std::str::from_utf8(tail.get(..4)?).ok()?;
+            bytes.push(u8::from_str_radix(digits, 8).ok()?);
+            rest = &tail[4..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    Some(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(bytes: &[u8]) -> PathBuf {
+        PathBuf::from(OsString::from_vec(bytes.to_vec()))
+    }
+
+    fn at(secs: u64, nanos: u32) -> Option<SystemTime> {
+        Some(UNIX_EPOCH + Duration::new(secs, nanos))
+    }
+
+    #[test]
+    fn parse_lines() {
+        let plain = DiffOpts::default();
+        let typed = DiffOpts { file_types: true, timestamps: false };
+        let timed = DiffOpts { file_types: true, timestamps: true };
+        let cases: &[(&[u8], &DiffOpts, DiffEntry)] = &[
+            (b"+\t/tank/fs/new", &plain, DiffEntry {
+                change: DiffChange::Added,
+                path: path(b"/tank/fs/new"),
+                file_type: None,
+                time: None,
+            }),
+            (b"-\t/tank/fs/a\\0040b", &plain, DiffEntry {
+                change: DiffChange::Removed,
+                path: path(b"/tank/fs/a b"),
+                file_type: None,
+                time: None,
+            }),
+            // A directory whose link count changed.
+            (b"M\t/\t/tank/fs/dir\t(+1)", &typed, DiffEntry {
+                change: DiffChange::Modified,
+                path: path(b"/tank/fs/dir"),
+                file_type: Some(DiffFileType::Directory),
+                time: None,
+            }),
+            (b"R\tF\t/tank/fs/old\\0011name\t/tank/fs/new\\0134name", &typed, DiffEntry {
+                change: DiffChange::Renamed {
+                    from: path(b"/tank/fs/old\tname"),
+                    to: path(b"/tank/fs/new\\name"),
+                },
+                path: path(b"/tank/fs/new\\name"),
+                file_type: Some(DiffFileType::Regular),
+                time: None,
+            }),
+            (b"1694000000.000000001\t+\t@\t/tank/fs/link", &timed, DiffEntry {
+                change: DiffChange::Added,
+                path: path(b"/tank/fs/link"),
+                file_type: Some(DiffFileType::Symlink),
+                time: at(1_694_000_000, 1),
+            }),
+            // Times are padded to 10 digits of seconds.
+            (b"       123.500000000\t-\t|\t/tank/fs/\\0377fifo", &timed, DiffEntry {
+                change: DiffChange::Removed,
+                path: path(b"/tank/fs/\xfffifo"),
+                file_type: Some(DiffFileType::Fifo),
+                time: at(123, 500_000_000),
+            }),
+            (b"M\t?\t/tank/fs/odd", &typed, DiffEntry {
+                change: DiffChange::Modified,
+                path: path(b"/tank/fs/odd"),
+                file_type: Some(DiffFileType::Unknown),
+                time: None,
+            }),
+        ];
+        for (line, opts, expected) in cases {
+            let entry = DiffEntry::parse(line, opts);
+            assert_eq!(entry.as_ref(), Some(expected), "{}", String::from_utf8_lossy(line));
+        }
+    }
+
+    #[test]
+    fn parse_bad_lines() {
+        let plain = DiffOpts::default();
+        let timed = DiffOpts { file_types: false, timestamps: true };
+        let cases: &[(&[u8], &DiffOpts)] = &[
+            (b"", &plain),
+            (b"X\t/tank/fs/a", &plain),
+            (b"+", &plain),
+            (b"++\t/tank/fs/a", &plain),
+            // A rename without its new path.
+            (b"R\t/tank/fs/a", &plain),
+            // A truncated escape.
+            (b"+\t/tank/fs/a\\004", &plain),
+            (b"+\t/tank/fs/a\\", &plain),
+            // Not octal.
+            (b"+\t/tank/fs/a\\0089", &plain),
+            // A time asked for but missing.
+            (b"+\t/tank/fs/a", &timed),
+            (b"123\t+\t/tank/fs/a", &timed),
+        ];
+        for (line, opts) in cases {
+            assert_eq!(DiffEntry::parse(line, opts), None, "{}", String::from_utf8_lossy(line));
+        }
+    }
+
+    #[test]
+    fn unescape_names() {
+        assert_eq!(unescape(b"plain"), Some(path(b"plain")));
+        assert_eq!(unescape(b"a\\0040b\\0012c"), Some(path(b"a b\nc")));
+        assert_eq!(unescape(b"\\0303\\0251"), Some(path("é".as_bytes())));
+        assert_eq!(unescape(b"end\\0134"), Some(path(b"end\\")));
+        assert_eq!(unescape(b"bad\\12"), None);
+    }
+}
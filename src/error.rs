@@ -1,21 +1,20 @@
 extern crate libzfs_sys as sys;
 
+use std::collections::BTreeMap;
 use std::ffi::{CStr};
 use std::fmt;
 use std::mem::transmute;
 
+use crate::SafeString;
+
 #[macro_export]
 macro_rules! ztry {
     ($stuff:expr, $lzfs:expr) => {
         {
+            let mark = $crate::ErrorMark::new($lzfs);
             let result = $stuff;
             if result != 0 {
-                let e = ZfsError::last_error($lzfs);
-                if matches!(e.code, sys::zfs_error::EZFS_UNKNOWN | sys::zfs_error::EZFS_SUCCESS) {
-                    return Err(Error::Sys(std::io::Error::last_os_error()));
-                } else {
-                    return Err(Error::Zfs(e));
-                }
+                return Err(mark.error($lzfs));
             }
             result
         }
@@ -31,6 +30,17 @@ pub struct ZfsError {
 }
 
 impl ZfsError {
+    /// Construct an error with the given code and message, such as for simulating libzfs failures
+    /// in tests.
+    pub fn new(code: sys::zfs_error, msg: impl Into<String>) -> Self {
+        ZfsError { code, msg: msg.into() }
+    }
+
+    /// Construct an error with the given code and an empty message.
+    pub fn with_code(code: sys::zfs_error) -> Self {
+        ZfsError::new(code, String::new())
+    }
+
     pub(crate) fn last_error(handle: *mut sys::libzfs_handle_t) -> Self {
         let code: sys::zfs_error = unsafe { transmute(sys::libzfs_errno(handle)) };
         let msg_cstr = unsafe { CStr::from_ptr(sys::libzfs_error_description(handle)) };
@@ -47,10 +57,103 @@ impl fmt::Display for ZfsError {
     }
 }
 
+/// An error from a libzfs operation.
+///
+/// More variants may be added for failures which callers need to tell apart, so matches need a
+/// wildcard arm. [`Error::is_out_of_memory`] classifies errors which may go away if retried.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     Sys(::std::io::Error),
     Zfs(ZfsError),
+    /// An operation on several devices, like TRIM, failed for some of them. Keyed by device
+    /// path, or GUID for devices without one.
+    Vdevs(BTreeMap<SafeString, ZfsError>),
+    /// Setting several properties at once failed for some of them. Keyed by property name, with
+    /// the system error the kernel gave for each.
+    Properties(BTreeMap<SafeString, ::std::io::Error>),
+    /// An operation on several datasets, like unmounting a filesystem and its descendants,
+    /// failed for some of them. Keyed by dataset name; the others were done.
+    Datasets(BTreeMap<SafeString, Error>),
+    /// Memory couldn't be allocated, either by libzfs (`EZFS_NOMEM`) or by the system
+    /// (`ENOMEM`). See [`Error::is_out_of_memory`].
+    NoMem,
+    /// There's no partially received state in the named dataset to abort. See
+    /// [`Dataset::abort_partial_receive`](crate::Dataset::abort_partial_receive).
+    NoPartialReceive(SafeString),
+    /// A stream can't be received into the named dataset because it has partially received state
+    /// from an interrupted receive, which has to be resumed or aborted first.
+    PartialReceiveExists(SafeString),
+}
+
+/// A handle whose libzfs error has been reset before a call, to tell whether a failed call set a
+/// new one.
+///
+/// libzfs doesn't clear a handle's error when a call succeeds, or when a call fails without
+/// setting one (like allocation failures, or calls into libzfs_core, which are only described by
+/// errno). So the error left from an earlier failure could otherwise be mistaken for the cause.
+/// Take a mark right before the call, and if it fails, get the error from the mark right after.
+pub(crate) struct ErrorMark(());
+
+impl ErrorMark {
+    pub(crate) fn new(libzfs: *mut sys::libzfs_handle_t) -> Self {
+        // libzfs has no call for clearing the error, but describing errno 0 sets it to
+        // EZFS_UNKNOWN, the code libzfs uses for failures which only errno describes. Any other
+        // code afterwards was set by the call.
+        unsafe { sys::zfs_standard_error(libzfs, 0, c"".as_ptr()) };
+        ErrorMark(())
+    }
+
+    /// The error from the libzfs call which just failed: the libzfs error if it set one, or the
+    /// system error otherwise. This must be called right after the failed call, before errno can
+    /// change.
+    pub(crate) fn error(self, libzfs: *mut sys::libzfs_handle_t) -> Error {
+        let os_error = std::io::Error::last_os_error();
+        Error::from_failure(ZfsError::last_error(libzfs), os_error)
+    }
+}
+
+impl Error {
+    /// Pick the error for a failed call, from the libzfs error after it, and errno.
+    fn from_failure(zfs_error: ZfsError, os_error: std::io::Error) -> Error {
+        let unset = matches!(
+            zfs_error.code,
+            sys::zfs_error::EZFS_UNKNOWN | sys::zfs_error::EZFS_SUCCESS
+        );
+        if unset {
+            if os_error.raw_os_error() == Some(libc::ENOMEM) {
+                Error::NoMem
+            } else {
+                Error::Sys(os_error)
+            }
+        } else if zfs_error.code == sys::zfs_error::EZFS_NOMEM {
+            Error::NoMem
+        } else {
+            Error::Zfs(zfs_error)
+        }
+    }
+
+    /// Whether the operation failed because memory couldn't be allocated, either by libzfs
+    /// (`EZFS_NOMEM`) or by the system (`ENOMEM`). Besides [`Error::NoMem`], this covers the
+    /// errors of any devices in [`Error::Vdevs`], properties in [`Error::Properties`], or
+    /// datasets in [`Error::Datasets`] which ran out of memory.
+    ///
+    /// Unlike most errors, this is usually temporary: the operation may succeed if retried after
+    /// backing off. Operations on multiple items may have partially completed, so check what
+    /// still needs to be done before retrying.
+    pub fn is_out_of_memory(&self) -> bool {
+        match self {
+            Error::NoMem => true,
+            Error::Zfs(e) => e.code == sys::zfs_error::EZFS_NOMEM,
+            Error::Sys(e) => e.raw_os_error() == Some(libc::ENOMEM),
+            Error::Vdevs(errors) => errors.values().any(|e| e.code == sys::zfs_error::EZFS_NOMEM),
+            Error::Properties(errors) => {
+                errors.values().any(|e| e.raw_os_error() == Some(libc::ENOMEM))
+            }
+            Error::Datasets(errors) => errors.values().any(Error::is_out_of_memory),
+            Error::NoPartialReceive(_) | Error::PartialReceiveExists(_) => false,
+        }
+    }
 }
 
 impl ::std::error::Error for Error {
@@ -58,6 +161,12 @@ impl ::std::error::Error for Error {
         match self {
             Error::Sys(e) => Some(e),
             Error::Zfs(e) => Some(e),
+            Error::Vdevs(_)
+            | Error::Properties(_)
+            | Error::Datasets(_)
+            | Error::NoMem
+            | Error::NoPartialReceive(_)
+            | Error::PartialReceiveExists(_) => None,
         }
     }
 }
@@ -67,6 +176,35 @@ impl fmt::Display for Error {
         match *self {
             Error::Sys(ref e) => e.fmt(f),
             Error::Zfs(ref e) => e.fmt(f),
+            Error::Vdevs(ref errors) => {
+                write!(f, "{} device(s) failed:", errors.len())?;
+                for (name, e) in errors {
+                    write!(f, " {}: {};", name, e)?;
+                }
+                Ok(())
+            }
+            Error::Properties(ref errors) => {
+                write!(f, "setting {} properties failed:", errors.len())?;
+                for (name, e) in errors {
+                    write!(f, " {}: {};", name, e)?;
+                }
+                Ok(())
+            }
+            Error::Datasets(ref errors) => {
+                write!(f, "{} dataset(s) failed:", errors.len())?;
+                for (name, e) in errors {
+                    write!(f, " {}: {};", name, e)?;
+                }
+                Ok(())
+            }
+            Error::NoMem => f.write_str("out of memory"),
+            Error::NoPartialReceive(ref name) => {
+                write!(f, "'{}' does not have any partially received state to abort", name)
+            }
+            Error::PartialReceiveExists(ref name) => {
+                write!(f, "'{}' has partially received state from an interrupted receive; \
+                    resume or abort it first", name)
+            }
         }
     }
 }
@@ -76,3 +214,46 @@ impl From<ZfsError> for Error {
         Error::Zfs(z)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn os_error(errno: i32) -> std::io::Error {
+        std::io::Error::from_raw_os_error(errno)
+    }
+
+    #[test]
+    fn failure_with_libzfs_error() {
+        let zfs_error = ZfsError::new(sys::zfs_error::EZFS_NOENT, "no such pool");
+        match Error::from_failure(zfs_error.clone(), os_error(libc::EIO)) {
+            Error::Zfs(e) => assert_eq!(e, zfs_error),
+            other => panic!("expected the libzfs error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn failure_without_libzfs_error() {
+        for code in [sys::zfs_error::EZFS_SUCCESS, sys::zfs_error::EZFS_UNKNOWN] {
+            let e = Error::from_failure(ZfsError::with_code(code), os_error(libc::EIO));
+            assert!(matches!(e, Error::Sys(ref e) if e.raw_os_error() == Some(libc::EIO)));
+        }
+    }
+
+    #[test]
+    fn out_of_memory() {
+        let nomem = ZfsError::new(sys::zfs_error::EZFS_NOMEM, "out of memory");
+        let e = Error::from_failure(nomem, os_error(libc::EIO));
+        assert!(matches!(e, Error::NoMem));
+        assert!(e.is_out_of_memory());
+
+        let unset = ZfsError::with_code(sys::zfs_error::EZFS_UNKNOWN);
+        let e = Error::from_failure(unset, os_error(libc::ENOMEM));
+        assert!(matches!(e, Error::NoMem));
+        assert!(e.is_out_of_memory());
+
+        let busy = ZfsError::with_code(sys::zfs_error::EZFS_BUSY);
+        let e = Error::from_failure(busy, os_error(libc::EIO));
+        assert!(!e.is_out_of_memory());
+    }
+}
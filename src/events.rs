@@ -0,0 +1,305 @@
+use libzfs_sys as sys;
+
+use std::ffi::CStr;
+use std::fs::OpenOptions;
+use std::mem;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::raw::c_int;
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Once, PoisonError};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::nvlist::{NvList, OwnedNvList};
+use crate::{ztry, Error, ErrorMark, LibZfs, Result, SafeString};
+
+/// Options for reading ZFS events.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EventOpts {
+    /// Once all queued events have been read, keep waiting for new ones instead of ending the
+    /// stream (`zpool events -f`). The stream then only ends when it's
+    /// [interrupted](EventInterrupter::interrupt).
+    pub follow: bool,
+}
+
+/// An event posted by ZFS, such as a device fault or the end of a resilver.
+#[derive(Debug)]
+pub struct ZfsEvent {
+    /// The event's class, like "sysevent.fs.zfs.resilver_finish" or "ereport.fs.zfs.io".
+    pub class: SafeString,
+    /// The event's ID, which increases with each event.
+    pub eid: u64,
+    pub time: SystemTime,
+    pub pool: Option<SafeString>,
+    pub pool_guid: Option<u64>,
+    pub vdev_guid: Option<u64>,
+    pub vdev_path: Option<PathBuf>,
+    pub vdev_type: Option<SafeString>,
+    payload: OwnedNvList,
+}
+
+impl ZfsEvent {
+    fn new(payload: OwnedNvList) -> Self {
+        let nvl = payload.borrow();
+        let time = match nvl.lookup_i64_array("time") {
+            Some(&[sec, nsec]) => event_time(sec, nsec),
+            _ => UNIX_EPOCH,
+        };
+        ZfsEvent {
+            class: nvl.lookup_string("class").unwrap_or_else(|| SafeString::from("")),
+            eid: nvl.lookup_u64("eid").unwrap_or(0),
+            time,
+            pool: nvl.lookup_string("pool"),
+            pool_guid: nvl.lookup_u64("pool_guid"),
+            vdev_guid: nvl.lookup_u64("vdev_guid"),
+            vdev_path: nvl.lookup_string("vdev_path").map(|p| PathBuf::from(String::from(p))),
+            vdev_type: nvl.lookup_string("vdev_type"),
+            payload,
+        }
+    }
+
+    /// The names of all the event's members, including the ones with their own fields.
+    pub fn keys(&self) -> Vec<SafeString> {
+        self.payload.borrow().names().collect()
+    }
+
+    /// Look up a numeric member of the event.
+    pub fn get_u64(&self, name: &str) -> Option<u64> {
+        self.payload.borrow().lookup_u64(name)
+    }
+
+    /// Look up a string member of the event.
+    pub fn get_string(&self, name: &str) -> Option<SafeString> {
+        self.payload.borrow().lookup_string(name)
+    }
+
+    /// The whole event, for reading members of other types, like the nested nvlists and arrays
+    /// in ereports.
+    pub fn payload(&self) -> NvList<'_> {
+        self.payload.borrow()
+    }
+}
+
+// The time an event was posted, or the epoch if it's out of range.
+fn event_time(sec: i64, nsec: i64) -> SystemTime {
+    let since_epoch = Duration::from_secs(sec.unsigned_abs());
+    let time = if sec >= 0 {
+        UNIX_EPOCH.checked_add(since_epoch)
+    } else {
+        UNIX_EPOCH.checked_sub(since_epoch)
+    };
+    let nsec = Duration::from_nanos(nsec.clamp(0, 999_999_999) as u64);
+    time.and_then(|time| time.checked_add(nsec)).unwrap_or(UNIX_EPOCH)
+}
+
+// The signal which wakes a thread waiting in the kernel for events, so that it sees it's been
+// interrupted: the waiting ioctl can't be woken any other way. The handler does nothing, and
+// is installed without SA_RESTART, so the ioctl fails with EINTR.
+fn interrupt_signal() -> c_int {
+    libc::SIGRTMAX()
+}
+
+static INSTALL_HANDLER: Once = Once::new();
+
+extern "C" fn ignore_signal(_: c_int) {}
+
+fn install_interrupt_handler() {
+    INSTALL_HANDLER.call_once(|| unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = ignore_signal as extern "C" fn(c_int) as libc::sighandler_t;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(interrupt_signal(), &action, ptr::null_mut());
+    });
+}
+
+#[derive(Debug, Default)]
+struct Interrupt {
+    interrupted: AtomicBool,
+    // The thread waiting in the kernel for an event, if any.
+    waiting: Mutex<Option<libc::pthread_t>>,
+}
+
+/// Stops an [`EventStream`] from another thread.
+#[derive(Debug, Clone)]
+pub struct EventInterrupter(Arc<Interrupt>);
+
+impl EventInterrupter {
+    /// Make the stream end, waking it if it's waiting for new events. Any event already read
+    /// from the kernel is still returned first.
+    ///
+    /// This wakes the waiting thread with a signal, `SIGRTMAX`, which the stream installs a
+    /// handler for that does nothing.
+    pub fn interrupt(&self) {
+        self.0.interrupted.store(true, Ordering::SeqCst);
+        // The signal can arrive just before the thread starts waiting, so keep sending it until
+        // the thread has stopped.
+        loop {
+            let waiting = *self.0.waiting.lock().unwrap_or_else(PoisonError::into_inner);
+            match waiting {
+                Some(thread) => unsafe { libc::pthread_kill(thread, interrupt_signal()) },
+                None => break,
+            };
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+/// An iterator over ZFS events. Create one with [`LibZfs::events`].
+#[derive(Debug)]
+pub struct EventStream<'a> {
+    libzfs: &'a LibZfs,
+    fd: OwnedFd,
+    follow: bool,
+    interrupt: Arc<Interrupt>,
+    dropped: u64,
+}
+
+impl<'a> EventStream<'a> {
+    /// Get a handle which can stop this stream from another thread.
+    pub fn interrupter(&self) -> EventInterrupter {
+        EventInterrupter(Arc::clone(&self.interrupt))
+    }
+
+    /// Skip ahead to the event with the given ID, so it's the next one returned.
+    pub fn seek(&mut self, eid: u64) -> Result<()> {
+        ztry!(unsafe {
+            sys::zpool_events_seek(self.libzfs.handle, eid, self.fd.as_raw_fd())
+        }, self.libzfs.handle);
+        Ok(())
+    }
+
+    /// How many events were lost because they were discarded before being read, so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    fn next_event(&mut self) -> Result<Option<ZfsEvent>> {
+        // When following, wait in the kernel for the next event. Otherwise, end the stream once
+        // there are none left.
+        let flags = if self.follow { sys::ZEVENT_NONE } else { sys::ZEVENT_NONBLOCK };
+        loop {
+            {
+                let mut waiting = self.interrupt.waiting.lock()
+                    .unwrap_or_else(PoisonError::into_inner);
+                if self.interrupt.interrupted.load(Ordering::SeqCst) {
+                    return Ok(None);
+                }
+                if self.follow {
+                    *waiting = Some(unsafe { libc::pthread_self() });
+                }
+            }
+            let mut nvl: *mut sys::nvlist_t = ptr::null_mut();
+            let mut dropped: c_int = 0;
+            let mark = ErrorMark::new(self.libzfs.handle);
+            let result = unsafe {
+                sys::zpool_events_next(
+                    self.libzfs.handle,
+                    &mut nvl,
+                    &mut dropped,
+                    flags,
+                    self.fd.as_raw_fd(),
+                )
+            };
+            let error = (result != 0).then(|| mark.error(self.libzfs.handle));
+            *self.interrupt.waiting.lock().unwrap_or_else(PoisonError::into_inner) = None;
+            match error {
+                // Woken by the interrupter, or some other signal; check which.
+                Some(e) if is_interrupted(&e) => continue,
+                Some(e) => return Err(e),
+                None => (),
+            }
+            self.dropped += dropped as u64;
+            if !nvl.is_null() {
+                return Ok(Some(ZfsEvent::new(unsafe { OwnedNvList::from_raw(nvl) })));
+            }
+            if !self.follow {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+// libzfs reports an interrupted wait as EZFS_INTR, or with just errno.
+fn is_interrupted(e: &Error) -> bool {
+    match e {
+        Error::Zfs(e) => e.code == sys::zfs_error::EZFS_INTR,
+        Error::Sys(e) => e.raw_os_error() == Some(libc::EINTR),
+        _ => false,
+    }
+}
+
+impl<'a> Iterator for EventStream<'a> {
+    type Item = Result<ZfsEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+impl LibZfs {
+    /// Read events posted by ZFS (like `zpool events`), starting with the oldest ones still
+    /// queued.
+    ///
+    /// For example, to react to checksum errors as they happen:
+    ///
+    /// ```no_run
+    /// # use libzfs::{EventOpts, LibZfs};
+    /// # fn f(libzfs: &LibZfs) -> libzfs::Result<()> {
+    /// let opts = EventOpts { follow: true, ..Default::default() };
+    /// for event in libzfs.events(opts)? {
+    ///     let event = event?;
+    ///     if AsRef::<str>::as_ref(&event.class) == "ereport.fs.zfs.checksum" {
+    ///         println!("checksum error on {:?} in pool {:?}", event.vdev_path, event.pool);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn events(&self, opts: EventOpts) -> Result<EventStream<'_>> {
+        if opts.follow {
+            install_interrupt_handler();
+        }
+        let dev = CStr::from_bytes_with_nul(sys::ZFS_DEV).expect("bad ZFS_DEV");
+        let fd: OwnedFd = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&*dev.to_string_lossy())
+            .map_err(Error::Sys)?
+            .into();
+        Ok(EventStream {
+            libzfs: self,
+            fd,
+            follow: opts.follow,
+            interrupt: Arc::new(Interrupt::default()),
+            dropped: 0,
+        })
+    }
+
+    /// Discard all queued events (like `zpool events -c`), returning how many were cleared.
+    pub fn clear_events(&self) -> Result<u64> {
+        let mut count: c_int = 0;
+        ztry!(unsafe { sys::zpool_events_clear(self.handle, &mut count) }, self.handle);
+        Ok(count as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_times() {
+        assert_eq!(event_time(0, 0), UNIX_EPOCH);
+        assert_eq!(event_time(10, 5), UNIX_EPOCH + Duration::new(10, 5));
+        assert_eq!(event_time(-10, 0), UNIX_EPOCH - Duration::from_secs(10));
+        assert_eq!(event_time(-10, 5), UNIX_EPOCH - Duration::new(9, 999_999_995));
+        assert_eq!(event_time(1, -1), UNIX_EPOCH + Duration::from_secs(1));
+        assert_eq!(event_time(1, 2_000_000_000), UNIX_EPOCH + Duration::new(1, 999_999_999));
+        // Whether these are in SystemTime's range depends on the platform, but they mustn't
+        // panic.
+        event_time(i64::MAX, 999_999_999);
+        event_time(i64::MIN, 999_999_999);
+    }
+}
@@ -0,0 +1,97 @@
+use libzfs_sys as sys;
+
+use std::ptr;
+
+use crate::nvlist::{NvList, OwnedNvList};
+use crate::{ztry, Result, SafeString, ZPool};
+
+/// Options for reading a pool's history.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HistoryOpts {
+    /// Include internally logged events, such as individual property changes and dataset
+    /// creations done as part of a command (`zpool history -i`).
+    pub internal: bool,
+    /// Include who ran each command, and where (`zpool history -l`).
+    pub long: bool,
+    /// Byte offset into the history log to start reading from. The log is a ring buffer, so use
+    /// the offset returned by [`ZPool::history_from`] to read only records added since.
+    pub offset: u64,
+}
+
+/// A record from a pool's history log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEvent {
+    /// When the event happened, in seconds since the Unix epoch.
+    pub time: u64,
+    /// The command line, for commands run by a user (like `zfs create pool/fs`).
+    pub command: Option<SafeString>,
+    /// The name of the internal event (like "create" or "set"), for internal events.
+    pub internal_name: Option<SafeString>,
+    /// The dataset an internal event applies to, if any.
+    pub dataset: Option<SafeString>,
+    /// Details of an internal event, like the property that was set.
+    pub details: Option<SafeString>,
+    /// The name of the ioctl, for internal records of ioctls.
+    pub ioctl: Option<SafeString>,
+    /// The transaction group an internal event happened in.
+    pub txg: Option<u64>,
+    /// The user ID which ran the command. Only set with [`HistoryOpts::long`].
+    pub uid: Option<u64>,
+    /// The host the command was run on. Only set with [`HistoryOpts::long`].
+    pub hostname: Option<SafeString>,
+    /// The zone the command was run in. Only set with [`HistoryOpts::long`].
+    pub zone: Option<SafeString>,
+}
+
+impl HistoryEvent {
+    fn from_record(record: NvList<'_>, long: bool) -> HistoryEvent {
+        let long_field = |name| if long { record.lookup_string(name) } else { None };
+        HistoryEvent {
+            time: record.lookup_u64("history time").unwrap_or(0),
+            command: record.lookup_string("history command"),
+            internal_name: record.lookup_string("internal_name"),
+            dataset: record.lookup_string("dsname"),
+            details: record.lookup_string("history internal str"),
+            ioctl: record.lookup_string("ioctl"),
+            txg: record.lookup_u64("history txg"),
+            uid: record.lookup_u64("history who").filter(|_| long),
+            hostname: long_field("history hostname"),
+            zone: long_field("history zone"),
+        }
+    }
+}
+
+impl ZPool {
+    /// Read the pool's history log (like `zpool history`), from the start of the log or the
+    /// given offset.
+    pub fn history(&self, opts: HistoryOpts) -> Result<Vec<HistoryEvent>> {
+        self.history_from(opts).map(|(events, _)| events)
+    }
+
+    /// Read the pool's history log, like [`ZPool::history`], also returning the offset of the end
+    /// of the log. Pass that as [`HistoryOpts::offset`] next time to get only newer records.
+    pub fn history_from(&self, opts: HistoryOpts) -> Result<(Vec<HistoryEvent>, u64)> {
+        let mut events = vec![];
+        let mut offset = opts.offset;
+        let mut eof: sys::boolean_t = 0;
+        while eof == 0 {
+            let mut nvl: *mut sys::nvlist_t = ptr::null_mut();
+            ztry!(unsafe {
+                sys::zpool_get_history(self.handle, &mut nvl, &mut offset, &mut eof)
+            }, self.libzfs);
+            if nvl.is_null() {
+                break;
+            }
+            let history = unsafe { OwnedNvList::from_raw(nvl) };
+            let records = history.borrow().lookup_nvlist_array("history record");
+            for record in records.unwrap_or_default() {
+                let event = HistoryEvent::from_record(record, opts.long);
+                // Internal records don't have a command.
+                if event.command.is_some() || opts.internal {
+                    events.push(event);
+                }
+            }
+        }
+        Ok((events, offset))
+    }
+}
@@ -0,0 +1,314 @@
+use libzfs_sys as sys;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::marker::PhantomData;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::raw::c_int;
+use std::ptr;
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::nvlist::OwnedNvList;
+use crate::{ztry, Dataset, DatasetType, Error, ErrorMark, Result, SafeString, ZfsError};
+
+// MAXNAMELEN, including the terminating NUL
+const MAX_TAG_LEN: usize = 255;
+// The kernel prefixes temporary hold tags with the cleanup fd's minor number (MAX_TAG_PREFIX_LEN
+// bytes), and the result must still fit in MAXNAMELEN.
+const MAX_TEMP_TAG_LEN: usize = MAX_TAG_LEN - 17;
+
+// The cleanup file descriptors for the temporary holds placed with each libzfs handle, keyed by
+// the handle's address. Each is closed once the last of its holds is released.
+static CLEANUP_FDS: Mutex<BTreeMap<usize, Weak<OwnedFd>>> = Mutex::new(BTreeMap::new());
+
+/// Check that a hold tag is one the kernel accepts: any characters, but not empty, and no longer
+/// than `max_len`.
+fn validate_tag(tag: &str, max_len: usize) -> Result<()> {
+    if tag.is_empty() {
+        return Err(Error::Zfs(ZfsError::new(
+            sys::zfs_error::EZFS_INVALIDNAME,
+            "hold tag can't be empty",
+        )));
+    }
+    if tag.len() > max_len {
+        return Err(Error::Zfs(ZfsError::new(
+            sys::zfs_error::EZFS_TAGTOOLONG,
+            format!("hold tag is longer than {} bytes", max_len),
+        )));
+    }
+    Ok(())
+}
+
+impl Dataset {
+    /// Put a user hold on this snapshot (like `zfs hold`), which stops it from being destroyed
+    /// until the hold is released. With `recursive`, the same-named snapshots of all descendent
+    /// filesystems are held too, atomically.
+    ///
+    /// A snapshot can have any number of holds, each with its own tag. Tags can contain any
+    /// characters, but must be between 1 and 255 bytes long. Fails with `EZFS_REFTAG_HOLD` if
+    /// the snapshot already has a hold with this tag, and with `EZFS_BADTYPE` if this isn't a
+    /// snapshot.
+    pub fn hold(&self, tag: &str, recursive: bool) -> Result<()> {
+        validate_tag(tag, MAX_TAG_LEN)?;
+        let (fs, snap) = self.snapshot_fs("held")?;
+        let tag = SafeString::from(tag);
+        ztry!(unsafe {
+            sys::zfs_hold(
+                fs.handle,
+                snap.as_ptr(),
+                tag.as_ptr(),
+                recursive as sys::boolean_t,
+                -1, // cleanup_fd: none, so the hold lasts until it's released
+            )
+        }, self.libzfs);
+        Ok(())
+    }
+
+    /// Release a user hold on this snapshot (like `zfs release`). With `recursive`, the holds
+    /// with the same tag on the same-named snapshots of all descendent filesystems are released
+    /// too.
+    ///
+    /// If the snapshot was destroyed with `defer` while it was held, releasing its last hold
+    /// destroys it:
+    ///
+    /// ```no_run
+    /// # use libzfs::*;
+    /// # fn main() -> Result<()> {
+    /// let libzfs = LibZfs::new()?;
+    /// let name = SafeString::from("tank/data@backup");
+    /// let snap = libzfs.dataset_by_name(&name, DatasetType::Snapshot.into())?;
+    /// snap.hold("replication", false)?;
+    /// // The snapshot can't be destroyed while it's held, but it can be marked for destruction.
+    /// libzfs.destroy_snapshots_deferred([&name].iter())?;
+    /// snap.release("replication", false)?;
+    /// assert!(libzfs.dataset_by_name(&name, DatasetType::Snapshot.into()).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Fails with `EZFS_REFTAG_RELE` if the snapshot has no hold with this tag, and with
+    /// `EZFS_BADTYPE` if this isn't a snapshot.
+    pub fn release(&self, tag: &str, recursive: bool) -> Result<()> {
+        let (fs, snap) = self.snapshot_fs("released")?;
+        let ctag = SafeString::from(tag);
+        let mark = ErrorMark::new(self.libzfs);
+        let result = unsafe {
+            sys::zfs_release(fs.handle, snap.as_ptr(), ctag.as_ptr(), recursive as sys::boolean_t)
+        };
+        if result != 0 {
+            return Err(match mark.error(self.libzfs) {
+                // libzfs doesn't say which hold was missing.
+                Error::Zfs(e) if e.code == sys::zfs_error::EZFS_REFTAG_RELE => {
+                    Error::Zfs(ZfsError::new(
+                        e.code,
+                        format!("'{}' has no hold with tag '{}'", self.get_name(), tag),
+                    ))
+                }
+                e => e,
+            });
+        }
+        Ok(())
+    }
+
+    /// Get the tags of the user holds on this snapshot, and when each was placed. This is empty
+    /// if the snapshot isn't held. Fails with `EZFS_BADTYPE` if this isn't a snapshot.
+    pub fn holds(&self) -> Result<BTreeMap<SafeString, SystemTime>> {
+        if self.get_type() != DatasetType::Snapshot {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADTYPE,
+                "only snapshots have holds",
+            )));
+        }
+        let mut nvl: *mut sys::nvlist_t = ptr::null_mut();
+        ztry!(unsafe { sys::zfs_get_holds(self.handle, &mut nvl) }, self.libzfs);
+        let nvl = unsafe { OwnedNvList::from_raw(nvl) };
+        let nvl = nvl.borrow();
+        // Each hold's tag maps to when it was placed, in seconds since the Unix epoch.
+        Ok(nvl.names()
+            .map(|tag| {
+                let time = nvl.lookup_u64(tag.as_ref()).unwrap_or(0);
+                (tag, UNIX_EPOCH + Duration::from_secs(time))
+            })
+            .collect())
+    }
+
+    /// Get the user holds on all snapshots of this filesystem or volume, keyed by the
+    /// snapshots' full names. Snapshots which aren't held are left out.
+    pub fn holds_recursive(&self)
+        -> Result<BTreeMap<SafeString, BTreeMap<SafeString, SystemTime>>>
+    {
+        let mut all = BTreeMap::new();
+        for snapshot in self.get_snapshots()? {
+            let holds = snapshot.holds()?;
+            if !holds.is_empty() {
+                all.insert(snapshot.get_name(), holds);
+            }
+        }
+        Ok(all)
+    }
+
+    /// Get a handle on the filesystem or volume this snapshot is of, and the snapshot's short
+    /// name, which is how libzfs holds and releases snapshots.
+    fn snapshot_fs(&self, verb: &str) -> Result<(Dataset, SafeString)> {
+        let name = self.get_name();
+        let (fs, snap) = match AsRef::<str>::as_ref(&name).split_once('@') {
+            Some((fs, snap)) if self.get_type() == DatasetType::Snapshot => (fs, snap),
+            _ => return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADTYPE,
+                format!("only snapshots can be {}", verb),
+            ))),
+        };
+        let fs = SafeString::from(fs);
+        let types = sys::zfs_type_t::ZFS_TYPE_FILESYSTEM | sys::zfs_type_t::ZFS_TYPE_VOLUME;
+        let mark = ErrorMark::new(self.libzfs);
+        let handle = unsafe { sys::zfs_open(self.libzfs, fs.as_ptr(), types as c_int) };
+        if handle.is_null() {
+            return Err(mark.error(self.libzfs));
+        }
+        Ok((Dataset { libzfs: self.libzfs, handle }, SafeString::from(snap)))
+    }
+}
+
+impl Dataset {
+    /// Put a user hold on this snapshot which only lasts as long as the returned [`TempHold`]:
+    /// it's released when that is dropped, or by the kernel if the process dies first (like
+    /// the holds `zfs send` puts on the snapshots it sends). This protects a snapshot while an
+    /// operation on it is in flight, without leaving a hold behind if the process is killed.
+    ///
+    /// The hold is registered against a cleanup file descriptor, which the kernel releases the
+    /// hold on when it's closed. The temporary holds placed with the same [`LibZfs`] handle
+    /// share one, which is closed once none of them are left.
+    ///
+    /// Fails with `EZFS_REFTAG_HOLD` if the snapshot already has a hold with this tag, and with
+    /// `EZFS_BADTYPE` if this isn't a snapshot. Tags can contain any characters, but must be
+    /// between 1 and 238 bytes long, shorter than for [`Dataset::hold`] because the kernel adds
+    /// a prefix to them.
+    ///
+    /// [`LibZfs`]: crate::LibZfs
+    pub fn hold_temporary(&self, tag: &str) -> Result<TempHold<'_>> {
+        validate_tag(tag, MAX_TEMP_TAG_LEN)?;
+        if self.get_type() != DatasetType::Snapshot {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADTYPE,
+                "only snapshots can be held",
+            )));
+        }
+        let snapshot = self.get_name();
+        let cleanup_fd = cleanup_fd(self.libzfs)?;
+
+        let mut holds = OwnedNvList::new();
+        holds.add_string(snapshot.as_ref(), tag);
+        let mut errors: *mut sys::nvlist_t = ptr::null_mut();
+        let err = unsafe { sys::lzc_hold(holds.as_ptr(), cleanup_fd.as_raw_fd(), &mut errors) };
+        let errors = (!errors.is_null()).then(|| unsafe { OwnedNvList::from_raw(errors) });
+        if err != 0 {
+            // The errno for the snapshot itself, if it was to blame, is more specific.
+            let errno = errors.as_ref()
+                .and_then(|errors| errors.borrow().lookup_i32(snapshot.as_ref()))
+                .unwrap_or(err);
+            return Err(match errno {
+                libc::EEXIST => Error::Zfs(ZfsError::new(
+                    sys::zfs_error::EZFS_REFTAG_HOLD,
+                    format!("'{}' already has a hold with tag '{}'", snapshot, tag),
+                )),
+                libc::E2BIG | libc::ENAMETOOLONG => Error::Zfs(ZfsError::new(
+                    sys::zfs_error::EZFS_TAGTOOLONG,
+                    format!("hold tag '{}' is too long", tag),
+                )),
+                _ => Error::Sys(io::Error::from_raw_os_error(errno)),
+            });
+        }
+
+        Ok(TempHold {
+            _dataset: PhantomData,
+            snapshot,
+            tag: SafeString::from(tag),
+            _cleanup_fd: cleanup_fd,
+            held: true,
+        })
+    }
+}
+
+/// Get the cleanup file descriptor shared by the temporary holds placed with a libzfs handle,
+/// opening one if there isn't one yet.
+fn cleanup_fd(libzfs: *mut sys::libzfs_handle_t) -> Result<Arc<OwnedFd>> {
+    let mut fds = CLEANUP_FDS.lock().unwrap_or_else(PoisonError::into_inner);
+    fds.retain(|_, fd| fd.strong_count() > 0);
+    if let Some(fd) = fds.get(&(libzfs as usize)).and_then(Weak::upgrade) {
+        return Ok(fd);
+    }
+    let file = File::options().read(true).write(true).open("/dev/zfs").map_err(Error::Sys)?;
+    let fd = Arc::new(OwnedFd::from(file));
+    fds.insert(libzfs as usize, Arc::downgrade(&fd));
+    Ok(fd)
+}
+
+/// A user hold on a snapshot, which is released when this is dropped. Create one with
+/// [`Dataset::hold_temporary`].
+#[derive(Debug)]
+pub struct TempHold<'a> {
+    _dataset: PhantomData<&'a Dataset>,
+    snapshot: SafeString,
+    tag: SafeString,
+    // The kernel releases the hold when the last reference to this is dropped and it's closed,
+    // if it's still held then.
+    _cleanup_fd: Arc<OwnedFd>,
+    held: bool,
+}
+
+impl<'a> TempHold<'a> {
+    /// The full name of the held snapshot.
+    pub fn snapshot(&self) -> &SafeString {
+        &self.snapshot
+    }
+
+    /// The hold's tag.
+    pub fn tag(&self) -> &SafeString {
+        &self.tag
+    }
+
+    /// Release the hold now, reporting any error, which dropping this doesn't. If the snapshot
+    /// was destroyed with `defer` while it was held, releasing its last hold destroys it.
+    ///
+    /// Fails with `EZFS_REFTAG_RELE` if the hold was already released some other way, like with
+    /// [`Dataset::release`].
+    pub fn release(mut self) -> Result<()> {
+        self.held = false;
+        self.release_hold()
+    }
+
+    fn release_hold(&self) -> Result<()> {
+        let mut tags = OwnedNvList::new();
+        tags.add_boolean(self.tag.as_ref());
+        let mut holds = OwnedNvList::new();
+        holds.add_nvlist(self.snapshot.as_ref(), &tags);
+        let mut errors: *mut sys::nvlist_t = ptr::null_mut();
+        let err = unsafe { sys::lzc_release(holds.as_ptr(), &mut errors) };
+        let errors = (!errors.is_null()).then(|| unsafe { OwnedNvList::from_raw(errors) });
+        if err == 0 {
+            return Ok(());
+        }
+        let errno = errors.as_ref()
+            .and_then(|errors| errors.borrow().lookup_i32(self.snapshot.as_ref()))
+            .unwrap_or(err);
+        Err(match errno {
+            libc::ESRCH => Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_REFTAG_RELE,
+                format!("'{}' has no hold with tag '{}'", self.snapshot, self.tag),
+            )),
+            _ => Error::Sys(io::Error::from_raw_os_error(errno)),
+        })
+    }
+}
+
+impl<'a> Drop for TempHold<'a> {
+    fn drop(&mut self) {
+        if self.held {
+            // Nothing to be done about an error here; the hold is released when the cleanup
+            // file descriptor is closed anyway, if this was the last one using it.
+            let _ = self.release_hold();
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use libzfs_sys as sys;
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_void};
+use std::path::PathBuf;
+use std::ptr;
+
+use crate::nvlist::{NvList, OwnedNvList};
+use crate::{ztry, Error, ErrorMark, LibZfs, Result, SafeString, ZPool, ZPoolState, ZfsError};
+
+/// Identifies a pool to import.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoolId {
+    ByName(SafeString),
+    ByGuid(u64),
+}
+
+/// Where to look for pools which can be imported.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImportSearch {
+    /// Directories to scan for devices or files making up pools (`zpool import -d dir`).
+    pub dirs: Vec<PathBuf>,
+    /// Specific devices or files to examine (`zpool import -d device`).
+    pub devices: Vec<PathBuf>,
+    /// Also list pools which were destroyed, whose devices haven't been reused yet
+    /// (`zpool import -D`). They're left out otherwise.
+    pub include_destroyed: bool,
+}
+
+/// A pool found by scanning devices, which can be imported.
+#[derive(Debug)]
+pub struct ImportablePool {
+    pub name: SafeString,
+    pub guid: u64,
+    /// Usually [`ZPoolState::Exported`], or [`ZPoolState::Active`] for a pool which was not
+    /// cleanly exported, and may be in use by another system.
+    pub state: ZPoolState,
+    /// The health of the pool's devices, as the CLI shows it: "ONLINE", "DEGRADED", "UNAVAIL",
+    /// etc. Pools with missing devices are still listed, but may not be importable.
+    pub health: SafeString,
+    config: OwnedNvList,
+}
+
+impl ImportablePool {
+    fn new(name: SafeString, config: NvList<'_>) -> Self {
+        let guid = config.lookup_u64("pool_guid").unwrap_or(0);
+        let state = ZPoolState::from(config.lookup_u64("state").unwrap_or(0) as u32);
+        let health = config.lookup_nvlist("vdev_tree")
+            .and_then(|tree| tree.lookup_u64_array("vdev_stats"))
+            .filter(|stats| stats.len() > 2)
+            .map(|stats| {
+                // vdev_stat_t starts with: timestamp, state, aux
+                let cstr = unsafe {
+                    CStr::from_ptr(sys::zpool_state_to_name(
+                        stats[1] as sys::vdev_state_t,
+                        stats[2] as sys::vdev_aux_t,
+                    ))
+                };
+                SafeString::from(cstr.to_string_lossy().into_owned())
+            })
+            .unwrap_or_else(|| SafeString::from("UNKNOWN"));
+        ImportablePool { name, guid, state, health, config: config.to_owned() }
+    }
+}
+
+/// Options for importing a pool.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImportOpts {
+    /// Where to look for the pool's devices. Only used by [`LibZfs::import_pool`].
+    pub search: ImportSearch,
+    /// Import the pool under a new name (`zpool import oldname newname`).
+    pub new_name: Option<SafeString>,
+    /// Import the pool under a different name for this import only, leaving its persistent name
+    /// unchanged (`zpool import -t oldname newname`). Can't be combined with `new_name`.
+    pub temporary_name: Option<SafeString>,
+    /// Import the pool even if it appears to be in use by another system (`zpool import -f`).
+    pub force: bool,
+    /// Alternate root directory to mount the pool's filesystems under (`zpool import -R`). This
+    /// also sets `cachefile=none`. The `mountpoint` property and mount paths of the pool's
+    /// filesystems include the altroot.
+    pub altroot: Option<PathBuf>,
+    /// Import the pool read-only (`zpool import -o readonly=on`). Writes to its datasets then
+    /// fail with `EZFS_POOLREADONLY` or `EROFS`.
+    pub readonly: bool,
+    /// Import the pool even if its separate intent log devices are missing, discarding any
+    /// transactions in them (`zpool import -m`).
+    pub allow_missing_log: bool,
+    /// Don't mount any of the pool's filesystems (`zpool import -N`).
+    pub no_mount: bool,
+}
+
+impl LibZfs {
+    /// Scan for pools which can be imported (like `zpool import` with no pool name). Pools which
+    /// are already imported are not included, and neither are destroyed pools, unless
+    /// [`ImportSearch::include_destroyed`] is set.
+    pub fn find_importable_pools(&self, search: &ImportSearch) -> Result<Vec<ImportablePool>> {
+        let paths = search.dirs.iter()
+            .chain(&search.devices)
+            .map(|path| SafeString::from_path(path))
+            .collect::<Result<Vec<_>>>()?;
+        let mut path_ptrs: Vec<*mut c_char> = paths.iter()
+            .map(|path| path.as_ptr() as *mut c_char)
+            .collect();
+
+        // With no paths, the default device directories are scanned.
+        let mut args: sys::importargs_t = unsafe { std::mem::zeroed() };
+        if !path_ptrs.is_empty() {
+            args.path = path_ptrs.as_mut_ptr();
+            args.paths = path_ptrs.len() as c_int;
+        }
+
+        let mark = ErrorMark::new(self.handle);
+        let pools = unsafe {
+            sys::zpool_search_import(self.handle as *mut c_void, &mut args, &sys::libzfs_config_ops)
+        };
+        let pools = unsafe { OwnedNvList::from_raw(self.ptr_or_err(mark, pools)?) };
+
+        // The result has the config of each pool, keyed by pool name.
+        Ok(pools.borrow()
+            .nvlists()
+            .map(|(name, config)| ImportablePool::new(name, config))
+            .filter(|pool| search.include_destroyed || pool.state != ZPoolState::Destroyed)
+            .collect())
+    }
+
+    /// Find a pool and import it (like `zpool import`).
+    ///
+    /// Destroyed pools are only matched if [`ImportSearch::include_destroyed`] is set, in which
+    /// case only destroyed pools are, like `zpool import -D`. So a destroyed pool is never
+    /// brought back by mistake, and doesn't get in the way of a live pool with the same name.
+    ///
+    /// In addition to the errors from [`LibZfs::import`], this fails with `EZFS_NOENT` if no
+    /// matching pool was found, or `EZFS_EXISTS` if the pool is already imported.
+    pub fn import_pool(&self, which: PoolId, opts: ImportOpts) -> Result<ZPool> {
+        let found = self.find_importable_pools(&opts.search)?;
+        let destroyed = opts.search.include_destroyed;
+        let mut matches = found.iter()
+            .filter(|pool| (pool.state == ZPoolState::Destroyed) == destroyed)
+            .filter(|pool| match &which {
+                PoolId::ByName(wanted) => pool.name == *wanted,
+                PoolId::ByGuid(wanted) => pool.guid == *wanted,
+            });
+
+        match (matches.next(), matches.next()) {
+            (Some(pool), None) => self.import(pool, opts),
+            (Some(_), Some(_)) => Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_POOL_INVALARG,
+                "more than one matching pool; import by GUID instead",
+            ))),
+            (None, _) => {
+                for pool in self.get_zpools()? {
+                    let imported = match &which {
+                        PoolId::ByName(wanted) => pool.get_name() == *wanted,
+                        PoolId::ByGuid(wanted) => pool.guid().ok() == Some(*wanted),
+                    };
+                    if imported {
+                        return Err(Error::Zfs(ZfsError::new(
+                            sys::zfs_error::EZFS_EXISTS,
+                            format!("pool '{}' is already imported", pool.get_name()),
+                        )));
+                    }
+                }
+                Err(Error::Zfs(ZfsError::new(sys::zfs_error::EZFS_NOENT, "no such pool available")))
+            }
+        }
+    }
+
+    /// Import a pool found by [`LibZfs::find_importable_pools`], and mount its datasets unless
+    /// [`ImportOpts::no_mount`] is set.
+    ///
+    /// Errors specific to importing are reported with these codes:
+    /// * `EZFS_INVALIDNAME`: a different pool with the same name is already imported; use
+    ///   [`ImportOpts::new_name`] to import it under a different name.
+    /// * `EZFS_ACTIVE_POOL`: the pool was last used by another system, and may still be in use
+    ///   there; use [`ImportOpts::force`] if you're sure it isn't.
+    ///
+    /// If mounting the datasets fails, the pool is left imported.
+    pub fn import(&self, pool: &ImportablePool, opts: ImportOpts) -> Result<ZPool> {
+        if opts.new_name.is_some() && opts.temporary_name.is_some() {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_POOL_INVALARG,
+                "a new name and a temporary name can't both be given",
+            )));
+        }
+
+        let config = pool.config.borrow();
+        if pool.state != ZPoolState::Exported && !opts.force {
+            if let Some(hostid) = config.lookup_u64("hostid") {
+                let our_hostid = unsafe { libc::gethostid() } as u64 & 0xffff_ffff;
+                if hostid != our_hostid {
+                    let hostname = config.lookup_string("hostname")
+                        .map(String::from)
+                        .unwrap_or_else(|| "unknown".to_owned());
+                    return Err(Error::Zfs(ZfsError::new(
+                        sys::zfs_error::EZFS_ACTIVE_POOL,
+                        format!("pool was previously in use from another system ({}, hostid={:x}); \
+                            force the import if it is not in use there", hostname, hostid),
+                    )));
+                }
+            }
+        }
+
+        let new_name = opts.new_name.as_ref().or(opts.temporary_name.as_ref());
+        let target = new_name.unwrap_or(&pool.name);
+        if self.get_zpools()?.iter().any(|imported| imported.get_name() == *target) {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_INVALIDNAME,
+                format!("a pool named '{}' is already imported; import under a new name", target),
+            )));
+        }
+
+        let mut props = OwnedNvList::new();
+        if let Some(altroot) = &opts.altroot {
+            props.add_string("altroot", SafeString::from_path(altroot)?.as_ref());
+            props.add_string("cachefile", "none");
+        }
+        if opts.readonly {
+            props.add_string("readonly", "on");
+        }
+
+        let mut flags = sys::ZFS_IMPORT_NORMAL;
+        if opts.force {
+            flags |= sys::ZFS_IMPORT_ANY_HOST;
+        }
+        if opts.allow_missing_log {
+            flags |= sys::ZFS_IMPORT_MISSING_LOG;
+        }
+        if opts.temporary_name.is_some() {
+            flags |= sys::ZFS_IMPORT_TEMP_NAME;
+        }
+
+        ztry!(unsafe {
+            sys::zpool_import_props(
+                self.handle,
+                config.as_ptr(),
+                new_name.map(|s| s.as_ptr()).unwrap_or(ptr::null()),
+                if props.is_empty() { ptr::null_mut() } else { props.as_ptr() },
+                flags as c_int,
+            )
+        }, self.handle);
+
+        let imported = self.pool_by_name(target)?;
+        if !opts.no_mount {
+            ztry!(unsafe {
+                sys::zpool_enable_datasets(imported.handle, ptr::null(), 0)
+            }, self.handle);
+        }
+        Ok(imported)
+    }
+}
@@ -0,0 +1,140 @@
+use libzfs_sys as sys;
+
+use std::io;
+use std::os::raw::c_int;
+use std::ptr;
+
+use crate::{Error, Result, VdevId, ZPool, ZfsError};
+
+/// An action to take on the initialization of a pool's devices, which writes a pattern over
+/// their unallocated space so that first writes to it aren't slow, as on some virtual disks.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InitializeCmd {
+    /// Start initializing, or resume a suspended initialization (`zpool initialize`).
+    Start,
+    /// Pause initializing, remembering its progress (`zpool initialize -s`).
+    Suspend,
+    /// Stop initializing and forget its progress (`zpool initialize -c`).
+    Cancel,
+    /// Clear the record of a finished or canceled initialization, so that it's no longer shown
+    /// and the next one starts from the beginning (`zpool initialize -u`). This needs OpenZFS
+    /// 2.2 or later; older versions fail with `EINVAL`.
+    Uninit,
+}
+
+/// The state of the most recent initialization of a device.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InitializeState {
+    Active,
+    Suspended,
+    Canceled,
+    Complete,
+}
+
+impl InitializeState {
+    /// Translate a `vdev_initializing_state_t`, which is `None` if the device has never been
+    /// initialized.
+    fn from_raw(state: u64) -> Option<Self> {
+        use sys::vdev_initializing_state::*;
+        match state as sys::vdev_initializing_state_t {
+            VDEV_INITIALIZE_ACTIVE => Some(InitializeState::Active),
+            VDEV_INITIALIZE_SUSPENDED => Some(InitializeState::Suspended),
+            VDEV_INITIALIZE_CANCELED => Some(InitializeState::Canceled),
+            VDEV_INITIALIZE_COMPLETE => Some(InitializeState::Complete),
+            _ => None,
+        }
+    }
+}
+
+/// Progress of the most recent initialization of a leaf device, as shown by `zpool status -i`.
+///
+/// Times are in seconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitializeProgress {
+    pub state: InitializeState,
+    /// Bytes initialized so far.
+    pub bytes_done: u64,
+    /// Estimated total bytes to initialize.
+    pub bytes_est: u64,
+    /// When the initialization was started, or last suspended, canceled or completed.
+    pub action_time: u64,
+}
+
+impl InitializeProgress {
+    /// Read the progress from a `vdev_stat_t`, or `None` if the device has never been
+    /// initialized.
+    pub(crate) fn from_stats(stats: &sys::vdev_stat_t) -> Option<Self> {
+        Some(InitializeProgress {
+            state: InitializeState::from_raw(stats.vs_initialize_state)?,
+            bytes_done: stats.vs_initialize_bytes_done,
+            bytes_est: stats.vs_initialize_bytes_est,
+            action_time: stats.vs_initialize_action_time,
+        })
+    }
+
+    pub fn bytes_remaining(&self) -> u64 {
+        self.bytes_est.saturating_sub(self.bytes_done)
+    }
+
+    /// How much of the initialization is done, from 0 to 100.
+    pub fn percent_complete(&self) -> f64 {
+        if self.state == InitializeState::Complete {
+            100.0
+        } else if self.bytes_est == 0 {
+            0.0
+        } else {
+            (self.bytes_done as f64 / self.bytes_est as f64 * 100.0).min(100.0)
+        }
+    }
+}
+
+impl ZPool {
+    /// Start, suspend, cancel or clear the initialization of the given devices, or by default
+    /// all of the pool's leaf devices except cache devices and spares. Each device's progress
+    /// is in its [`Vdev::initialize`](crate::Vdev::initialize).
+    ///
+    /// If any devices fail, the others are still acted on, and the failures are reported
+    /// together as [`Error::Vdevs`], with these codes:
+    /// * `EZFS_INITIALIZING`: starting while the device is already being initialized.
+    /// * `EZFS_NO_INITIALIZE`: suspending or canceling when the device isn't being initialized.
+    /// * `EZFS_BADDEV`: the device isn't a writable leaf device.
+    /// * `EZFS_NODEVICE`: the device is offline or missing.
+    pub fn initialize(&self, cmd: InitializeCmd, vdevs: Option<&[VdevId]>) -> Result<()> {
+        use sys::pool_initialize_func::*;
+        let vdevs = self.vdev_list(vdevs)?;
+        let func = match cmd {
+            InitializeCmd::Start => POOL_INITIALIZE_START,
+            InitializeCmd::Suspend => POOL_INITIALIZE_SUSPEND,
+            InitializeCmd::Cancel => POOL_INITIALIZE_CANCEL,
+            // POOL_INITIALIZE_UNINIT, which isn't in the OpenZFS 2.1 headers.
+            InitializeCmd::Uninit => 3,
+        };
+
+        // This goes straight to libzfs_core, because zpool_initialize only reports the last
+        // failure.
+        let mut errlist: *mut sys::nvlist_t = ptr::null_mut();
+        let err = unsafe {
+            sys::lzc_initialize(self.get_name().as_ptr(), func, vdevs.as_ptr(), &mut errlist)
+        };
+        // ZPOOL_INITIALIZE_VDEVS
+        let errors = vdevs.errors(err, errlist, "initialize_vdevs", initialize_error)?;
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Vdevs(errors))
+        }
+    }
+}
+
+fn initialize_error(errno: c_int) -> ZfsError {
+    use sys::zfs_error::*;
+    let code = match errno {
+        libc::ENODEV => EZFS_NODEVICE,
+        libc::EINVAL | libc::EROFS => EZFS_BADDEV,
+        libc::EBUSY => EZFS_INITIALIZING,
+        libc::ESRCH => EZFS_NO_INITIALIZE,
+        _ => EZFS_UNKNOWN,
+    };
+    ZfsError::new(code, io::Error::from_raw_os_error(errno).to_string())
+}
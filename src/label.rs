@@ -0,0 +1,121 @@
+use libzfs_sys as sys;
+
+use std::ffi::CStr;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::raw::c_char;
+use std::path::Path;
+use std::ptr;
+
+use crate::nvlist::{NvList, OwnedNvList};
+use crate::{ztry, Error, LibZfs, Result, SafeString, ZPoolState, ZfsError};
+
+/// The contents of a ZFS label on a device, as read by [`LibZfs::read_label`].
+#[derive(Debug)]
+pub struct LabelInfo {
+    /// The name of the pool the device belongs to. Hot spares and cache devices don't record
+    /// which pool they belong to.
+    pub pool_name: Option<SafeString>,
+    pub pool_guid: Option<u64>,
+    /// The GUID of the device itself.
+    pub guid: u64,
+    /// The state of the pool when the label was last written.
+    pub state: ZPoolState,
+    /// The transaction group the label was last written in.
+    pub txg: Option<u64>,
+    config: OwnedNvList,
+}
+
+impl LabelInfo {
+    /// The full configuration stored in the label, for reading anything not covered above.
+    pub fn config(&self) -> NvList<'_> {
+        self.config.borrow()
+    }
+}
+
+impl LibZfs {
+    /// Read the ZFS label from a device or file, or `None` if it has no valid label.
+    pub fn read_label(&self, device: &Path) -> Result<Option<LabelInfo>> {
+        let file = File::open(device).map_err(Error::Sys)?;
+        let mut config: *mut sys::nvlist_t = ptr::null_mut();
+        if 0 != unsafe { sys::zpool_read_label(file.as_raw_fd(), &mut config, ptr::null_mut()) } {
+            return Err(Error::Sys(io::Error::last_os_error()));
+        }
+        if config.is_null() {
+            return Ok(None);
+        }
+        let config = unsafe { OwnedNvList::from_raw(config) };
+        let nvl = config.borrow();
+        Ok(Some(LabelInfo {
+            pool_name: nvl.lookup_string("name"),
+            pool_guid: nvl.lookup_u64("pool_guid"),
+            guid: nvl.lookup_u64("guid").unwrap_or(0),
+            state: ZPoolState::from(nvl.lookup_u64("state").unwrap_or(0) as sys::pool_state_t),
+            txg: nvl.lookup_u64("txg"),
+            config,
+        }))
+    }
+
+    /// Wipe the ZFS labels from a device or file (like `zpool labelclear`), such as one left over
+    /// from a pool which was destroyed or never exported, so it can be reused.
+    ///
+    /// Devices which are part of an imported pool, or are hot spares or cache devices in use,
+    /// are never cleared, failing with `EZFS_BUSY`. Without `force`, devices which are part of
+    /// an exported pool, or a pool which may be imported on another system, fail with
+    /// `EZFS_BADDEV`. The error message names the pool either way.
+    pub fn label_clear(&self, device: &Path, force: bool) -> Result<()> {
+        let file = OpenOptions::new().read(true).write(true).open(device).map_err(Error::Sys)?;
+        // Make sure the labels are read from the device rather than stale cached data.
+        let _ = file.sync_all();
+
+        let mut state: sys::pool_state_t = sys::pool_state::POOL_STATE_UNINITIALIZED;
+        let mut name: *mut c_char = ptr::null_mut();
+        let mut inuse: sys::boolean_t = 0;
+        ztry!(unsafe {
+            sys::zpool_in_use(self.handle, file.as_raw_fd(), &mut state, &mut name, &mut inuse)
+        }, self.handle);
+
+        if inuse != 0 {
+            let name = if name.is_null() {
+                String::new()
+            } else {
+                let owned = unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned();
+                unsafe { libc::free(name as *mut libc::c_void) };
+                owned
+            };
+            let device = device.display();
+            use sys::pool_state::*;
+            let (code, msg) = match state {
+                POOL_STATE_EXPORTED if !force => (
+                    sys::zfs_error::EZFS_BADDEV,
+                    format!("{} is a member of exported pool '{}'", device, name),
+                ),
+                POOL_STATE_POTENTIALLY_ACTIVE if !force => (
+                    sys::zfs_error::EZFS_BADDEV,
+                    format!("{} is a member of potentially active pool '{}'", device, name),
+                ),
+                POOL_STATE_EXPORTED | POOL_STATE_POTENTIALLY_ACTIVE => {
+                    return clear_label(&file);
+                }
+                _ => {
+                    let state = ZPoolState::from(state);
+                    (
+                        sys::zfs_error::EZFS_BUSY,
+                        format!("{} is a member ({:?}) of pool '{}'", device, state, name),
+                    )
+                }
+            };
+            return Err(Error::Zfs(ZfsError::new(code, msg)));
+        }
+
+        clear_label(&file)
+    }
+}
+
+fn clear_label(file: &File) -> Result<()> {
+    if 0 != unsafe { sys::zpool_clear_label(file.as_raw_fd()) } {
+        return Err(Error::Sys(io::Error::last_os_error()));
+    }
+    Ok(())
+}
@@ -6,16 +6,78 @@ use libzfs_sys as sys;
 use std::ffi::CStr;
 use std::io;
 use std::marker::PhantomData;
-use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::fd::OwnedFd;
 use std::os::raw::{c_char, c_void};
 use std::ptr;
-use std::thread::{self, JoinHandle};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[macro_use]
+mod macros;
 
 mod string;
+mod bookmark;
+mod checkpoint;
+mod diff;
 mod error;
-
-pub use string::SafeString;
+mod events;
+mod history;
+mod hold;
+mod import;
+mod initialize;
+mod label;
+mod mount;
+mod nvlist;
+mod pool;
+mod property;
+mod receive;
+mod redact;
+mod resume;
+mod scan;
+mod send;
+mod shared;
+mod space;
+mod status;
+mod stream;
+mod trim;
+mod upgrade;
+mod validate;
+mod vdev;
+mod vdev_spec;
+mod volume;
+
+pub use string::{set_lossy_names, SafeString};
 pub use error::*;
+pub use checkpoint::CheckpointStats;
+pub use diff::{DiffChange, DiffEntry, DiffFileType, DiffOpts, DiffStats};
+pub use events::{EventInterrupter, EventOpts, EventStream, ZfsEvent};
+pub use history::{HistoryEvent, HistoryOpts};
+pub use hold::TempHold;
+pub use import::{ImportablePool, ImportOpts, ImportSearch, PoolId};
+pub use initialize::{InitializeCmd, InitializeProgress, InitializeState};
+pub use label::LabelInfo;
+pub use mount::MountOpts;
+pub use nvlist::NvList;
+pub use pool::{AddOpts, ExportOpts, PoolConfig, PoolCreateOpts, SplitOpts};
+pub use property::{AtimeMode, CanMount, Compression, Property, PropSource};
+pub use receive::{ReceiveNaming, ReceiveOpts, SendHeader};
+pub use resume::ResumeToken;
+pub use scan::{ScanFunction, ScanState, ScanStats, ScrubCmd};
+pub use send::{IncrementalSource, SendBuilder, SendFlags};
+pub use shared::SharedLibZfs;
+pub use space::{PoolSpace, VdevSpace};
+pub use status::{ErrorLogEntry, PoolHealth, ZpoolStatusReason};
+pub use trim::{TrimCmd, TrimOpts, TrimProgress, TrimState};
+pub use upgrade::UpgradeReport;
+pub use vdev::{
+    AttachOpts, RemovalStats, RewindPolicy, SpareInfo, SpareStatus, Vdev, VdevClass, VdevId,
+    VdevState, VdevType,
+};
+pub use vdev_spec::{VdevDevice, VdevGroup, VdevSpec};
+pub use volume::Volume;
+
+/// Error codes reported by libzfs.
+pub use sys::zfs_error as ZfsErrorCode;
 
 /// Flags for ZFS send operations.
 pub use sys::lzc_send_flags as ZfsSendFlags;
@@ -35,14 +97,34 @@ impl LibZfs {
         }
     }
 
+    /// Get the underlying libzfs handle, for calling libzfs functions which aren't wrapped by
+    /// this crate.
+    ///
+    /// # Safety
+    /// The handle stays owned by this `LibZfs`, which calls `libzfs_fini` on it when dropped. It
+    /// must not be used after this `LibZfs` is dropped, and must not be passed to `libzfs_fini`
+    /// or anything else which frees it. Pool and dataset handles opened from it directly must be
+    /// closed before this `LibZfs` is dropped.
+    pub unsafe fn as_raw_handle(&self) -> *mut sys::libzfs_handle_t {
+        self.handle
+    }
+
     pub fn pool_by_name(&self, name: &SafeString) -> Result<ZPool> {
+        let mark = ErrorMark::new(self.handle);
         let handle = unsafe { sys::zpool_open(self.handle, name.as_ptr()) };
-        self.ptr_or_err(handle).map(|handle| ZPool { libzfs: self.handle, handle })
+        self.ptr_or_err(mark, handle).map(|handle| ZPool { libzfs: self.handle, handle })
     }
 
     pub fn dataset_by_name(&self, name: &SafeString, types: DatasetTypeMask) -> Result<Dataset> {
+        let mark = ErrorMark::new(self.handle);
         let handle = unsafe { sys::zfs_open(self.handle, name.as_ptr(), types.0 as i32) };
-        self.ptr_or_err(handle).map(|handle| Dataset { libzfs: self.handle, handle })
+        self.ptr_or_err(mark, handle).map(|handle| Dataset { libzfs: self.handle, handle })
+    }
+
+    /// Open several datasets by name, returning the result for each, in the same order as the
+    /// names. A failure to open one doesn't stop the others from being opened.
+    pub fn open_many(&self, names: &[SafeString], types: DatasetTypeMask) -> Vec<Result<Dataset>> {
+        names.iter().map(|name| self.dataset_by_name(name, types)).collect()
     }
 
     pub fn create_snapshots<I, T>(&self, names: I) -> Result<()>
@@ -52,9 +134,10 @@ impl LibZfs {
         let nvl = self.build_nvlist(names)?;
 
         // Need to check if empty, otherwise it segfaults.
+        let mark = ErrorMark::new(self.handle);
         let ret = match unsafe { sys::nvlist_empty(nvl) } {
             0 => if 0 != unsafe { sys::zfs_snapshot_nvl(self.handle, nvl, std::ptr::null_mut()) } {
-                self.get_last_error()
+                self.get_last_error(mark)
             } else {
                 Ok(())
             },
@@ -66,17 +149,47 @@ impl LibZfs {
         ret
     }
 
+    /// Create a snapshot (`pool/fs@snap`) and open it. If `recursive` is set, same-named
+    /// snapshots of all descendent filesystems are created too, atomically, and the returned
+    /// handle is the one for the named dataset.
+    pub fn snapshot(&self, name: &SafeString, recursive: bool) -> Result<Dataset> {
+        ztry!(unsafe {
+            sys::zfs_snapshot(self.handle, name.as_ptr(), recursive as sys::boolean_t, ptr::null_mut())
+        }, self.handle);
+        self.dataset_by_name(name, DatasetType::Snapshot.into())
+    }
+
     pub fn destroy_snapshots<I, T>(&self, names: I) -> Result<()>
         where I: Iterator<Item = T>,
               T: AsRef<str>,
+    {
+        self.destroy_snapshots_impl(names, false)
+    }
+
+    /// Like [`LibZfs::destroy_snapshots`], but snapshots which are held or have clones are
+    /// marked for destruction instead of failing (like `zfs destroy -d`). They're destroyed
+    /// once their last hold is released and their last clone is destroyed.
+    pub fn destroy_snapshots_deferred<I, T>(&self, names: I) -> Result<()>
+        where I: Iterator<Item = T>,
+              T: AsRef<str>,
+    {
+        self.destroy_snapshots_impl(names, true)
+    }
+
+    fn destroy_snapshots_impl<I, T>(&self, names: I, defer: bool) -> Result<()>
+        where I: Iterator<Item = T>,
+              T: AsRef<str>,
     {
         let nvl = self.build_nvlist(names)?;
 
         // Need to check if empty, otherwise it segfaults.
+        let mark = ErrorMark::new(self.handle);
         let ret = match unsafe { sys::nvlist_empty(nvl) } {
-            0 => match unsafe { sys::zfs_destroy_snaps_nvl(self.handle, nvl, 0) } {
+            0 => match unsafe {
+                sys::zfs_destroy_snaps_nvl(self.handle, nvl, defer as sys::boolean_t)
+            } {
                 0 => Ok(()),
-                _ => self.get_last_error(),
+                _ => self.get_last_error(mark),
             },
             _ => Ok(()),
         };
@@ -92,8 +205,9 @@ impl LibZfs {
     {
         let mut nvl = std::ptr::null_mut();
 
+        let mark = ErrorMark::new(self.handle);
         if 0 != unsafe { sys::nvlist_alloc(&mut nvl as *mut _, sys::NV_UNIQUE_NAME, 0) } {
-            return self.get_last_error();
+            return self.get_last_error(mark);
         }
 
         for name in names {
@@ -133,22 +247,16 @@ impl LibZfs {
         Ok(ctx.pools)
     }
 
-    fn ptr_or_err<T>(&self, ptr: *mut T) -> Result<*mut T> {
+    fn ptr_or_err<T>(&self, mark: ErrorMark, ptr: *mut T) -> Result<*mut T> {
         if ptr.is_null() {
-            self.get_last_error()
+            self.get_last_error(mark)
         } else {
             Ok(ptr)
         }
     }
 
-    fn get_last_error<T>(&self) -> Result<T> {
-        let zfs_err = ZfsError::last_error(self.handle);
-        // TODO: is this valid? should we do this on EZFS_SUCCESS instead / in addition?
-        if zfs_err.code != sys::zfs_error::EZFS_UNKNOWN {
-            Err(Error::Zfs(zfs_err))
-        } else {
-            Err(Error::Sys(std::io::Error::last_os_error()))
-        }
+    fn get_last_error<T>(&self, mark: ErrorMark) -> Result<T> {
+        Err(mark.error(self.handle))
     }
 }
 
@@ -175,18 +283,43 @@ impl ZPool {
 
     pub fn get_name(&self) -> SafeString {
         let cstr = unsafe { CStr::from_ptr(sys::zpool_get_name(self.handle)) };
-        let utf8_verified = cstr.to_str().expect("invalid UTF8 in pool name");
-        SafeString::from(utf8_verified.to_owned())
+        SafeString::from_name(cstr, "pool")
+    }
+
+    /// Get the pool's GUID, which identifies it even if it's renamed or imported under a
+    /// different name.
+    ///
+    /// This is read from the pool's configuration rather than its properties, so it works even
+    /// if the pool is faulted or suspended.
+    pub fn guid(&self) -> Result<u64> {
+        self.config().lookup_u64("pool_guid").ok_or_else(|| {
+            Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_INVALCONFIG,
+                "pool configuration has no GUID",
+            ))
+        })
+    }
+
+    /// Get the pool's load GUID, which is regenerated every time the pool is imported. This
+    /// tells apart separate imports of the same pool.
+    pub fn load_guid(&self) -> Result<u64> {
+        self.get_numeric_property("load_guid").map(|p| p.value)
+    }
+
+    /// Get the pool's name and GUID together, such as for listing pools.
+    pub fn name_and_guid(&self) -> Result<(SafeString, u64)> {
+        Ok((self.get_name(), self.guid()?))
     }
 
     pub fn get_datasets(&self) -> Result<Vec<Dataset>> {
         let pool_name = self.get_name();
 
+        let mark = ErrorMark::new(self.libzfs);
         let root_handle = unsafe {
             sys::zfs_open(self.libzfs, pool_name.as_ptr(), sys::zfs_type_t::ZFS_TYPE_FILESYSTEM as i32)
         };
         if root_handle.is_null() {
-            return Err(ZfsError::last_error(self.libzfs).into());
+            return Err(mark.error(self.libzfs));
         }
 
         let mut ctx = ZfsIterCollectContext {
@@ -209,6 +342,54 @@ impl ZPool {
         });
         Ok(ctx.vec)
     }
+
+    /// Open a dataset in this pool by its name relative to the pool: `a/b` for `pool/a/b`, or
+    /// `a/b@snap` for `pool/a/b@snap`. The pool's root filesystem is the empty name, and its
+    /// snapshots and bookmarks are `@snap` and `#bookmark`.
+    ///
+    /// Fails with `EZFS_INVALIDNAME` if the name isn't valid (see [`LibZfs::is_valid_name`]), or
+    /// if it starts with `/`, or its first component is the pool's name, which is most likely a
+    /// full name given by mistake. (A filesystem named the same as the pool, and its
+    /// descendants, have to be opened by their full names, with [`LibZfs::dataset_by_name`].)
+    pub fn dataset_by_name(&self, relative: &SafeString, types: DatasetTypeMask)
+        -> Result<Dataset>
+    {
+        let pool_name = self.get_name();
+        let pool: &str = pool_name.as_ref();
+        let relative: &str = relative.as_ref();
+        let first = relative.split(['/', '@', '#']).next().unwrap_or("");
+        if relative.starts_with('/') || first == pool {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_INVALIDNAME,
+                format!("'{}' must be relative to the pool, without '{}/' in front", relative,
+                    pool),
+            )));
+        }
+        let name = if relative.is_empty() || relative.starts_with(['@', '#']) {
+            SafeString::from(format!("{}{}", pool, relative))
+        } else {
+            SafeString::from(format!("{}/{}", pool, relative))
+        };
+        let dataset_type = if relative.contains('@') {
+            DatasetType::Snapshot
+        } else if relative.contains('#') {
+            DatasetType::Bookmark
+        } else {
+            DatasetType::Filesystem
+        };
+        if !LibZfs::is_valid_name(&name, dataset_type) {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_INVALIDNAME,
+                format!("'{}' is not a valid dataset name", relative),
+            )));
+        }
+        let mark = ErrorMark::new(self.libzfs);
+        let handle = unsafe { sys::zfs_open(self.libzfs, name.as_ptr(), types.0 as i32) };
+        if handle.is_null() {
+            return Err(mark.error(self.libzfs));
+        }
+        Ok(Dataset { libzfs: self.libzfs, handle })
+    }
 }
 
 impl Drop for ZPool {
@@ -219,6 +400,38 @@ impl Drop for ZPool {
     }
 }
 
+#[derive(PartialEq, Eq, Hash)]
+enum PoolIdentity {
+    Guid(u64),
+    Name(SafeString),
+}
+
+impl ZPool {
+    fn identity(&self) -> PoolIdentity {
+        match self.guid() {
+            Ok(guid) => PoolIdentity::Guid(guid),
+            Err(_) => PoolIdentity::Name(self.get_name()),
+        }
+    }
+}
+
+/// Pools are compared and hashed by their GUID, so two handles to the same pool are equal even
+/// if they were opened separately. If the GUID isn't available from the pool's config, the pool
+/// name is used instead.
+impl PartialEq for ZPool {
+    fn eq(&self, other: &Self) -> bool {
+        self.identity() == other.identity()
+    }
+}
+
+impl Eq for ZPool {}
+
+impl std::hash::Hash for ZPool {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.identity().hash(state)
+    }
+}
+
 #[derive(Debug)]
 pub struct Dataset {
     libzfs: *mut sys::libzfs_handle_t,
@@ -226,16 +439,51 @@ pub struct Dataset {
 }
 
 impl Dataset {
+    /// Take ownership of a raw libzfs dataset handle, such as one obtained from other FFI code
+    /// running in the same process.
+    ///
+    /// # Safety
+    /// `handle` must be a valid, open `zfs_handle_t` that nothing else will close: the returned
+    /// `Dataset` calls `zfs_close` on it when dropped. The libzfs handle it was opened from must
+    /// outlive the returned `Dataset`.
+    pub unsafe fn from_raw(handle: *mut sys::zfs_handle_t) -> Dataset {
+        let libzfs = sys::zfs_get_handle(handle);
+        Dataset { libzfs, handle }
+    }
+
+    /// Give up ownership of the underlying libzfs dataset handle without closing it.
+    ///
+    /// The caller becomes responsible for eventually passing the pointer to `zfs_close` (or back
+    /// to [`Dataset::from_raw`]).
+    pub fn into_raw(self) -> *mut sys::zfs_handle_t {
+        let handle = self.handle;
+        std::mem::forget(self);
+        handle
+    }
+
     /// Get the type of this dataset.
     pub fn get_type(&self) -> DatasetType {
         DatasetType::from(unsafe { sys::zfs_get_type(self.handle) })
     }
 
+    /// Get a type mask matching just this dataset's type, for looking up other datasets like it.
+    pub fn get_type_mask(&self) -> DatasetTypeMask {
+        DatasetTypeMask::from(self.get_type())
+    }
+
     /// Get the name of this dataset.
     pub fn get_name(&self) -> SafeString {
         let cstr = unsafe { CStr::from_ptr(sys::zfs_get_name(self.handle)) };
-        let utf8_verified = cstr.to_str().expect("invalid UTF8 in dataset name");
-        SafeString::from(utf8_verified.to_owned())
+        SafeString::from_name(cstr, "dataset")
+    }
+
+    /// Check whether this handle still refers to an existing dataset. It stops doing so if the
+    /// dataset is destroyed or renamed by someone else after it was opened, after which most
+    /// operations on it fail.
+    pub fn is_valid(&self) -> bool {
+        let name = unsafe { sys::zfs_get_name(self.handle) };
+        let types = self.get_type_mask();
+        0 != unsafe { sys::zfs_dataset_exists(self.libzfs, name, types.0) }
     }
 
     /// Get the pool this dataset belongs to.
@@ -247,8 +495,7 @@ impl Dataset {
     /// Get the name of the pool this dataset belongs to.
     pub fn get_pool_name(&self) -> SafeString {
         let cstr = unsafe { CStr::from_ptr(sys::zfs_get_pool_name(self.handle)) };
-        let utf8_verified = cstr.to_str().expect("invalid UTF8 in pool name");
-        SafeString::from(utf8_verified.to_owned())
+        SafeString::from_name(cstr, "pool")
     }
 
     /// Get all snapshots of this dataset.
@@ -288,6 +535,25 @@ impl Dataset {
         Ok(ctx.vec)
     }
 
+    /// Get the snapshots of this dataset created from `start` up to but not including `end`,
+    /// ordered by creation time (oldest first).
+    ///
+    /// Creation times are in whole seconds, so a snapshot created during the second `start`
+    /// falls within the range, and one created during the second `end` doesn't.
+    pub fn get_snapshots_between(&self, start: SystemTime, end: SystemTime)
+        -> Result<Vec<Dataset>>
+    {
+        let mut snapshots = vec![];
+        for snapshot in self.get_snapshots_ordered()? {
+            let creation = snapshot.get_numeric_property("creation")?.value;
+            let created = UNIX_EPOCH + Duration::from_secs(creation);
+            if start <= created && created < end {
+                snapshots.push(snapshot);
+            }
+        }
+        Ok(snapshots)
+    }
+
     /// Execute a callback function for each snapshot of this dataset.
     pub fn foreach_snapshot(&self, callback: Box<dyn FnMut(Dataset)>) -> Result<()> {
         let mut ctx = ZfsIterCallbackContext {
@@ -342,8 +608,48 @@ impl Dataset {
         Ok(ctx.vec)
     }
 
+    /// Get all descendent filesystems under this one, recursively, down to `max_depth` levels
+    /// below it (so a depth of 1 is the same as [`Dataset::get_child_filesystems`]), or the
+    /// whole subtree if it's `None`. Each filesystem comes before its own children.
+    pub fn get_descendent_filesystems(&self, max_depth: Option<usize>) -> Result<Vec<Dataset>> {
+        let mut vec = vec![];
+        self.collect_descendent_filesystems(max_depth, &mut vec)?;
+        Ok(vec)
+    }
+
+    fn collect_descendent_filesystems(
+        &self,
+        max_depth: Option<usize>,
+        vec: &mut Vec<Dataset>,
+    ) -> Result<()> {
+        if max_depth == Some(0) {
+            return Ok(());
+        }
+        for child in self.get_child_filesystems()? {
+            let mut descendents = vec![];
+            child.collect_descendent_filesystems(
+                max_depth.map(|depth| depth - 1),
+                &mut descendents,
+            )?;
+            vec.push(child);
+            vec.append(&mut descendents);
+        }
+        Ok(())
+    }
+
     /// Get all child datasets of this one, recursively, of all types (snapshot, filesystem, etc.).
     pub fn get_all_dependents(&self) -> Result<Vec<Dataset>> {
+        self.dependents(true)
+    }
+
+    /// Get everything that would be destroyed along with this dataset (like the list
+    /// `zfs destroy -nvR` prints): its children and snapshots, recursively, and clones of those
+    /// snapshots and their children. They're in an order that's safe for destroying them one at a
+    /// time: each dataset comes before the ones it depends on.
+    ///
+    /// If `allow_recursion` is not set, this fails with `EZFS_RECURSIVE` if a dataset is reached
+    /// twice, which can only happen in a damaged pool.
+    pub fn dependents(&self, allow_recursion: bool) -> Result<Vec<Dataset>> {
         let mut ctx = ZfsIterCollectContext {
             libzfs: self.libzfs,
             vec: vec![],
@@ -351,7 +657,7 @@ impl Dataset {
         ztry!(unsafe {
             sys::zfs_iter_dependents(
                 self.handle,
-                1, // allow recursion
+                allow_recursion as sys::boolean_t,
                 Some(zfs_iter_collect),
                 &mut ctx as *mut _ as *mut c_void,
             )
@@ -359,6 +665,58 @@ impl Dataset {
         Ok(ctx.vec)
     }
 
+    /// Get the clones of this snapshot: the filesystems and volumes which were created from it,
+    /// and which prevent it from being destroyed.
+    pub fn get_clones(&self) -> Result<Vec<Dataset>> {
+        if self.get_type() != DatasetType::Snapshot {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADTYPE,
+                "only snapshots have clones",
+            )));
+        }
+        // The nvlist belongs to the handle; its keys are the clones' names.
+        let nvl = unsafe { sys::zfs_get_clones_nvl(self.handle) };
+        if nvl.is_null() {
+            return Ok(vec![]);
+        }
+        let types = DatasetType::Filesystem | DatasetType::Volume;
+        unsafe { nvlist::NvList::from_ptr(nvl) }
+            .names()
+            .map(|name| {
+                let mark = ErrorMark::new(self.libzfs);
+                let handle = unsafe { sys::zfs_open(self.libzfs, name.as_ptr(), types.0 as i32) };
+                if handle.is_null() {
+                    return Err(mark.error(self.libzfs));
+                }
+                Ok(Dataset { libzfs: self.libzfs, handle })
+            })
+            .collect()
+    }
+
+    /// Destroy this dataset along with everything that depends on it (like `zfs destroy -R`),
+    /// but only if `confirm` agrees. It's called with the list of
+    /// [dependents](Dataset::dependents) which would be destroyed too, so it can ask "are you
+    /// sure?", and nothing is destroyed if it returns false.
+    ///
+    /// Returns whether the dataset was destroyed. Filesystems are unmounted first. If destroying
+    /// one of the datasets fails, this stops there, leaving it and the rest in place.
+    pub fn destroy_interactive<F>(self, mut confirm: F) -> Result<bool>
+        where F: FnMut(&[Dataset]) -> bool,
+    {
+        let dependents = self.dependents(false)?;
+        if !confirm(&dependents) {
+            return Ok(false);
+        }
+        for dataset in dependents.iter().chain(std::iter::once(&self)) {
+            if dataset.get_type() == DatasetType::Filesystem {
+                dataset.unmount(false)?;
+            }
+            ztry!(unsafe { sys::zfs_destroy(dataset.handle, 0) }, self.libzfs);
+        }
+        // The handles still need to be closed, which dropping them does.
+        Ok(true)
+    }
+
     pub fn get_send_space(&self, from_fq: Option<&SafeString>, flags: ZfsSendFlags) -> Result<u64> {
         let name: *const c_char = unsafe { sys::zfs_get_name(self.handle) };
         let from: *const c_char = from_fq.map(|s| s.as_ptr()).unwrap_or(ptr::null());
@@ -373,38 +731,12 @@ impl Dataset {
         fd: OwnedFd,
         flags: ZfsSendFlags,
     ) -> Result<ZfsSend<'_>> {
-        // Best-effort attempt to set a big buffer size in case fd is a pipe.
-        let _ = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETPIPE_SZ, 1_048_576_i32) };
-
-        // Safety is ensured by tying the lifetime of the ZfsSend holding the thread to the
-        // lifetime of the dataset, which holds the libzfs handle.
-        struct Wrap(*mut sys::libzfs_handle);
-        unsafe impl Send for Wrap {}
-        impl Wrap {
-            pub fn ptr(&self) -> *mut sys::libzfs_handle {
-                self.0
-            }
+        let mut builder = self.send_builder().flags(flags);
+        builder.check_from = false;
+        match from_fq {
+            Some(from) => builder.from(from).send(fd),
+            None => builder.send(fd),
         }
-        let w = Wrap(self.libzfs);
-
-        let fqname = self.get_name();
-        let thread = thread::spawn(move || {
-            ztry!(unsafe {
-                sys::lzc_send(
-                    fqname.as_ptr(),
-                    from_fq.as_ref().map(|s| s.as_ptr()).unwrap_or(ptr::null()),
-                    fd.as_raw_fd(),
-                    flags,
-                )
-            }, w.ptr());
-            drop(fd);
-            Ok(())
-        });
-
-        Ok(ZfsSend {
-            _dataset: PhantomData,
-            thread,
-        })
     }
 }
 
@@ -424,6 +756,9 @@ pub struct ZfsSend<'a> {
 
 impl<'a> ZfsSend<'a> {
     /// Blocks the current thread until the send is done, whether successful or failure.
+    ///
+    /// If the reader of the stream goes away before the end, like the receiving side of a pipe
+    /// or socket, this fails with [`Error::Sys`] with `EPIPE`, whichever way it was sent.
     pub fn wait(self) -> Result<()> {
         self.thread.join()
             .unwrap_or(Err(Error::Sys(io::Error::new(io::ErrorKind::Other, ZfsSendPanicked()))))
@@ -467,48 +802,6 @@ impl Drop for Dataset {
     }
 }
 
-// this is meant to be used with the bindgen option 'constified_enum_module'
-macro_rules! translate_enum {
-    (
-        new_name: $new_name:ident,
-        sys_name: $sys_name:path,
-        repr: $repr:ident,
-        variants: {
-            $(
-                $sys:ident => $new:ident,
-            )*
-        }
-    ) => {
-        // This is needed to access variants of $sys_name. As far as I can tell, it's impossible
-        // to join a path and an identifier with '::' in a macro. :(
-        use $sys_name::*;
-
-        #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-        #[repr($repr)]
-        pub enum $new_name {
-            $($new = ($sys as $repr),)*
-        }
-
-        impl From<$repr> for $new_name {
-            fn from(raw: $repr) -> $new_name {
-                use $new_name::*;
-                match raw {
-                    $(
-                        $sys => $new
-                    ),*,
-                    _ => panic!("unknown {} variant: {}", stringify!($sys_name), raw)
-                }
-            }
-        }
-
-        impl From<$new_name> for $repr {
-            fn from(val: $new_name) -> $repr {
-                unsafe { std::mem::transmute(val) }
-            }
-        }
-    }
-}
-
 translate_enum! {
     new_name: ZPoolState,
     sys_name: sys::pool_state,
@@ -0,0 +1,41 @@
+// this is meant to be used with the bindgen option 'constified_enum_module'
+macro_rules! translate_enum {
+    (
+        new_name: $new_name:ident,
+        sys_name: $sys_name:path,
+        repr: $repr:ident,
+        variants: {
+            $(
+                $sys:ident => $new:ident,
+            )*
+        }
+    ) => {
+        // This is needed to access variants of $sys_name. As far as I can tell, it's impossible
+        // to join a path and an identifier with '::' in a macro. :(
+        use $sys_name::*;
+
+        #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+        #[repr($repr)]
+        pub enum $new_name {
+            $($new = ($sys as $repr),)*
+        }
+
+        impl From<$repr> for $new_name {
+            fn from(raw: $repr) -> $new_name {
+                use $new_name::*;
+                match raw {
+                    $(
+                        $sys => $new
+                    ),*,
+                    _ => panic!("unknown {} variant: {}", stringify!($sys_name), raw)
+                }
+            }
+        }
+
+        impl From<$new_name> for $repr {
+            fn from(val: $new_name) -> $repr {
+                unsafe { std::mem::transmute(val) }
+            }
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use libzfs_sys as sys;
+
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString, OsStr};
+use std::io;
+use std::mem;
+use std::os::raw::{c_char, c_int};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use crate::{
+    ztry, Dataset, DatasetType, DatasetTypeMask, Error, ErrorMark, LibZfs, Result, SafeString,
+    ZfsError,
+};
+
+// The f_type statfs reports for ZFS.
+const ZFS_SUPER_MAGIC: u32 = 0x2fc1_2fc1;
+
+/// Temporary mount options, which apply only for the lifetime of a mount and don't change the
+/// dataset's persistent properties (like `zfs mount -o`).
+///
+/// While mounted this way, the corresponding properties report the temporary value with
+/// [`PropSource::Temporary`](crate::PropSource::Temporary) as their source.
+///
+/// Options which aren't set are left to the dataset's properties.
+///
+/// ```
+/// # use libzfs::MountOpts;
+/// let opts = MountOpts::new().readonly(true).atime(false);
+/// assert_eq!(String::from(opts.to_options_string().unwrap()), "ro,noatime");
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MountOpts {
+    remount: bool,
+    readonly: Option<bool>,
+    exec: Option<bool>,
+    setuid: Option<bool>,
+    devices: Option<bool>,
+    atime: Option<bool>,
+    relatime: Option<bool>,
+    xattr: Option<bool>,
+}
+
+impl MountOpts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Change the options of an already-mounted filesystem instead of mounting it (`remount`).
+    pub fn remount(mut self, remount: bool) -> Self {
+        self.remount = remount;
+        self
+    }
+
+    /// Mount read-only (`ro`) or read-write (`rw`).
+    pub fn readonly(mut self, readonly: bool) -> Self {
+        self.readonly = Some(readonly);
+        self
+    }
+
+    /// Allow (`exec`) or disallow (`noexec`) execution of binaries.
+    pub fn exec(mut self, exec: bool) -> Self {
+        self.exec = Some(exec);
+        self
+    }
+
+    /// Honor (`suid`) or ignore (`nosuid`) setuid and setgid bits.
+    pub fn setuid(mut self, setuid: bool) -> Self {
+        self.setuid = Some(setuid);
+        self
+    }
+
+    /// Allow (`dev`) or disallow (`nodev`) access to device nodes.
+    pub fn devices(mut self, devices: bool) -> Self {
+        self.devices = Some(devices);
+        self
+    }
+
+    /// Update (`atime`) or don't update (`noatime`) access times.
+    pub fn atime(mut self, atime: bool) -> Self {
+        self.atime = Some(atime);
+        self
+    }
+
+    /// Only update access times when older than the modification time (`relatime`), or always
+    /// update them (`norelatime`). Has no effect if access times are off.
+    pub fn relatime(mut self, relatime: bool) -> Self {
+        self.relatime = Some(relatime);
+        self
+    }
+
+    /// Enable (`xattr`) or disable (`noxattr`) extended attributes.
+    pub fn xattr(mut self, xattr: bool) -> Self {
+        self.xattr = Some(xattr);
+        self
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.atime == Some(false) && self.relatime == Some(true) {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADPROP,
+                "mount options 'noatime' and 'relatime' are incompatible",
+            )));
+        }
+        Ok(())
+    }
+
+    /// Render the options as the comma-separated string that `zfs_mount` takes, checking that
+    /// they make sense together.
+    pub fn to_options_string(&self) -> Result<SafeString> {
+        self.validate()?;
+
+        let mut opts = vec![];
+        if self.remount {
+            opts.push("remount");
+        }
+        let pairs = [
+            (self.readonly, "ro", "rw"),
+            (self.exec, "exec", "noexec"),
+            (self.setuid, "suid", "nosuid"),
+            (self.devices, "dev", "nodev"),
+            (self.atime, "atime", "noatime"),
+            (self.relatime, "relatime", "norelatime"),
+            (self.xattr, "xattr", "noxattr"),
+        ];
+        for (value, on, off) in pairs {
+            match value {
+                Some(true) => opts.push(on),
+                Some(false) => opts.push(off),
+                None => (),
+            }
+        }
+        Ok(SafeString::from(opts.join(",")))
+    }
+}
+
+impl Dataset {
+    /// Mount this filesystem at its configured mountpoint, with the given temporary options.
+    ///
+    /// The options override the corresponding properties (`readonly`, `atime`, etc.) only until
+    /// the filesystem is unmounted; the stored property values are left unchanged. For example,
+    /// to mount a filesystem read-only for maintenance, without access time updates:
+    ///
+    /// ```no_run
+    /// # use libzfs::{Dataset, MountOpts};
+    /// # fn f(fs: &Dataset) -> libzfs::Result<()> {
+    /// fs.mount(&MountOpts::new().readonly(true).atime(false))?;
+    /// // ... fs.get_readonly() now reports `true`, with PropSource::Temporary ...
+    /// fs.unmount(false)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn mount(&self, opts: &MountOpts) -> Result<()> {
+        let options = opts.to_options_string()?;
+        ztry!(unsafe { sys::zfs_mount(self.handle, options.as_ptr(), 0) }, self.libzfs);
+        Ok(())
+    }
+
+    /// Set the `readonly` property, and if the filesystem is mounted with a different effective
+    /// setting (because of a temporary mount option), remount it to match.
+    pub fn set_readonly_remount(&self, readonly: bool) -> Result<()> {
+        self.set_readonly(readonly)?;
+        if self.is_mounted() && self.get_readonly()?.value != readonly {
+            self.mount(&MountOpts::new().remount(true).readonly(readonly))?;
+        }
+        Ok(())
+    }
+
+    /// Unmount this filesystem, forcibly if `force` is set (like `zfs unmount -f`).
+    pub fn unmount(&self, force: bool) -> Result<()> {
+        let flags = if force { libc::MNT_FORCE } else { 0 };
+        ztry!(unsafe { sys::zfs_unmount(self.handle, ptr::null(), flags) }, self.libzfs);
+        Ok(())
+    }
+
+    /// Check whether this filesystem is currently mounted.
+    pub fn is_mounted(&self) -> bool {
+        0 != unsafe { sys::zfs_is_mounted(self.handle, ptr::null_mut()) }
+    }
+
+    /// Get the path this filesystem is currently mounted at, if it is mounted.
+    pub fn mount_path(&self) -> Option<PathBuf> {
+        let mut path: *mut c_char = ptr::null_mut();
+        if 0 == unsafe { sys::zfs_is_mounted(self.handle, &mut path) } {
+            return None;
+        }
+        let cstr = unsafe { CStr::from_ptr(path) };
+        let owned = PathBuf::from(cstr.to_string_lossy().into_owned());
+        unsafe { libc::free(path as *mut libc::c_void) };
+        Some(owned)
+    }
+
+    /// Unmount this filesystem and all mounted filesystems under it (like `zfs unmount` on a
+    /// filesystem with mounted descendants).
+    ///
+    /// Filesystems are unmounted in order of their mount paths, deepest first, so that nested
+    /// mounts are out of the way before the filesystems they're mounted inside of. Every
+    /// filesystem is attempted even if some fail, and if any did, this fails with
+    /// [`Error::Datasets`], holding the error for each one that couldn't be unmounted.
+    pub fn unmount_all(&self, force: bool) -> Result<()> {
+        // Not get_all_dependents, which also reaches clones of snapshots, wherever they are.
+        let all = self.get_descendent_filesystems(None)?
+            .into_iter()
+            .chain(std::iter::once(self.clone()));
+        let mut errors = BTreeMap::new();
+        for ds in deepest_mounted_first(all) {
+            if let Err(e) = ds.unmount(force) {
+                errors.insert(ds.get_name(), e);
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Datasets(errors))
+        }
+    }
+
+    /// Get all currently mounted filesystems under this one, not including itself, ordered by
+    /// their mount paths, deepest first, so they can safely be unmounted in order.
+    pub fn mounted_descendants(&self) -> Result<Vec<Dataset>> {
+        Ok(deepest_mounted_first(self.get_descendent_filesystems(None)?))
+    }
+}
+
+impl LibZfs {
+    /// Get the dataset which contains the given file or directory, or `None` if it isn't on ZFS.
+    ///
+    /// Symlinks in the path are resolved first, including the last component, so a symlink
+    /// resolves to the dataset containing what it points to, the same as `zfs_path_to_zhandle`
+    /// does by `stat`ing the path. Fails with an [`Error::Sys`] if the path doesn't exist.
+    ///
+    /// A path under a filesystem's `.zfs/snapshot/<name>` directory resolves to that snapshot if
+    /// `types` includes snapshots, or to the filesystem otherwise. Fails with `EZFS_BADTYPE` if
+    /// the dataset isn't one of `types`.
+    pub fn dataset_for_path(&self, path: &Path, types: DatasetTypeMask)
+        -> Result<Option<Dataset>>
+    {
+        let path = path.canonicalize().map_err(Error::Sys)?;
+        let mut fs_info: libc::statfs = unsafe { mem::zeroed() };
+        if 0 != unsafe { libc::statfs(path_cstring(&path)?.as_ptr(), &mut fs_info) } {
+            return Err(Error::Sys(io::Error::last_os_error()));
+        }
+        if fs_info.f_type as u32 != ZFS_SUPER_MAGIC {
+            return Ok(None);
+        }
+
+        // Snapshots are mounted on demand under .zfs/snapshot, but zfs_path_to_zhandle only
+        // opens filesystems, so open the snapshot by name, from its filesystem's.
+        let parts: Vec<&OsStr> = path.iter().collect();
+        let snapshot = parts.windows(3)
+            .position(|w| w[0] == OsStr::new(".zfs") && w[1] == OsStr::new("snapshot"))
+            .map(|i| (parts[..i].iter().collect::<PathBuf>(), parts[i + 2]));
+        let fs_path = snapshot.as_ref().map(|(fs_path, _)| fs_path.as_path()).unwrap_or(&path);
+
+        // The path is absolute, so it isn't mistaken for a dataset name.
+        let types_fs = sys::zfs_type_t::ZFS_TYPE_FILESYSTEM;
+        let mark = ErrorMark::new(self.handle);
+        let handle = unsafe {
+            sys::zfs_path_to_zhandle(self.handle, path_cstring(fs_path)?.as_ptr(), types_fs)
+        };
+        if handle.is_null() {
+            return Err(mark.error(self.handle));
+        }
+        let mut dataset = Dataset { libzfs: self.handle, handle };
+
+        if let Some((_, snap)) = snapshot {
+            if types.0 & sys::zfs_type_t::ZFS_TYPE_SNAPSHOT != 0 {
+                let name = SafeString::from(
+                    format!("{}@{}", dataset.get_name(), snap.to_string_lossy()));
+                let types_snap = sys::zfs_type_t::ZFS_TYPE_SNAPSHOT as c_int;
+                let mark = ErrorMark::new(self.handle);
+                let handle = unsafe { sys::zfs_open(self.handle, name.as_ptr(), types_snap) };
+                if handle.is_null() {
+                    return Err(mark.error(self.handle));
+                }
+                dataset = Dataset { libzfs: self.handle, handle };
+            }
+        }
+
+        if dataset.get_type_mask().0 & types.0 == 0 {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADTYPE,
+                format!("'{}' is in {}, which isn't one of the types asked for",
+                    path.display(), dataset.get_name()),
+            )));
+        }
+        Ok(Some(dataset))
+    }
+}
+
+fn path_cstring(path: &Path) -> Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| Error::Sys(io::Error::new(io::ErrorKind::InvalidInput, e)))
+}
+
+/// Filter out the filesystems which aren't mounted, and sort the rest by mount path, deepest
+/// first.
+fn deepest_mounted_first(datasets: impl IntoIterator<Item = Dataset>) -> Vec<Dataset> {
+    let mut mounted = vec![];
+    for ds in datasets {
+        if ds.get_type() != DatasetType::Filesystem {
+            continue;
+        }
+        if let Some(path) = ds.mount_path() {
+            mounted.push((path, ds));
+        }
+    }
+
+    // A mount path sorts after any path it's nested inside of, so reverse order puts the
+    // deepest ones first.
+    mounted.sort_by(|a, b| b.0.cmp(&a.0));
+    mounted.into_iter().map(|(_, ds)| ds).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(opts: MountOpts) -> String {
+        String::from(opts.to_options_string().unwrap())
+    }
+
+    #[test]
+    fn options_string() {
+        assert_eq!(render(MountOpts::new()), "");
+        assert_eq!(render(MountOpts::new().remount(true).readonly(false)), "remount,rw");
+        assert_eq!(render(MountOpts::new().xattr(false).exec(true).readonly(true)),
+            "ro,exec,noxattr");
+        let all_off = MountOpts::new()
+            .readonly(false)
+            .exec(false)
+            .setuid(false)
+            .devices(false)
+            .atime(false)
+            .relatime(false)
+            .xattr(false);
+        assert_eq!(render(all_off), "rw,noexec,nosuid,nodev,noatime,norelatime,noxattr");
+        let all_on = MountOpts::new()
+            .readonly(true)
+            .exec(true)
+            .setuid(true)
+            .devices(true)
+            .atime(true)
+            .relatime(true)
+            .xattr(true);
+        assert_eq!(render(all_on), "ro,exec,suid,dev,atime,relatime,xattr");
+    }
+
+    #[test]
+    fn noatime_relatime_conflict() {
+        match MountOpts::new().atime(false).relatime(true).to_options_string() {
+            Err(Error::Zfs(e)) => assert_eq!(e.code, sys::zfs_error::EZFS_BADPROP),
+            other => panic!("expected EZFS_BADPROP, got {:?}", other),
+        }
+        assert_eq!(render(MountOpts::new().atime(true).relatime(true)), "atime,relatime");
+        assert_eq!(render(MountOpts::new().relatime(true)), "relatime");
+        assert_eq!(render(MountOpts::new().atime(false).relatime(false)), "noatime,norelatime");
+    }
+}
@@ -0,0 +1,283 @@
+use libzfs_sys as sys;
+
+use std::ffi::CStr;
+use std::marker::PhantomData;
+use std::os::raw::{c_char, c_uint};
+use std::ptr;
+
+use crate::SafeString;
+
+/// An nvlist owned by us, which is freed when dropped. Used for building up arguments to libzfs
+/// calls.
+#[derive(Debug)]
+pub(crate) struct OwnedNvList {
+    nvl: *mut sys::nvlist_t,
+}
+
+impl OwnedNvList {
+    pub fn new() -> Self {
+        // fnvlist_alloc aborts instead of returning failure
+        OwnedNvList { nvl: unsafe { sys::fnvlist_alloc() } }
+    }
+
+    /// Take ownership of an nvlist allocated by libzfs.
+    ///
+    /// Safety: `nvl` must be a valid nvlist that nothing else will free.
+    pub unsafe fn from_raw(nvl: *mut sys::nvlist_t) -> Self {
+        OwnedNvList { nvl }
+    }
+
+    /// Give up ownership of the nvlist, for libzfs calls which take it over.
+    pub fn into_raw(self) -> *mut sys::nvlist_t {
+        let nvl = self.nvl;
+        std::mem::forget(self);
+        nvl
+    }
+
+    pub fn borrow(&self) -> NvList<'_> {
+        unsafe { NvList::from_ptr(self.nvl) }
+    }
+
+    pub fn as_ptr(&self) -> *mut sys::nvlist_t {
+        self.nvl
+    }
+
+    pub fn is_empty(&self) -> bool {
+        0 != unsafe { sys::nvlist_empty(self.nvl) }
+    }
+
+    pub fn add_boolean(&mut self, name: &str) {
+        let name = SafeString::from(name);
+        unsafe { sys::fnvlist_add_boolean(self.nvl, name.as_ptr()) };
+    }
+
+    pub fn add_u32(&mut self, name: &str, value: u32) {
+        let name = SafeString::from(name);
+        unsafe { sys::fnvlist_add_uint32(self.nvl, name.as_ptr(), value) };
+    }
+
+    pub fn add_u64(&mut self, name: &str, value: u64) {
+        let name = SafeString::from(name);
+        unsafe { sys::fnvlist_add_uint64(self.nvl, name.as_ptr(), value) };
+    }
+
+    pub fn add_string(&mut self, name: &str, value: &str) {
+        let name = SafeString::from(name);
+        let value = SafeString::from(value);
+        unsafe { sys::fnvlist_add_string(self.nvl, name.as_ptr(), value.as_ptr()) };
+    }
+
+    /// Adds a copy of the given nvlist.
+    pub fn add_nvlist(&mut self, name: &str, value: &OwnedNvList) {
+        let name = SafeString::from(name);
+        unsafe { sys::fnvlist_add_nvlist(self.nvl, name.as_ptr(), value.nvl) };
+    }
+
+    /// Adds copies of the given nvlists.
+    pub fn add_nvlist_array(&mut self, name: &str, values: &[OwnedNvList]) {
+        let name = SafeString::from(name);
+        let mut ptrs: Vec<*mut sys::nvlist_t> = values.iter().map(|v| v.nvl).collect();
+        unsafe {
+            sys::fnvlist_add_nvlist_array(
+                self.nvl,
+                name.as_ptr(),
+                ptrs.as_mut_ptr() as _,
+                ptrs.len() as c_uint,
+            )
+        };
+    }
+}
+
+impl Drop for OwnedNvList {
+    fn drop(&mut self) {
+        unsafe { sys::nvlist_free(self.nvl) };
+    }
+}
+
+/// A read-only view of an nvlist (name/value list) owned by someone else, such as a pool's
+/// configuration.
+///
+/// Lookups return `None` if there is no member with the given name, or if it has a different
+/// type.
+#[derive(Debug, Clone, Copy)]
+pub struct NvList<'a> {
+    nvl: *mut sys::nvlist_t,
+    _owner: PhantomData<&'a sys::nvlist_t>,
+}
+
+impl<'a> NvList<'a> {
+    /// Wrap a raw nvlist pointer.
+    ///
+    /// # Safety
+    /// `nvl` must be a valid nvlist which lives at least as long as `'a`.
+    pub unsafe fn from_ptr(nvl: *mut sys::nvlist_t) -> Self {
+        NvList { nvl, _owner: PhantomData }
+    }
+
+    pub fn as_ptr(&self) -> *mut sys::nvlist_t {
+        self.nvl
+    }
+
+    /// Make an owned copy of this nvlist.
+    pub(crate) fn to_owned(self) -> OwnedNvList {
+        unsafe { OwnedNvList::from_raw(sys::fnvlist_dup(self.nvl)) }
+    }
+
+    /// Whether there is a member with the given name, of any type.
+    pub fn contains(&self, name: &str) -> bool {
+        let name = SafeString::from(name);
+        unsafe { sys::nvlist_exists(self.nvl, name.as_ptr()) != 0 }
+    }
+
+    /// Look up a `boolean_value` member. Members added with `nvlist_add_boolean`, which have no
+    /// value and are only present or absent, should be checked with [`NvList::contains`]
+    /// instead.
+    pub fn lookup_bool(&self, name: &str) -> Option<bool> {
+        let name = SafeString::from(name);
+        let mut value: sys::boolean_t = 0;
+        match unsafe { sys::nvlist_lookup_boolean_value(self.nvl, name.as_ptr(), &mut value) } {
+            0 => Some(value != 0),
+            _ => None,
+        }
+    }
+
+    /// Look up a `uint32` member.
+    pub fn lookup_u32(&self, name: &str) -> Option<u32> {
+        let name = SafeString::from(name);
+        let mut value = 0u32;
+        match unsafe { sys::nvlist_lookup_uint32(self.nvl, name.as_ptr(), &mut value) } {
+            0 => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Look up an `int32` member.
+    pub fn lookup_i32(&self, name: &str) -> Option<i32> {
+        let name = SafeString::from(name);
+        let mut value = 0i32;
+        match unsafe { sys::nvlist_lookup_int32(self.nvl, name.as_ptr(), &mut value) } {
+            0 => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Look up an `int64` member.
+    pub fn lookup_i64(&self, name: &str) -> Option<i64> {
+        let name = SafeString::from(name);
+        let mut value = 0i64;
+        match unsafe { sys::nvlist_lookup_int64(self.nvl, name.as_ptr(), &mut value) } {
+            0 => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Look up a `uint64` member.
+    pub fn lookup_u64(&self, name: &str) -> Option<u64> {
+        let name = SafeString::from(name);
+        let mut value = 0u64;
+        match unsafe { sys::nvlist_lookup_uint64(self.nvl, name.as_ptr(), &mut value) } {
+            0 => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Look up a `uint64` array member.
+    pub fn lookup_u64_array(&self, name: &str) -> Option<&'a [u64]> {
+        let name = SafeString::from(name);
+        let mut values: *mut u64 = ptr::null_mut();
+        let mut len: c_uint = 0;
+        match unsafe {
+            sys::nvlist_lookup_uint64_array(self.nvl, name.as_ptr(), &mut values, &mut len)
+        } {
+            0 if len == 0 => Some(&[]),
+            0 => Some(unsafe { std::slice::from_raw_parts(values, len as usize) }),
+            _ => None,
+        }
+    }
+
+    /// Look up an `int64` array member.
+    pub fn lookup_i64_array(&self, name: &str) -> Option<&'a [i64]> {
+        let name = SafeString::from(name);
+        let mut values: *mut i64 = ptr::null_mut();
+        let mut len: c_uint = 0;
+        match unsafe {
+            sys::nvlist_lookup_int64_array(self.nvl, name.as_ptr(), &mut values, &mut len)
+        } {
+            0 if len == 0 => Some(&[]),
+            0 => Some(unsafe { std::slice::from_raw_parts(values, len as usize) }),
+            _ => None,
+        }
+    }
+
+    /// Look up a nested nvlist member.
+    pub fn lookup_nvlist(&self, name: &str) -> Option<NvList<'a>> {
+        let name = SafeString::from(name);
+        let mut value: *mut sys::nvlist_t = ptr::null_mut();
+        match unsafe { sys::nvlist_lookup_nvlist(self.nvl, name.as_ptr(), &mut value) } {
+            0 => Some(unsafe { NvList::from_ptr(value) }),
+            _ => None,
+        }
+    }
+
+    /// Look up an array of nested nvlists.
+    pub fn lookup_nvlist_array(&self, name: &str) -> Option<Vec<NvList<'a>>> {
+        let name = SafeString::from(name);
+        let mut values: *mut *mut sys::nvlist_t = ptr::null_mut();
+        let mut len: c_uint = 0;
+        match unsafe {
+            sys::nvlist_lookup_nvlist_array(self.nvl, name.as_ptr(), &mut values, &mut len)
+        } {
+            0 if len == 0 => Some(vec![]),
+            0 => {
+                let ptrs = unsafe { std::slice::from_raw_parts(values, len as usize) };
+                Some(ptrs.iter().map(|&nvl| unsafe { NvList::from_ptr(nvl) }).collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// Look up a string member.
+    pub fn lookup_string(&self, name: &str) -> Option<SafeString> {
+        let name = SafeString::from(name);
+        let mut value: *mut c_char = ptr::null_mut();
+        match unsafe { sys::nvlist_lookup_string(self.nvl, name.as_ptr(), &mut value) } {
+            0 => {
+                let cstr = unsafe { CStr::from_ptr(value) };
+                Some(SafeString::from(cstr.to_string_lossy().into_owned()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Iterate over the names of all members.
+    pub fn names(&self) -> impl Iterator<Item = SafeString> + 'a {
+        let nvl = self.nvl;
+        let mut pair: *mut sys::nvpair_t = ptr::null_mut();
+        std::iter::from_fn(move || {
+            pair = unsafe { sys::nvlist_next_nvpair(nvl, pair) };
+            if pair.is_null() {
+                return None;
+            }
+            let name = unsafe { CStr::from_ptr(sys::nvpair_name(pair)) };
+            Some(SafeString::from(name.to_string_lossy().into_owned()))
+        })
+    }
+
+    /// Iterate over the members whose values are nvlists, along with their names.
+    pub fn nvlists(&self) -> impl Iterator<Item = (SafeString, NvList<'a>)> + 'a {
+        let nvl = self.nvl;
+        let mut pair: *mut sys::nvpair_t = ptr::null_mut();
+        std::iter::from_fn(move || loop {
+            pair = unsafe { sys::nvlist_next_nvpair(nvl, pair) };
+            if pair.is_null() {
+                return None;
+            }
+            let mut value: *mut sys::nvlist_t = ptr::null_mut();
+            if 0 == unsafe { sys::nvpair_value_nvlist(pair, &mut value) } {
+                let name = unsafe { CStr::from_ptr(sys::nvpair_name(pair)) };
+                let name = SafeString::from(name.to_string_lossy().into_owned());
+                return Some((name, unsafe { NvList::from_ptr(value) }));
+            }
+        })
+    }
+}
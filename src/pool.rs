@@ -0,0 +1,382 @@
+use libzfs_sys as sys;
+
+use std::collections::BTreeMap;
+use std::mem;
+use std::os::raw::{c_char, c_int};
+use std::path::PathBuf;
+use std::ptr;
+
+use crate::nvlist::{NvList, OwnedNvList};
+use crate::{
+    ztry, Error, ErrorMark, LibZfs, Result, SafeString, Vdev, VdevClass, VdevId, VdevSpec, ZPool,
+    ZPoolState, ZfsError,
+};
+
+/// Options for creating a pool.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PoolCreateOpts {
+    /// Pool properties, like `ashift` (`zpool create -o`).
+    pub props: BTreeMap<String, String>,
+    /// Properties for the pool's root filesystem, like `compression` (`zpool create -O`).
+    pub fs_props: BTreeMap<String, String>,
+    /// Alternate root directory to mount the pool's filesystems under (`zpool create -R`). This
+    /// also sets `cachefile=none` unless a cachefile is given in `props`.
+    pub altroot: Option<PathBuf>,
+    /// Skip the checks for mismatched redundancy between vdevs (`zpool create -f`).
+    pub force: bool,
+}
+
+/// A copy of a pool's configuration, as cached by libzfs when it was taken. Get one with
+/// [`ZPool::get_config`].
+///
+/// This has typed getters for the well-known members; anything else can be read through
+/// [`PoolConfig::nvlist`]. Getters return `None` if the member is missing, as some are for
+/// pools which were never imported on this system.
+#[derive(Debug)]
+pub struct PoolConfig {
+    nvl: OwnedNvList,
+}
+
+impl Clone for PoolConfig {
+    fn clone(&self) -> Self {
+        PoolConfig { nvl: self.nvl.borrow().to_owned() }
+    }
+}
+
+impl PoolConfig {
+    /// The underlying nvlist, for looking up members which don't have their own getter.
+    pub fn nvlist(&self) -> NvList<'_> {
+        self.nvl.borrow()
+    }
+
+    pub fn name(&self) -> Option<SafeString> {
+        self.nvlist().lookup_string("name")
+    }
+
+    pub fn pool_guid(&self) -> Option<u64> {
+        self.nvlist().lookup_u64("pool_guid")
+    }
+
+    /// The on-disk version of the pool. Pools using feature flags report 5000.
+    pub fn version(&self) -> Option<u64> {
+        self.nvlist().lookup_u64("version")
+    }
+
+    pub fn state(&self) -> Option<ZPoolState> {
+        self.nvlist().lookup_u64("state").map(|raw| ZPoolState::from(raw as sys::pool_state_t))
+    }
+
+    /// The transaction group the configuration was last written in.
+    pub fn txg(&self) -> Option<u64> {
+        self.nvlist().lookup_u64("txg")
+    }
+
+    /// The host ID of the system which last imported the pool.
+    pub fn hostid(&self) -> Option<u64> {
+        self.nvlist().lookup_u64("hostid")
+    }
+
+    /// The hostname of the system which last imported the pool.
+    pub fn hostname(&self) -> Option<SafeString> {
+        self.nvlist().lookup_string("hostname")
+    }
+
+    /// The raw vdev tree. [`ZPool::vdev_tree`] gives a structured version of this.
+    pub fn vdev_tree(&self) -> Option<NvList<'_>> {
+        self.nvlist().lookup_nvlist("vdev_tree")
+    }
+}
+
+/// Options for exporting a pool.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExportOpts {
+    /// Forcibly unmount busy datasets, and export even if the pool has an active shared spare
+    /// (`zpool export -f`).
+    pub force: bool,
+    /// Export without updating the on-disk labels to mark the pool as exported, so that it still
+    /// appears to be in use (`zpool export -F`).
+    pub hard_force: bool,
+    /// Message to record in the pool's history for the export.
+    pub log_message: Option<String>,
+}
+
+/// Options for adding vdevs to a pool.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AddOpts {
+    /// Add the vdevs even if their redundancy doesn't match the pool's existing vdevs
+    /// (`zpool add -f`).
+    pub force: bool,
+    /// Check the vdevs and return the resulting layout, without actually adding them
+    /// (`zpool add -n`).
+    pub dry_run: bool,
+}
+
+/// Options for splitting a pool of mirrors into two pools.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SplitOpts {
+    /// Devices to move to the new pool, at most one from each mirror. Mirrors with no device
+    /// listed give up their last device, as with `zpool split` given no devices.
+    pub vdevs: Vec<VdevId>,
+    /// Properties for the new pool (`zpool split -o`).
+    pub props: BTreeMap<String, String>,
+    /// Alternate root directory to mount the new pool's filesystems under (`zpool split -R`).
+    /// This implies `import`.
+    pub altroot: Option<PathBuf>,
+    /// Import the new pool and mount its filesystems, instead of leaving it exported
+    /// (`zpool split` without `-R` leaves it exported).
+    pub import: bool,
+}
+
+impl LibZfs {
+    /// Create a new pool out of the given vdevs, and open it.
+    pub fn create_pool(&self, name: &SafeString, spec: &VdevSpec, opts: PoolCreateOpts) -> Result<ZPool> {
+        spec.validate(opts.force)?;
+        if !spec.has_data_vdevs() {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_INVALCONFIG,
+                "invalid vdev specification: at least one data vdev is required",
+            )));
+        }
+        let nvroot = spec.to_nvlist()?;
+
+        let mut props = OwnedNvList::new();
+        for (k, v) in &opts.props {
+            props.add_string(k, v);
+        }
+        if let Some(altroot) = &opts.altroot {
+            let altroot = SafeString::from_path(altroot)?;
+            props.add_string("altroot", altroot.as_ref());
+            if !opts.props.contains_key("cachefile") {
+                props.add_string("cachefile", "none");
+            }
+        }
+
+        let mut fs_props = OwnedNvList::new();
+        for (k, v) in &opts.fs_props {
+            fs_props.add_string(k, v);
+        }
+
+        ztry!(unsafe {
+            sys::zpool_create(
+                self.handle,
+                name.as_ptr(),
+                nvroot.as_ptr(),
+                if props.is_empty() { ptr::null_mut() } else { props.as_ptr() },
+                if fs_props.is_empty() { ptr::null_mut() } else { fs_props.as_ptr() },
+            )
+        }, self.handle);
+
+        self.pool_by_name(name)
+    }
+}
+
+impl ZPool {
+    /// Add vdevs to the pool (like `zpool add`), and return the pool's resulting vdev tree.
+    ///
+    /// Unless [`AddOpts::force`] is set, new data, special and dedup vdevs must have the same
+    /// redundancy as the pool's existing ones, so that adding a single disk to a pool of mirrors
+    /// fails with `EZFS_INVALCONFIG`.
+    ///
+    /// With [`AddOpts::dry_run`], the returned tree shows the new vdevs as they would be added,
+    /// with a GUID of 0 and an unknown state.
+    pub fn add_vdevs(&self, spec: &VdevSpec, opts: AddOpts) -> Result<Vdev> {
+        spec.validate(opts.force)?;
+        let mut tree = self.vdev_tree()?;
+
+        if !opts.force {
+            let existing = tree.children.iter()
+                .filter(|vdev| {
+                    matches!(vdev.class, VdevClass::Normal | VdevClass::Special | VdevClass::Dedup)
+                })
+                .find_map(Vdev::redundancy);
+            if let Some(existing) = existing {
+                if let Some(new) = spec.redundancies().find(|new| *new != existing) {
+                    return Err(Error::Zfs(ZfsError::new(
+                        sys::zfs_error::EZFS_INVALCONFIG,
+                        format!("mismatched replication level: pool uses {} and new vdev is {}",
+                            existing, new),
+                    )));
+                }
+            }
+        }
+
+        let nvroot = spec.to_nvlist()?;
+        if opts.dry_run {
+            let new = Vdev::from_config(nvroot.borrow(), VdevClass::Normal);
+            tree.children.extend(new.children);
+            return Ok(tree);
+        }
+
+        ztry!(unsafe { sys::zpool_add(self.handle, nvroot.as_ptr()) }, self.libzfs);
+        let mut missing: sys::boolean_t = 0;
+        ztry!(unsafe { sys::zpool_refresh_stats(self.handle, &mut missing) }, self.libzfs);
+        self.vdev_tree()
+    }
+
+    /// Split off one device from each of the pool's mirrors into a new pool with the given name
+    /// (like `zpool split`). The new pool has the same data as this one, and is left exported
+    /// unless [`SplitOpts::import`] or [`SplitOpts::altroot`] is set.
+    ///
+    /// Every data vdev must be a mirror with all of its devices online and not resilvering;
+    /// otherwise this fails with `EZFS_INVALCONFIG` and libzfs's explanation, as it does if
+    /// more than one device of a mirror is given. Log and cache devices and spares stay with
+    /// this pool.
+    ///
+    /// If mounting the new pool's filesystems fails, the pool is left imported.
+    pub fn split(&self, new_pool_name: &SafeString, opts: SplitOpts) -> Result<()> {
+        let tree = self.config().lookup_nvlist("vdev_tree").ok_or_else(|| {
+            Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_INVALCONFIG,
+                "pool configuration has no vdev tree",
+            ))
+        })?;
+
+        // libzfs matches the chosen devices to the pool's by name, so pass it their own
+        // configuration.
+        let mut newroot = ptr::null_mut();
+        if !opts.vdevs.is_empty() {
+            let vdevs = self.vdev_tree()?;
+            let chosen = opts.vdevs.iter()
+                .map(|id| {
+                    vdevs.find(id)
+                        .and_then(|vdev| find_vdev_config(tree, vdev.guid))
+                        .map(NvList::to_owned)
+                        .ok_or_else(|| Error::Zfs(ZfsError::new(
+                            sys::zfs_error::EZFS_NODEVICE,
+                            "no such device in pool",
+                        )))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let mut root = OwnedNvList::new();
+            root.add_string("type", "root");
+            root.add_nvlist_array("children", &chosen);
+            newroot = root.into_raw();
+        }
+
+        let mut props = OwnedNvList::new();
+        for (k, v) in &opts.props {
+            props.add_string(k, v);
+        }
+        if let Some(altroot) = &opts.altroot {
+            props.add_string("altroot", SafeString::from_path(altroot)?.as_ref());
+        }
+
+        let import = opts.import || opts.altroot.is_some();
+        let mut flags: sys::splitflags_t = unsafe { mem::zeroed() };
+        flags.set_import(import as c_int);
+
+        let mark = ErrorMark::new(self.libzfs);
+        let err = unsafe {
+            sys::zpool_vdev_split(
+                self.handle,
+                new_pool_name.as_ptr() as *mut c_char,
+                &mut newroot,
+                if props.is_empty() { ptr::null_mut() } else { props.as_ptr() },
+                flags,
+            )
+        };
+        let error = (err != 0).then(|| mark.error(self.libzfs));
+        // libzfs takes over the device list, and replaces it with the new pool's vdev tree, which
+        // is ours to free.
+        if !newroot.is_null() {
+            drop(unsafe { OwnedNvList::from_raw(newroot) });
+        }
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        if import {
+            let mark = ErrorMark::new(self.libzfs);
+            let handle = unsafe { sys::zpool_open_canfail(self.libzfs, new_pool_name.as_ptr()) };
+            if handle.is_null() {
+                return Err(mark.error(self.libzfs));
+            }
+            let new_pool = ZPool { libzfs: self.libzfs, handle };
+            ztry!(unsafe {
+                sys::zpool_enable_datasets(new_pool.handle, ptr::null(), 0)
+            }, self.libzfs);
+        }
+        Ok(())
+    }
+
+    /// Give the pool a new random GUID (like `zpool reguid`), and return it. This lets a copy
+    /// of a pool's devices, such as a cloned VM image, be imported alongside the original.
+    ///
+    /// The kernel refuses with `EZFS_IO` unless all of the pool's devices are healthy, and with
+    /// `EZFS_CHECKPOINT_EXISTS` or `EZFS_DISCARDING_CHECKPOINT` if the pool has a checkpoint. A
+    /// scrub or resilver in progress doesn't prevent it.
+    ///
+    /// The root of a [`Vdev`] tree read beforehand, and an [`ImportablePool`] found
+    /// beforehand, still have the old GUID.
+    ///
+    /// [`ImportablePool`]: crate::ImportablePool
+    pub fn reguid(&self) -> Result<u64> {
+        ztry!(unsafe { sys::zpool_reguid(self.handle) }, self.libzfs);
+        let mut missing: sys::boolean_t = 0;
+        ztry!(unsafe { sys::zpool_refresh_stats(self.handle, &mut missing) }, self.libzfs);
+        self.guid()
+    }
+
+    /// Get the pool's configuration, as cached by libzfs.
+    pub(crate) fn config(&self) -> NvList<'_> {
+        unsafe { NvList::from_ptr(sys::zpool_get_config(self.handle, ptr::null_mut())) }
+    }
+
+    /// Get a copy of the pool's full configuration, as cached by libzfs. The cache is refreshed
+    /// by operations which change the pool's devices, and by [`ZPool::scan_stats`]; the copy
+    /// isn't, so get a new one to see those changes.
+    pub fn get_config(&self) -> PoolConfig {
+        PoolConfig { nvl: self.config().to_owned() }
+    }
+
+    /// Destroy this pool, first unmounting and unsharing all of its datasets (like
+    /// `zpool destroy`). With `force_unmount`, busy filesystems are forcibly unmounted.
+    ///
+    /// If a dataset can't be unmounted, or a volume is in use, the pool is left intact and the
+    /// error names the mountpoint or reports the pool as busy. Any scrub or resilver in progress
+    /// is simply abandoned, as with the CLI.
+    pub fn destroy(self, force_unmount: bool) -> Result<()> {
+        ztry!(unsafe {
+            sys::zpool_disable_datasets(self.handle, force_unmount as sys::boolean_t)
+        }, self.libzfs);
+        ztry!(unsafe { sys::zpool_destroy(self.handle, ptr::null()) }, self.libzfs);
+        // The handle still needs to be closed, which dropping self does.
+        Ok(())
+    }
+
+    /// Export this pool, first unmounting and unsharing all of its datasets (like
+    /// `zpool export`). The pool can be found again afterwards with the import scanning API.
+    ///
+    /// A pool with busy datasets fails with `EZFS_BUSY`, and one with a shared hot spare in use
+    /// fails with `EZFS_ACTIVE_SPARE` unless forced. If a device removal is in progress, the error
+    /// is `EZFS_DEVRM_IN_PROGRESS`.
+    pub fn export(self, opts: ExportOpts) -> Result<()> {
+        let log_message = opts.log_message.map(SafeString::from);
+        let log_ptr = log_message.as_ref().map(|s| s.as_ptr()).unwrap_or(ptr::null());
+
+        ztry!(unsafe {
+            sys::zpool_disable_datasets(self.handle, opts.force as sys::boolean_t)
+        }, self.libzfs);
+
+        if opts.hard_force {
+            ztry!(unsafe { sys::zpool_export_force(self.handle, log_ptr) }, self.libzfs);
+        } else {
+            ztry!(unsafe {
+                sys::zpool_export(self.handle, opts.force as sys::boolean_t, log_ptr)
+            }, self.libzfs);
+        }
+        // The handle still needs to be closed, which dropping self does.
+        Ok(())
+    }
+}
+
+/// Find the configuration of the vdev with the given GUID in a vdev tree.
+fn find_vdev_config(config: NvList<'_>, guid: u64) -> Option<NvList<'_>> {
+    if config.lookup_u64("guid") == Some(guid) {
+        return Some(config);
+    }
+    config.lookup_nvlist_array("children")
+        .unwrap_or_default()
+        .into_iter()
+        .find_map(|child| find_vdev_config(child, guid))
+}
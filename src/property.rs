@@ -0,0 +1,833 @@
+use libzfs_sys as sys;
+
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::mem;
+use std::ops::RangeInclusive;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use crate::nvlist::{NvList, OwnedNvList};
+use crate::{ztry, Dataset, Error, ErrorMark, Result, SafeString, ZPool, ZfsError};
+
+translate_enum! {
+    new_name: PropSource,
+    sys_name: sys::zprop_source_t,
+    repr: u32,
+    variants: {
+        ZPROP_SRC_NONE => None,
+        ZPROP_SRC_DEFAULT => Default,
+        ZPROP_SRC_TEMPORARY => Temporary,
+        ZPROP_SRC_LOCAL => Local,
+        ZPROP_SRC_INHERITED => Inherited,
+        ZPROP_SRC_RECEIVED => Received,
+    }
+}
+
+translate_enum! {
+    new_name: CanMount,
+    sys_name: sys::zfs_canmount_type_t,
+    repr: u32,
+    variants: {
+        ZFS_CANMOUNT_OFF => Off,
+        ZFS_CANMOUNT_ON => On,
+        ZFS_CANMOUNT_NOAUTO => NoAuto,
+    }
+}
+
+impl CanMount {
+    /// The property value string for this setting.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CanMount::Off => "off",
+            CanMount::On => "on",
+            CanMount::NoAuto => "noauto",
+        }
+    }
+}
+
+/// The `compression` property: the algorithm used to compress new data written to a dataset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Compression {
+    Off,
+    /// The default algorithm, which is `lz4` if the `lz4_compress` feature is enabled, or `lzjb`
+    /// otherwise.
+    On,
+    Lzjb,
+    Zle,
+    Lz4,
+    /// gzip at the given level, from 1 to 9. Plain `gzip` is level 6.
+    Gzip(u8),
+    /// zstd at the given level, from 1 to 19. Plain `zstd` is level 3.
+    Zstd(u8),
+    /// zstd's faster, lower-ratio mode at the given level: 1 to 10, multiples of 10 up to 100,
+    /// 500 or 1000. Plain `zstd-fast` is level 1.
+    ZstdFast(u16),
+    /// A value this library doesn't know about, from a newer version of ZFS.
+    Other(SafeString),
+}
+
+impl Compression {
+    fn from_str(s: &str) -> Self {
+        match s {
+            "off" => return Compression::Off,
+            "on" => return Compression::On,
+            "lzjb" => return Compression::Lzjb,
+            "zle" => return Compression::Zle,
+            "lz4" => return Compression::Lz4,
+            "gzip" => return Compression::Gzip(6),
+            "zstd" => return Compression::Zstd(3),
+            "zstd-fast" => return Compression::ZstdFast(1),
+            _ => (),
+        }
+        // zstd-fast has to be checked first, because it also starts with "zstd-".
+        let parsed = if let Some(level) = s.strip_prefix("zstd-fast-") {
+            level.parse().ok().map(Compression::ZstdFast)
+        } else if let Some(level) = s.strip_prefix("zstd-") {
+            level.parse().ok().map(Compression::Zstd)
+        } else if let Some(level) = s.strip_prefix("gzip-") {
+            level.parse().ok().map(Compression::Gzip)
+        } else {
+            None
+        };
+        // Levels ZFS doesn't have aren't this library's to interpret.
+        parsed.filter(Compression::has_valid_level)
+            .unwrap_or_else(|| Compression::Other(SafeString::from(s)))
+    }
+
+    /// Whether the level, for the algorithms which have one, is one ZFS accepts.
+    fn has_valid_level(&self) -> bool {
+        match *self {
+            Compression::Gzip(level) => (1..=9).contains(&level),
+            Compression::Zstd(level) => (1..=19).contains(&level),
+            Compression::ZstdFast(level) => {
+                (1..=10).contains(&level)
+                    || ((10..=100).contains(&level) && level % 10 == 0)
+                    || level == 500
+                    || level == 1000
+            }
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    /// Formats the value as the property takes it, like `gzip-6`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Compression::Off => f.write_str("off"),
+            Compression::On => f.write_str("on"),
+            Compression::Lzjb => f.write_str("lzjb"),
+            Compression::Zle => f.write_str("zle"),
+            Compression::Lz4 => f.write_str("lz4"),
+            Compression::Gzip(level) => write!(f, "gzip-{}", level),
+            Compression::Zstd(level) => write!(f, "zstd-{}", level),
+            Compression::ZstdFast(level) => write!(f, "zstd-fast-{}", level),
+            Compression::Other(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// How access times are updated, which is set by the `atime` and `relatime` properties
+/// together.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AtimeMode {
+    /// Access times aren't updated (`atime=off`), whatever `relatime` is set to.
+    Off,
+    /// Access times are updated on every access (`atime=on`, `relatime=off`).
+    On,
+    /// Access times are only updated if they're older than the modification or change time, or
+    /// more than a day old (`atime=on`, `relatime=on`).
+    Relatime,
+}
+
+/// A property value, along with where that value comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Property<T> {
+    pub value: T,
+    pub source: PropSource,
+}
+
+impl Dataset {
+    /// Get the value of a property as a string, in the same parsable form that `zfs get -p`
+    /// prints.
+    pub fn get_property(&self, name: &str) -> Result<Property<SafeString>> {
+        let prop = prop_from_name(name)?;
+        let mut buf = vec![0 as c_char; libc::PATH_MAX as usize];
+        let mut source = ZPROP_SRC_NONE;
+        if 0 != unsafe {
+            sys::zfs_prop_get(
+                self.handle,
+                prop,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut source,
+                ptr::null_mut(),
+                0,
+                1, // literal
+            )
+        } {
+            return Err(prop_unavailable(name));
+        }
+        let cstr = unsafe { CStr::from_ptr(buf.as_ptr()) };
+        Ok(Property {
+            value: SafeString::from(cstr.to_string_lossy().into_owned()),
+            source: PropSource::from(source),
+        })
+    }
+
+    /// Get the value of a numeric, boolean or index property as a number.
+    pub fn get_numeric_property(&self, name: &str) -> Result<Property<u64>> {
+        let prop = prop_from_name(name)?;
+        let mut value = 0u64;
+        let mut source = ZPROP_SRC_NONE;
+        if 0 != unsafe {
+            sys::zfs_prop_get_numeric(self.handle, prop, &mut value, &mut source, ptr::null_mut(), 0)
+        } {
+            return Err(prop_unavailable(name));
+        }
+        Ok(Property {
+            value,
+            source: PropSource::from(source),
+        })
+    }
+
+    /// Get the value of an on/off property.
+    ///
+    /// Properties which can be overridden by temporary mount options (`readonly`, `exec`,
+    /// `setuid`, `atime`, etc.) report the effective value of the current mount, with
+    /// [`PropSource::Temporary`] as the source if it differs from the stored value.
+    pub fn get_bool_property(&self, name: &str) -> Result<Property<bool>> {
+        self.get_numeric_property(name).map(|p| Property { value: p.value != 0, source: p.source })
+    }
+
+    /// Set a property to the given value, as `zfs set` would.
+    pub fn set_property(&self, name: &str, value: &str) -> Result<()> {
+        let name = SafeString::from(name);
+        let value = SafeString::from(value);
+        ztry!(unsafe { sys::zfs_prop_set(self.handle, name.as_ptr(), value.as_ptr()) }, self.libzfs);
+        Ok(())
+    }
+
+    /// Clear a locally set property so that it is inherited from the parent, or reverts to its
+    /// received value if `received` is set (like `zfs inherit -S`).
+    pub fn inherit_property(&self, name: &str, received: bool) -> Result<()> {
+        let name = SafeString::from(name);
+        ztry!(unsafe {
+            sys::zfs_prop_inherit(self.handle, name.as_ptr(), received as sys::boolean_t)
+        }, self.libzfs);
+        Ok(())
+    }
+
+    /// Set a property's received value, as `zfs receive` does for properties in the stream,
+    /// instead of its local value. A local value still overrides it, and
+    /// [`Dataset::inherit_property`] with `received` set reverts to it.
+    ///
+    /// Other received values of the dataset are kept.
+    ///
+    /// This needs OpenZFS 2.0 through 2.2, and fails with `EZFS_BADVERSION` with other versions
+    /// of the kernel module. If the kernel rejects the new value, or one of the values being
+    /// kept, this fails with [`Error::Properties`], giving the reason for each.
+    pub fn set_received_property(&self, name: &str, value: &str) -> Result<()> {
+        let mut props = OwnedNvList::new();
+        props.add_string(name, value);
+
+        // Setting received properties clears any others the dataset has, so pass those along
+        // too.
+        let recvd = unsafe { sys::zfs_get_recvd_props(self.handle) };
+        if !recvd.is_null() {
+            for (other, prop) in unsafe { NvList::from_ptr(recvd) }.nvlists() {
+                if AsRef::<str>::as_ref(&other) == name {
+                    continue;
+                }
+                if let Some(value) = prop.lookup_u64("value") {
+                    props.add_u64(other.as_ref(), value);
+                } else if let Some(value) = prop.lookup_string("value") {
+                    props.add_string(other.as_ref(), value.as_ref());
+                }
+            }
+        }
+
+        // Check every property and convert its value to the type the kernel expects, as
+        // zfs_prop_set does, since the ioctl below bypasses libzfs.
+        let errbuf = SafeString::from(
+            format!("cannot set received property for '{}'", self.get_name()));
+        let mark = ErrorMark::new(self.libzfs);
+        let props = unsafe {
+            let zoned = sys::zfs_prop_get_int(self.handle, sys::zfs_prop_t::ZFS_PROP_ZONED);
+            sys::zfs_valid_proplist(
+                self.libzfs,
+                sys::zfs_get_type(self.handle),
+                props.as_ptr(),
+                zoned,
+                self.handle,
+                sys::zfs_get_pool_handle(self.handle),
+                0, // key_params_ok
+                errbuf.as_ptr(),
+            )
+        };
+        if props.is_null() {
+            return Err(mark.error(self.libzfs));
+        }
+        let props = unsafe { OwnedNvList::from_raw(props) };
+
+        // libzfs has no call for this, so it's done with the same ioctl as zfs_prop_set, with
+        // zc_cookie set as zfs_receive does for received properties. This relies on zfs_cmd_t
+        // having the layout in libzfs-sys's bindings, so check the kernel module is a version
+        // with that layout before issuing it.
+        check_set_prop_version()?;
+        let mut zc: sys::zfs_cmd_t = unsafe { mem::zeroed() };
+        let ds_name = self.get_name();
+        let name_bytes = AsRef::<str>::as_ref(&ds_name).as_bytes();
+        if name_bytes.len() >= zc.zc_name.len() {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_NAMETOOLONG,
+                "dataset name is too long",
+            )));
+        }
+        for (dst, &src) in zc.zc_name.iter_mut().zip(name_bytes) {
+            *dst = src as c_char;
+        }
+        let mut packed_size = 0;
+        let packed = unsafe { sys::fnvlist_pack(props.as_ptr(), &mut packed_size) };
+        zc.zc_nvlist_src = packed as u64;
+        zc.zc_nvlist_src_size = packed_size as u64;
+        // The kernel returns the error for each property which couldn't be set here.
+        let mut errors_buf = vec![0u8; ERRORS_BUF_SIZE];
+        zc.zc_nvlist_dst = errors_buf.as_mut_ptr() as u64;
+        zc.zc_nvlist_dst_size = errors_buf.len() as u64;
+        zc.zc_cookie = 1; // received
+        let err = unsafe {
+            sys::zfs_ioctl(self.libzfs, sys::zfs_ioc::ZFS_IOC_SET_PROP as c_int, &mut zc)
+        };
+        let result = if err == 0 {
+            Ok(())
+        } else {
+            let os_error = io::Error::last_os_error();
+            let filled = zc.zc_nvlist_dst_filled != 0;
+            let errors = filled.then(|| unpack_errors(&mut errors_buf, zc.zc_nvlist_dst_size));
+            match errors.flatten() {
+                Some(errors) if !errors.is_empty() => Err(Error::Properties(errors)),
+                _ => Err(Error::Sys(os_error)),
+            }
+        };
+        unsafe {
+            sys::fnvlist_pack_free(packed, packed_size);
+            sys::zfs_refresh_properties(self.handle);
+        }
+        result
+    }
+
+    /// Get a property's received value, if it has one, in the same parsable form as
+    /// [`Dataset::get_property`]. This is the value set by `zfs receive` or
+    /// [`Dataset::set_received_property`], even if a local value overrides it.
+    pub fn get_received_property(&self, name: &str) -> Result<Option<SafeString>> {
+        let cname = SafeString::from(name);
+        let mut buf = vec![0 as c_char; libc::PATH_MAX as usize];
+        if 0 != unsafe {
+            sys::zfs_prop_get_recvd(self.handle, cname.as_ptr(), buf.as_mut_ptr(), buf.len(), 1)
+        } {
+            return Ok(None);
+        }
+        let cstr = unsafe { CStr::from_ptr(buf.as_ptr()) };
+        Ok(Some(SafeString::from(cstr.to_string_lossy().into_owned())))
+    }
+
+    /// Get the dataset's `guid` property, which stays the same when the dataset is renamed.
+    ///
+    /// Snapshots keep their GUIDs when sent and received, so a snapshot and its received copy
+    /// have the same GUID. Filesystems and volumes get new GUIDs when received.
+    pub fn stable_id(&self) -> Result<u64> {
+        self.get_numeric_property("guid").map(|p| p.value)
+    }
+
+    /// Find the dataset with the given [`stable_id`](Dataset::stable_id) among this dataset and
+    /// everything under it, such as to find a snapshot again after it or its parent was renamed.
+    pub fn find_by_stable_id(&self, guid: u64) -> Result<Option<Dataset>> {
+        if self.stable_id()? == guid {
+            return Ok(Some(self.clone()));
+        }
+        for ds in self.get_all_dependents()? {
+            if ds.stable_id()? == guid {
+                return Ok(Some(ds));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Get the dataset's `objsetid` property: the number of its object set within the pool.
+    /// This is the number in the dataset's kstats, like `/proc/spl/kstat/zfs/pool/objset-0x36`,
+    /// and in the kernel's messages about it.
+    ///
+    /// It's only unique within a pool, and unlike the [`stable_id`](Dataset::stable_id), it
+    /// changes when the dataset is received elsewhere.
+    pub fn get_objsetid(&self) -> Result<u64> {
+        self.get_numeric_property("objsetid").map(|p| p.value)
+    }
+
+    /// Get the `readonly` property.
+    pub fn get_readonly(&self) -> Result<Property<bool>> {
+        self.get_bool_property("readonly")
+    }
+
+    /// Set the `readonly` property.
+    ///
+    /// If the filesystem is mounted, libzfs remounts it so the change takes effect right away,
+    /// unless it was mounted with a temporary `ro` or `rw` option, which keeps overriding the
+    /// property until the filesystem is remounted. Use [`Dataset::set_readonly_remount`] to
+    /// replace such an override too.
+    pub fn set_readonly(&self, readonly: bool) -> Result<()> {
+        self.set_property("readonly", if readonly { "on" } else { "off" })
+    }
+
+    /// Get the `exec` property.
+    pub fn get_exec(&self) -> Result<Property<bool>> {
+        self.get_bool_property("exec")
+    }
+
+    /// Get the `setuid` property.
+    pub fn get_setuid(&self) -> Result<Property<bool>> {
+        self.get_bool_property("setuid")
+    }
+
+    /// Get how access times are updated, from the `atime` and `relatime` properties.
+    ///
+    /// Like [`Dataset::get_bool_property`], this reports the effective values of the current
+    /// mount.
+    pub fn get_atime(&self) -> Result<AtimeMode> {
+        if !self.get_bool_property("atime")?.value {
+            return Ok(AtimeMode::Off);
+        }
+        if self.get_bool_property("relatime")?.value {
+            Ok(AtimeMode::Relatime)
+        } else {
+            Ok(AtimeMode::On)
+        }
+    }
+
+    /// Set how access times are updated, by setting both the `atime` and `relatime` properties
+    /// locally, in one operation. For [`AtimeMode::Off`], `relatime` is turned off too.
+    pub fn set_atime(&self, mode: AtimeMode) -> Result<()> {
+        let (atime, relatime) = match mode {
+            AtimeMode::Off => ("off", "off"),
+            AtimeMode::On => ("on", "off"),
+            AtimeMode::Relatime => ("on", "on"),
+        };
+        let mut props = OwnedNvList::new();
+        props.add_string("atime", atime);
+        props.add_string("relatime", relatime);
+        ztry!(unsafe { sys::zfs_prop_set_list(self.handle, props.as_ptr()) }, self.libzfs);
+        Ok(())
+    }
+
+    /// Get the `canmount` property.
+    pub fn get_canmount(&self) -> Result<Property<CanMount>> {
+        self.get_numeric_property("canmount")
+            .map(|p| Property { value: CanMount::from(p.value as u32), source: p.source })
+    }
+
+    /// Set the `canmount` property.
+    pub fn set_canmount(&self, value: CanMount) -> Result<()> {
+        self.set_property("canmount", value.as_str())
+    }
+
+    /// Get the `compression` property.
+    pub fn get_compression(&self) -> Result<Property<Compression>> {
+        self.get_property("compression")
+            .map(|p| Property { value: Compression::from_str(p.value.as_ref()), source: p.source })
+    }
+
+    /// Set the `compression` property. This only affects data written afterwards.
+    ///
+    /// Fails with `EZFS_BADPROP` if the level isn't one the algorithm has.
+    pub fn set_compression(&self, value: &Compression) -> Result<()> {
+        if !value.has_valid_level() {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADPROP,
+                format!("'{}' is not a valid compression level", value),
+            )));
+        }
+        self.set_property("compression", &value.to_string())
+    }
+
+    /// Get the `snapshot_count` property: the number of snapshots of this dataset and its
+    /// descendants. Counts are only kept up to date while a limit is set on this dataset or one of
+    /// its ancestors.
+    pub fn get_snapshot_count(&self) -> Result<Property<u64>> {
+        self.get_numeric_property("snapshot_count")
+    }
+
+    /// Get the `filesystem_count` property: the number of filesystems and volumes under this one.
+    /// Counts are only kept up to date while a limit is set on this dataset or one of its
+    /// ancestors.
+    pub fn get_filesystem_count(&self) -> Result<Property<u64>> {
+        self.get_numeric_property("filesystem_count")
+    }
+
+    /// Get the `snapshot_limit` property, or `None` if there's no limit.
+    pub fn get_snapshot_limit(&self) -> Result<Property<Option<u64>>> {
+        self.get_limit_property("snapshot_limit")
+    }
+
+    /// Get the `filesystem_limit` property, or `None` if there's no limit.
+    pub fn get_filesystem_limit(&self) -> Result<Property<Option<u64>>> {
+        self.get_limit_property("filesystem_limit")
+    }
+
+    /// Set the `snapshot_limit` property, or remove the limit with `None`.
+    pub fn set_snapshot_limit(&self, limit: Option<u64>) -> Result<()> {
+        self.set_limit_property("snapshot_limit", limit)
+    }
+
+    /// Set the `filesystem_limit` property, or remove the limit with `None`.
+    pub fn set_filesystem_limit(&self, limit: Option<u64>) -> Result<()> {
+        self.set_limit_property("filesystem_limit", limit)
+    }
+
+    /// Get the `quota` property, or `None` if there's no quota.
+    pub fn get_quota(&self) -> Result<Property<Option<u64>>> {
+        self.get_space_property("quota")
+    }
+
+    /// Set the `quota` property, in bytes, or remove the quota with `None`. The quota limits the
+    /// space used by this dataset and its descendants, including snapshots.
+    pub fn set_quota(&self, bytes: Option<u64>) -> Result<()> {
+        self.set_limit_property("quota", bytes)
+    }
+
+    /// Get the `refquota` property, or `None` if there's no quota.
+    pub fn get_refquota(&self) -> Result<Property<Option<u64>>> {
+        self.get_space_property("refquota")
+    }
+
+    /// Set the `refquota` property, in bytes, or remove the quota with `None`. Unlike `quota`,
+    /// this only limits the space referenced by this dataset itself.
+    pub fn set_refquota(&self, bytes: Option<u64>) -> Result<()> {
+        self.set_limit_property("refquota", bytes)
+    }
+
+    /// Get the `reservation` property, or `None` if there's no reservation.
+    pub fn get_reservation(&self) -> Result<Property<Option<u64>>> {
+        self.get_space_property("reservation")
+    }
+
+    /// Set the `reservation` property, in bytes, or remove the reservation with `None`. The
+    /// reservation guarantees space for this dataset and its descendants.
+    pub fn set_reservation(&self, bytes: Option<u64>) -> Result<()> {
+        self.set_limit_property("reservation", bytes)
+    }
+
+    /// Get the `refreservation` property, or `None` if there's no reservation.
+    pub fn get_refreservation(&self) -> Result<Property<Option<u64>>> {
+        self.get_space_property("refreservation")
+    }
+
+    /// Set the `refreservation` property, in bytes, or remove the reservation with `None`.
+    /// Unlike `reservation`, this only guarantees space for this dataset itself, not its
+    /// descendants or snapshots.
+    ///
+    /// For volumes, `refreservation=auto` can be set with [`Dataset::set_property`].
+    pub fn set_refreservation(&self, bytes: Option<u64>) -> Result<()> {
+        self.set_limit_property("refreservation", bytes)
+    }
+
+    /// Get the `used` property: the space used by this dataset, its snapshots and descendants,
+    /// and its reservations, in bytes, after compression.
+    pub fn get_used(&self) -> Result<u64> {
+        self.get_numeric_property("used").map(|p| p.value)
+    }
+
+    /// Get the `referenced` property: the space used by the data this dataset can access, which
+    /// may be shared with other datasets, in bytes, after compression.
+    pub fn get_referenced(&self) -> Result<u64> {
+        self.get_numeric_property("referenced").map(|p| p.value)
+    }
+
+    /// Get the `usedbyrefreservation` property: the part of `used` which is set aside by
+    /// `refreservation` and not yet used.
+    pub fn get_used_by_refreservation(&self) -> Result<u64> {
+        self.get_numeric_property("usedbyrefreservation").map(|p| p.value)
+    }
+
+    /// Get the `logicalused` property: like `used`, but before compression, and not counting
+    /// reservations.
+    pub fn get_logicalused(&self) -> Result<u64> {
+        self.get_numeric_property("logicalused").map(|p| p.value)
+    }
+
+    /// Get the `logicalreferenced` property: like `referenced`, but before compression.
+    pub fn get_logicalreferenced(&self) -> Result<u64> {
+        self.get_numeric_property("logicalreferenced").map(|p| p.value)
+    }
+
+    /// Get the ratio of the referenced data's size before compression to its size after, which is
+    /// 1.0 if nothing is compressed. This is the `refcompressratio` property, but exact instead
+    /// of rounded to two decimal places.
+    pub fn compression_ratio(&self) -> Result<f64> {
+        let referenced = self.get_referenced()?;
+        if referenced == 0 {
+            return Ok(1.0);
+        }
+        Ok(self.get_logicalreferenced()? as f64 / referenced as f64)
+    }
+
+    // "none" is stored as UINT64_MAX.
+    fn get_limit_property(&self, name: &str) -> Result<Property<Option<u64>>> {
+        self.get_numeric_property(name).map(|p| Property {
+            value: Some(p.value).filter(|&v| v != u64::MAX),
+            source: p.source,
+        })
+    }
+
+    // Unlike the count limits, "none" for quotas and reservations is stored as 0.
+    fn get_space_property(&self, name: &str) -> Result<Property<Option<u64>>> {
+        self.get_numeric_property(name).map(|p| Property {
+            value: Some(p.value).filter(|&v| v != 0),
+            source: p.source,
+        })
+    }
+
+    fn set_limit_property(&self, name: &str, limit: Option<u64>) -> Result<()> {
+        match limit {
+            Some(limit) => self.set_property(name, &limit.to_string()),
+            None => self.set_property(name, "none"),
+        }
+    }
+}
+
+impl ZPool {
+    /// Get the value of a pool property as a string, in the same parsable form that
+    /// `zpool get -p` prints.
+    pub fn get_property(&self, name: &str) -> Result<Property<SafeString>> {
+        let prop = pool_prop_from_name(name)?;
+        let mut buf = vec![0 as c_char; libc::PATH_MAX as usize];
+        let mut source = ZPROP_SRC_NONE;
+        if 0 != unsafe {
+            sys::zpool_get_prop(
+                self.handle,
+                prop,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut source,
+                1, // literal
+            )
+        } {
+            // Like zfs_prop_get, this fails without setting a libzfs error.
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADPROP,
+                format!("property '{}' is not available for this pool", name),
+            )));
+        }
+        let cstr = unsafe { CStr::from_ptr(buf.as_ptr()) };
+        Ok(Property {
+            value: SafeString::from(cstr.to_string_lossy().into_owned()),
+            source: PropSource::from(source),
+        })
+    }
+
+    /// Set a pool property, including feature properties like `feature@async_destroy`.
+    pub fn set_property(&self, name: &str, value: &str) -> Result<()> {
+        let name = SafeString::from(name);
+        let value = SafeString::from(value);
+        ztry!(unsafe { sys::zpool_set_prop(self.handle, name.as_ptr(), value.as_ptr()) }, self.libzfs);
+        Ok(())
+    }
+
+    /// Get the value of a numeric, boolean or index pool property as a number.
+    pub fn get_numeric_property(&self, name: &str) -> Result<Property<u64>> {
+        let prop = pool_prop_from_name(name)?;
+        let mut source = ZPROP_SRC_NONE;
+        let value = unsafe { sys::zpool_get_prop_int(self.handle, prop, &mut source) };
+        Ok(Property {
+            value,
+            source: PropSource::from(source),
+        })
+    }
+
+    /// Get the `size` property: the total size of the pool, in bytes.
+    pub fn get_size(&self) -> Result<u64> {
+        self.get_numeric_property("size").map(|p| p.value)
+    }
+
+    /// Get the `allocated` property: the space used in the pool, in bytes.
+    pub fn get_allocated(&self) -> Result<u64> {
+        self.get_numeric_property("allocated").map(|p| p.value)
+    }
+
+    /// Get the `free` property: the space not yet used in the pool, in bytes.
+    pub fn get_free(&self) -> Result<u64> {
+        self.get_numeric_property("free").map(|p| p.value)
+    }
+
+    /// Get the `freeing` property: the space still being reclaimed from destroyed datasets, in
+    /// bytes, which will become free.
+    pub fn get_freeing(&self) -> Result<u64> {
+        self.get_numeric_property("freeing").map(|p| p.value)
+    }
+
+    /// Get the `fragmentation` property, as a percentage, or `None` if it isn't known (such as
+    /// when the `spacemap_histogram` feature isn't enabled).
+    pub fn get_fragmentation(&self) -> Result<Option<u8>> {
+        self.get_numeric_property("fragmentation")
+            .map(|p| Some(p.value).filter(|&v| v <= 100).map(|v| v as u8))
+    }
+
+    /// Get the `capacity` property: how full the pool is, as a percentage from 0 to 100.
+    pub fn capacity_percent(&self) -> Result<u8> {
+        self.get_numeric_property("capacity").map(|p| p.value.min(100) as u8)
+    }
+
+    /// Check whether the pool is more than `threshold` percent full.
+    pub fn is_capacity_over(&self, threshold: u8) -> Result<bool> {
+        self.capacity_percent().map(|capacity| capacity > threshold)
+    }
+}
+
+fn prop_from_name(name: &str) -> Result<sys::zfs_prop_t::Type> {
+    let cname = SafeString::from(name);
+    let prop = unsafe { sys::zfs_name_to_prop(cname.as_ptr()) };
+    if prop == sys::zfs_prop_t::ZPROP_INVAL {
+        Err(Error::Zfs(ZfsError::new(
+            sys::zfs_error::EZFS_BADPROP,
+            format!("invalid property '{}'", name),
+        )))
+    } else {
+        Ok(prop)
+    }
+}
+
+fn pool_prop_from_name(name: &str) -> Result<sys::zpool_prop_t::Type> {
+    let cname = SafeString::from(name);
+    let prop = unsafe { sys::zpool_name_to_prop(cname.as_ptr()) };
+    if prop == sys::zpool_prop_t::ZPOOL_PROP_INVAL {
+        Err(Error::Zfs(ZfsError::new(
+            sys::zfs_error::EZFS_BADPROP,
+            format!("invalid pool property '{}'", name),
+        )))
+    } else {
+        Ok(prop)
+    }
+}
+
+// zfs_prop_get and friends fail without setting a libzfs error when the property doesn't apply to
+// the type of dataset.
+fn prop_unavailable(name: &str) -> Error {
+    Error::Zfs(ZfsError::new(
+        sys::zfs_error::EZFS_BADPROP,
+        format!("property '{}' is not available for this dataset", name),
+    ))
+}
+
+// Room for the errors ZFS_IOC_SET_PROP returns, an nvlist of property names and errno values.
+const ERRORS_BUF_SIZE: usize = 16 << 10;
+
+// The OpenZFS releases whose zfs_cmd_t has the layout in libzfs-sys's bindings.
+const SET_PROP_VERSIONS: RangeInclusive<(u32, u32)> = (2, 0)..=(2, 2);
+
+// Check that the loaded kernel module takes the zfs_cmd_t in the bindings, before issuing an
+// ioctl with one directly.
+fn check_set_prop_version() -> Result<()> {
+    let version = fs::read_to_string("/sys/module/zfs/version").unwrap_or_default();
+    let version = version.trim();
+    match parse_module_version(version) {
+        Some(parsed) if SET_PROP_VERSIONS.contains(&parsed) => Ok(()),
+        Some(_) => Err(Error::Zfs(ZfsError::new(
+            sys::zfs_error::EZFS_BADVERSION,
+            format!("setting received properties isn't supported with ZFS kernel module {}",
+                version),
+        ))),
+        None => Err(Error::Zfs(ZfsError::new(
+            sys::zfs_error::EZFS_BADVERSION,
+            "setting received properties needs a ZFS kernel module of a known version",
+        ))),
+    }
+}
+
+// The major and minor version from a module version like "2.1.5-1ubuntu6".
+fn parse_module_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split(['.', '-']);
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+// Unpack the errors the kernel packed into `buf`, keyed by property name.
+fn unpack_errors(buf: &mut [u8], size: u64) -> Option<BTreeMap<SafeString, io::Error>> {
+    let size = buf.len().min(size as usize);
+    let mut nvl = ptr::null_mut();
+    if 0 != unsafe { sys::nvlist_unpack(buf.as_mut_ptr() as *mut c_char, size, &mut nvl, 0) } {
+        return None;
+    }
+    let nvl = unsafe { OwnedNvList::from_raw(nvl) };
+    let errors = nvl.borrow();
+    Some(errors.names()
+        .filter_map(|name| {
+            let errno = errors.lookup_i32(name.as_ref())?;
+            Some((name, io::Error::from_raw_os_error(errno)))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_versions() {
+        assert_eq!(parse_module_version("2.1.5-1ubuntu6"), Some((2, 1)));
+        assert_eq!(parse_module_version("2.2.0"), Some((2, 2)));
+        assert_eq!(parse_module_version("0.8.6-1"), Some((0, 8)));
+        assert_eq!(parse_module_version("2"), None);
+        assert_eq!(parse_module_version(""), None);
+        assert!(SET_PROP_VERSIONS.contains(&(2, 1)));
+        assert!(!SET_PROP_VERSIONS.contains(&(2, 3)));
+        assert!(!SET_PROP_VERSIONS.contains(&(0, 8)));
+    }
+
+    #[test]
+    fn compression_round_trip() {
+        let mut values = vec![
+            Compression::Off,
+            Compression::On,
+            Compression::Lzjb,
+            Compression::Zle,
+            Compression::Lz4,
+        ];
+        values.extend((1..=9).map(Compression::Gzip));
+        values.extend((1..=19).map(Compression::Zstd));
+        values.extend((1..=10).chain((20..=100).step_by(10)).chain([500, 1000])
+            .map(Compression::ZstdFast));
+        for value in values {
+            assert!(value.has_valid_level(), "{:?}", value);
+            assert_eq!(Compression::from_str(&value.to_string()), value);
+        }
+    }
+
+    #[test]
+    fn compression_default_levels() {
+        assert_eq!(Compression::from_str("gzip"), Compression::Gzip(6));
+        assert_eq!(Compression::from_str("zstd"), Compression::Zstd(3));
+        assert_eq!(Compression::from_str("zstd-fast"), Compression::ZstdFast(1));
+        assert_eq!(Compression::from_str("gzip-9").to_string(), "gzip-9");
+        assert_eq!(Compression::from_str("zstd-fast-50").to_string(), "zstd-fast-50");
+    }
+
+    #[test]
+    fn compression_bad_levels() {
+        let bad = [
+            "gzip-0", "gzip-10", "gzip-", "gzip-x", "zstd-0", "zstd-20", "zstd-300",
+            "zstd-fast-0", "zstd-fast-11", "zstd-fast-110", "zstd-fast-2000", "zstd-fast-x",
+        ];
+        for s in bad {
+            assert_eq!(Compression::from_str(s), Compression::Other(SafeString::from(s)));
+        }
+        assert_eq!(Compression::from_str("lz5").to_string(), "lz5");
+
+        for value in [Compression::Gzip(0), Compression::Zstd(20), Compression::ZstdFast(15)] {
+            assert!(!value.has_valid_level(), "{:?}", value);
+        }
+    }
+}
@@ -0,0 +1,442 @@
+use libzfs_sys as sys;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::CStr;
+use std::io::{self, Read};
+use std::mem;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::raw::c_int;
+use std::ptr;
+
+use crate::nvlist::{NvList, OwnedNvList};
+use crate::{Dataset, DatasetType, Error, ErrorMark, LibZfs, Result, SafeString, ZfsError};
+
+// sizeof(dmu_replay_record_t)
+const RECORD_SIZE: usize = 312;
+// DRR_BEGIN
+const RECORD_BEGIN: u32 = 0;
+// DMU_COMPOUNDSTREAM
+const COMPOUND_STREAM: u64 = 2;
+// SPA_MAXBLOCKSIZE, the largest record the kernel receives. A begin record's payload (the
+// properties and snapshots of a compound stream) is far smaller in practice.
+const MAX_PAYLOAD_SIZE: usize = 16 << 20;
+
+/// The header of a send stream: its first record, which describes what was sent.
+///
+/// Streams sent with properties, holds, or intermediate snapshots (like `zfs send -p`, `-h`,
+/// `-I` or `-R`) are compound streams, whose header only says which snapshot was sent. The rest
+/// is in its [`payload`](SendHeader::payload), including the properties.
+#[derive(Debug)]
+pub struct SendHeader {
+    /// Full name of the snapshot sent.
+    pub toname: SafeString,
+    /// GUID of the snapshot sent, or 0 for a compound stream.
+    pub toguid: u64,
+    /// GUID of the incremental source, or `None` for a full stream or a compound stream.
+    pub fromguid: Option<u64>,
+    /// When the snapshot was created, in seconds since the Unix epoch.
+    pub creation_time: u64,
+    /// Whether a filesystem or a volume was sent, or `None` for a compound stream.
+    pub dataset_type: Option<DatasetType>,
+    /// The stream's `DMU_BACKUP_FEATURE_*` flags, which say what the receiver needs to support,
+    /// like large blocks or raw encrypted data.
+    pub feature_flags: u64,
+    compound: bool,
+    payload: Option<OwnedNvList>,
+    bytes: Vec<u8>,
+}
+
+impl SendHeader {
+    /// Read the header from the start of a stream, leaving the reader positioned after it.
+    ///
+    /// The header is consumed from the reader, so to receive the stream afterwards, put the
+    /// header's [`bytes`](SendHeader::bytes) back in front of the rest of it:
+    ///
+    /// ```no_run
+    /// # use libzfs::*;
+    /// # use std::io::Read;
+    /// # fn main() -> Result<()> {
+    /// let libzfs = LibZfs::new()?;
+    /// let mut stream = std::io::stdin().lock();
+    /// let header = SendHeader::read(&mut stream)?;
+    /// println!("receiving {}", header.toname);
+    /// let mut whole = header.bytes().chain(stream);
+    /// libzfs.receive_from_reader(&SafeString::from("tank/backup"), &mut whole,
+    ///     ReceiveOpts::default())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Fails with `EZFS_BADSTREAM` if the stream doesn't start with a valid header. Streams
+    /// sent from systems of the other byte order are understood.
+    pub fn read(stream: &mut impl Read) -> Result<SendHeader> {
+        let bad_stream = |msg: &str| Error::Zfs(ZfsError::new(sys::zfs_error::EZFS_BADSTREAM, msg));
+        let mut record = [0u8; RECORD_SIZE];
+        stream.read_exact(&mut record).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => bad_stream("invalid stream (too short)"),
+            _ => Error::Sys(e),
+        })?;
+
+        // Records are in the sender's byte order, which the magic number tells.
+        let magic = u64::from_ne_bytes(record[8..16].try_into().unwrap());
+        let swap = if magic == sys::DMU_BACKUP_MAGIC {
+            false
+        } else if magic.swap_bytes() == sys::DMU_BACKUP_MAGIC {
+            true
+        } else {
+            return Err(bad_stream("invalid stream (bad magic number)"));
+        };
+        let u32_at = |offset: usize| {
+            let value = u32::from_ne_bytes(record[offset..offset + 4].try_into().unwrap());
+            if swap { value.swap_bytes() } else { value }
+        };
+        let u64_at = |offset: usize| {
+            let value = u64::from_ne_bytes(record[offset..offset + 8].try_into().unwrap());
+            if swap { value.swap_bytes() } else { value }
+        };
+
+        if u32_at(0) != RECORD_BEGIN {
+            return Err(bad_stream("invalid stream (doesn't start with a begin record)"));
+        }
+        let payload_len = u32_at(4) as usize;
+        if payload_len > MAX_PAYLOAD_SIZE {
+            return Err(bad_stream("invalid stream (payload too large)"));
+        }
+        let versioninfo = u64_at(16);
+        let toname = CStr::from_bytes_until_nul(&record[56..])
+            .map_err(|_| bad_stream("invalid stream (bad snapshot name)"))?;
+
+        let mut bytes = record.to_vec();
+        let payload = if payload_len == 0 {
+            None
+        } else {
+            let mut buf = vec![0u8; payload_len];
+            stream.read_exact(&mut buf).map_err(|e| match e.kind() {
+                io::ErrorKind::UnexpectedEof => bad_stream("invalid stream (too short)"),
+                _ => Error::Sys(e),
+            })?;
+            bytes.extend_from_slice(&buf);
+            let mut nvl: *mut sys::nvlist_t = ptr::null_mut();
+            if 0 != unsafe {
+                sys::nvlist_unpack(buf.as_mut_ptr() as *mut _, buf.len(), &mut nvl, 0)
+            } {
+                return Err(bad_stream("invalid stream (malformed nvlist)"));
+            }
+            Some(unsafe { OwnedNvList::from_raw(nvl) })
+        };
+
+        Ok(SendHeader {
+            toname: SafeString::from(toname.to_string_lossy().into_owned()),
+            toguid: u64_at(40),
+            fromguid: Some(u64_at(48)).filter(|&guid| guid != 0),
+            creation_time: u64_at(24),
+            dataset_type: match u32_at(32) {
+                sys::dmu_objset_type::DMU_OST_ZFS => Some(DatasetType::Filesystem),
+                sys::dmu_objset_type::DMU_OST_ZVOL => Some(DatasetType::Volume),
+                _ => None,
+            },
+            feature_flags: (versioninfo >> 2) & 0x3fff_ffff,
+            compound: versioninfo & 0x3 == COMPOUND_STREAM,
+            payload,
+            bytes,
+        })
+    }
+
+    /// The bytes [`SendHeader::read`] consumed from the stream: the begin record, and the
+    /// payload if there is one.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Whether this is a compound stream, with more than a single bare snapshot stream.
+    pub fn is_compound(&self) -> bool {
+        self.compound
+    }
+
+    /// Whether the stream is incremental, so that it can only be received into a dataset which
+    /// already has the source snapshot.
+    pub fn is_incremental(&self) -> bool {
+        self.fromguid.is_some() || self.payload().is_some_and(|p| p.contains("fromsnap"))
+    }
+
+    /// The nvlist which follows the header. For a compound stream, this describes everything
+    /// which was sent; for a resumed stream, it says where it resumes from.
+    pub fn payload(&self) -> Option<NvList<'_>> {
+        self.payload.as_ref().map(OwnedNvList::borrow)
+    }
+
+    /// The properties of the sent dataset, if they were sent, with numeric and index properties
+    /// as numbers and others as strings.
+    pub fn props(&self) -> Option<NvList<'_>> {
+        let fs = AsRef::<str>::as_ref(&self.toname).split('@').next()?;
+        // Each sent dataset is listed under its GUID.
+        self.payload()?
+            .lookup_nvlist("fss")?
+            .nvlists()
+            .map(|(_, info)| info)
+            .find(|info| info.lookup_string("name").is_some_and(|name| {
+                AsRef::<str>::as_ref(&name) == fs
+            }))?
+            .lookup_nvlist("props")
+    }
+}
+
+/// How the name of the dataset to receive into is worked out.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum ReceiveNaming {
+    /// The target is the name of the snapshot to create, or of the dataset to receive into, in
+    /// which case the snapshot gets the same short name as the one sent.
+    #[default]
+    Exact,
+    /// The target is a filesystem under which the sent dataset's name, minus its pool name, is
+    /// created (`zfs receive -d`). Sending `pool/a/b@snap` to `backup` creates `backup/a/b@snap`.
+    FullPath,
+    /// The target is a filesystem under which the last component of the sent dataset's name is
+    /// created (`zfs receive -e`). Sending `pool/a/b@snap` to `backup` creates `backup/b@snap`.
+    LastComponent,
+}
+
+/// Options for receiving a stream.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReceiveOpts {
+    pub naming: ReceiveNaming,
+    /// Roll the destination back to its most recent snapshot first if it has been modified, and
+    /// for a full stream, replace it if it exists (`zfs receive -F`).
+    pub force: bool,
+    /// Keep the partially received state if the receive is interrupted, so that it can be
+    /// resumed with the dataset's [`receive_resume_token`](crate::Dataset::receive_resume_token)
+    /// (`zfs receive -s`).
+    pub resumable: bool,
+    /// Don't mount the received filesystems (`zfs receive -u`).
+    pub no_mount: bool,
+    /// Check the stream and the destination without receiving anything (`zfs receive -n`). The
+    /// stream is still read to the end.
+    pub dry_run: bool,
+    /// Print what is received, and how long it took, to standard output like the CLI does
+    /// (`zfs receive -v`).
+    pub verbose: bool,
+    /// Properties to set on the received datasets, overriding any in the stream
+    /// (`zfs receive -o`).
+    pub props: BTreeMap<String, String>,
+    /// Properties in the stream not to set on the received datasets, so that they inherit them
+    /// instead (`zfs receive -x`). A property can't be both excluded and set with
+    /// [`props`](ReceiveOpts::props).
+    pub exclude_props: BTreeSet<String>,
+    /// Full name of an existing local snapshot to receive an incremental stream on top of,
+    /// creating a clone of it, instead of the destination's most recent snapshot
+    /// (`zfs receive -o origin=`). This is how clones are replicated without sending the data
+    /// they share with their origin again. It overrides an `origin` in
+    /// [`props`](ReceiveOpts::props).
+    pub origin: Option<SafeString>,
+}
+
+impl LibZfs {
+    /// Receive a send stream from the given file descriptor into `target`, as described by
+    /// [`ReceiveOpts::naming`], and wait for it to finish (like `zfs receive`). The descriptor can
+    /// be any kind, like a pipe, a regular file, or a socket, and is closed afterwards.
+    ///
+    /// Stream and destination problems are reported with these codes:
+    /// * `EZFS_BADSTREAM`: the stream is corrupt, such as having a bad magic number or checksum,
+    ///   or is cut short.
+    /// * `EZFS_BADVERSION`: the destination pool doesn't support features the stream uses; it
+    ///   may need to be upgraded.
+    /// * `EZFS_EXISTS`: for a full stream, the destination exists and [`ReceiveOpts::force`]
+    ///   isn't set.
+    /// * `EZFS_NOENT`: for an incremental stream, the destination doesn't exist.
+    /// * `EZFS_BADRESTORE`: the destination has been modified since its most recent snapshot
+    ///   and [`ReceiveOpts::force`] isn't set, or that snapshot isn't the incremental source.
+    /// * [`Error::PartialReceiveExists`]: the destination has partially received state from an
+    ///   interrupted resumable receive, which has to be resumed, or aborted with
+    ///   [`Dataset::abort_partial_receive`], first. With [`ReceiveOpts::naming`] other than
+    ///   [`ReceiveNaming::Exact`], the destination isn't known until the stream is read, so this
+    ///   is reported as `EZFS_BUSY` instead.
+    ///
+    /// The options are checked before anything is read from the stream: this fails with
+    /// `EZFS_BADPROP` if a property is both set and excluded, and with `EZFS_NOENT` if
+    /// [`ReceiveOpts::origin`] isn't an existing snapshot.
+    pub fn receive(&self, target: &SafeString, fd: OwnedFd, opts: ReceiveOpts) -> Result<()> {
+        receive_with_handle(self.handle, target, fd, &opts)
+    }
+}
+
+pub(crate) fn receive_with_handle(
+    libzfs: *mut sys::libzfs_handle_t,
+    target: &SafeString,
+    fd: OwnedFd,
+    opts: &ReceiveOpts,
+) -> Result<()> {
+    let mut flags: sys::recvflags_t = unsafe { mem::zeroed() };
+    flags.isprefix = (opts.naming != ReceiveNaming::Exact) as sys::boolean_t;
+    flags.istail = (opts.naming == ReceiveNaming::LastComponent) as sys::boolean_t;
+    flags.force = opts.force as sys::boolean_t;
+    flags.resumable = opts.resumable as sys::boolean_t;
+    flags.nomount = opts.no_mount as sys::boolean_t;
+    flags.dryrun = opts.dry_run as sys::boolean_t;
+    flags.verbose = opts.verbose as sys::boolean_t;
+
+    if opts.naming != ReceiveNaming::Exact && AsRef::<str>::as_ref(target).contains('@') {
+        return Err(Error::Zfs(ZfsError::new(
+            sys::zfs_error::EZFS_INVALIDNAME,
+            "the target must be a filesystem when the received name is appended to it",
+        )));
+    }
+
+    let overridden = opts.props.keys()
+        .map(String::as_str)
+        .chain(opts.origin.as_ref().map(|_| "origin"));
+    for name in overridden {
+        if opts.exclude_props.contains(name) {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADPROP,
+                format!("property '{}' can't be both set and excluded", name),
+            )));
+        }
+    }
+
+    // Properties to set are strings; properties to exclude are booleans.
+    let mut props = OwnedNvList::new();
+    for name in &opts.exclude_props {
+        props.add_boolean(name);
+    }
+    for (k, v) in &opts.props {
+        if k != "origin" || opts.origin.is_none() {
+            props.add_string(k, v);
+        }
+    }
+    if let Some(origin) = &opts.origin {
+        let types = sys::zfs_type_t::ZFS_TYPE_SNAPSHOT;
+        let zhp = unsafe { sys::zfs_open(libzfs, origin.as_ptr(), types as c_int) };
+        if zhp.is_null() {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_NOENT,
+                format!("origin snapshot '{}' does not exist", origin),
+            )));
+        }
+        unsafe { sys::zfs_close(zhp) };
+        props.add_string("origin", origin.as_ref());
+    }
+
+    let mark = ErrorMark::new(libzfs);
+    let result = unsafe {
+        sys::zfs_receive(
+            libzfs,
+            target.as_ptr(),
+            if props.is_empty() { ptr::null_mut() } else { props.as_ptr() },
+            &mut flags,
+            fd.as_raw_fd(),
+            ptr::null_mut(),
+        )
+    };
+    if result != 0 {
+        let error = mark.error(libzfs);
+        let busy = matches!(error, Error::Zfs(ref e) if e.code == sys::zfs_error::EZFS_BUSY);
+        if busy && opts.naming == ReceiveNaming::Exact {
+            if let Some(partial) = partial_receive_error(libzfs, target) {
+                return Err(partial);
+            }
+        }
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// libzfs reports a destination with partially received state as just busy, like a destination
+/// which is in use. Tell them apart by checking the destination for the state.
+fn partial_receive_error(libzfs: *mut sys::libzfs_handle_t, target: &SafeString) -> Option<Error> {
+    let name = SafeString::from(AsRef::<str>::as_ref(target).split('@').next().unwrap_or(""));
+    let types = sys::zfs_type_t::ZFS_TYPE_FILESYSTEM | sys::zfs_type_t::ZFS_TYPE_VOLUME;
+    let handle = unsafe { sys::zfs_open(libzfs, name.as_ptr(), types as c_int) };
+    if handle.is_null() {
+        return None;
+    }
+    let dataset = Dataset { libzfs, handle };
+    match dataset.has_partial_receive() {
+        Ok(true) => Some(Error::PartialReceiveExists(name)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A begin record with no payload, in native or swapped byte order.
+    fn begin_record(swap: bool, objset_type: u32, toname: &str) -> Vec<u8> {
+        let u32_bytes = |v: u32| if swap { v.swap_bytes() } else { v }.to_ne_bytes();
+        let u64_bytes = |v: u64| if swap { v.swap_bytes() } else { v }.to_ne_bytes();
+        let mut record = vec![0u8; RECORD_SIZE];
+        record[0..4].copy_from_slice(&u32_bytes(RECORD_BEGIN));
+        record[8..16].copy_from_slice(&u64_bytes(sys::DMU_BACKUP_MAGIC));
+        // Feature flags 0x4 (large blocks), a substream.
+        record[16..24].copy_from_slice(&u64_bytes((0x4 << 2) | 1));
+        record[24..32].copy_from_slice(&u64_bytes(1_600_000_000));
+        record[32..36].copy_from_slice(&u32_bytes(objset_type));
+        record[40..48].copy_from_slice(&u64_bytes(0x1234));
+        record[48..56].copy_from_slice(&u64_bytes(0x5678));
+        record[56..56 + toname.len()].copy_from_slice(toname.as_bytes());
+        record
+    }
+
+    #[test]
+    fn read_native_filesystem() {
+        let record = begin_record(false, sys::dmu_objset_type::DMU_OST_ZFS, "tank/fs@snap");
+        let mut stream = &record[..];
+        let header = SendHeader::read(&mut stream).unwrap();
+        assert_eq!(AsRef::<str>::as_ref(&header.toname), "tank/fs@snap");
+        assert_eq!(header.toguid, 0x1234);
+        assert_eq!(header.fromguid, Some(0x5678));
+        assert_eq!(header.creation_time, 1_600_000_000);
+        assert_eq!(header.dataset_type, Some(DatasetType::Filesystem));
+        assert_eq!(header.feature_flags, 0x4);
+        assert!(!header.is_compound());
+        assert_eq!(header.bytes(), &record[..]);
+        assert!(stream.is_empty());
+    }
+
+    #[test]
+    fn read_swapped_zvol() {
+        let record = begin_record(true, sys::dmu_objset_type::DMU_OST_ZVOL, "tank/vol@snap");
+        let header = SendHeader::read(&mut &record[..]).unwrap();
+        assert_eq!(AsRef::<str>::as_ref(&header.toname), "tank/vol@snap");
+        assert_eq!(header.toguid, 0x1234);
+        assert_eq!(header.creation_time, 1_600_000_000);
+        assert_eq!(header.dataset_type, Some(DatasetType::Volume));
+        assert_eq!(header.feature_flags, 0x4);
+    }
+
+    #[test]
+    fn read_meta_objset_is_not_a_volume() {
+        let record = begin_record(false, sys::dmu_objset_type::DMU_OST_META, "tank@snap");
+        let header = SendHeader::read(&mut &record[..]).unwrap();
+        assert_eq!(header.dataset_type, None);
+    }
+
+    #[test]
+    fn read_bad_streams() {
+        let bad_stream = |result: Result<SendHeader>| match result {
+            Err(Error::Zfs(e)) => e.code == sys::zfs_error::EZFS_BADSTREAM,
+            _ => false,
+        };
+        let record = begin_record(false, sys::dmu_objset_type::DMU_OST_ZFS, "tank/fs@snap");
+        assert!(bad_stream(SendHeader::read(&mut &record[..100])));
+
+        let mut bad_magic = record.clone();
+        bad_magic[8] ^= 0xff;
+        assert!(bad_stream(SendHeader::read(&mut &bad_magic[..])));
+
+        let mut not_begin = record.clone();
+        not_begin[0] = 1;
+        assert!(bad_stream(SendHeader::read(&mut &not_begin[..])));
+
+        // A corrupt payload length is rejected before anything is allocated for it.
+        let mut huge_payload = record;
+        huge_payload[4..8].copy_from_slice(&u32::MAX.to_ne_bytes());
+        match SendHeader::read(&mut &huge_payload[..]) {
+            Err(Error::Zfs(e)) => {
+                assert_eq!(e.code, sys::zfs_error::EZFS_BADSTREAM);
+                assert!(e.msg.contains("payload too large"), "{}", e.msg);
+            }
+            other => panic!("expected EZFS_BADSTREAM, got {:?}", other),
+        }
+    }
+}
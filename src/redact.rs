@@ -0,0 +1,103 @@
+use libzfs_sys as sys;
+
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::raw::c_int;
+use std::ptr;
+
+use crate::nvlist::OwnedNvList;
+use crate::{
+    ztry, Dataset, DatasetType, DatasetTypeMask, Error, ErrorMark, Result, SafeString, ZfsError,
+    ZfsSendFlags,
+};
+
+impl Dataset {
+    /// Create a redaction bookmark of this snapshot (`zfs redact`), for sending it with
+    /// [`Dataset::send_redacted`]. `bookmark` is the bookmark's short name, without the dataset
+    /// and `#`.
+    ///
+    /// `redaction_snaps` are the full names of snapshots of clones of this snapshot, in which
+    /// the data to leave out has been removed or overwritten. Blocks which were changed in all
+    /// of them are left out of redacted sends.
+    pub fn redact(&self, bookmark: &str, redaction_snaps: &[SafeString]) -> Result<()> {
+        let mut snaps = OwnedNvList::new();
+        for snap in redaction_snaps {
+            snaps.add_boolean(snap.as_ref());
+        }
+        let bookmark = SafeString::from(bookmark);
+        ztry!(unsafe {
+            sys::lzc_redact(sys::zfs_get_name(self.handle), bookmark.as_ptr(), snaps.as_ptr())
+        }, self.libzfs);
+        Ok(())
+    }
+
+    /// Send a full stream of this snapshot, leaving out the blocks which the given redaction
+    /// bookmark redacts (`zfs send --redact`), and wait for it to finish. `redaction_bookmark`
+    /// is the bookmark's full name, and it must have been created from this snapshot, with
+    /// [`Dataset::redact`].
+    ///
+    /// This fails with `EZFS_BADTYPE` if the bookmark isn't a redaction bookmark, and with
+    /// [`Error::Sys`] with `EPIPE` if the reader of the stream goes away before the end.
+    ///
+    /// The dataset received from the stream is marked as [redacted](Dataset::is_redacted),
+    /// and can't be mounted. Incremental streams can only be received on top of it if they
+    /// were redacted with the same snapshots, or a subset of them.
+    pub fn send_redacted(
+        &self,
+        redaction_bookmark: &SafeString,
+        fd: OwnedFd,
+        flags: ZfsSendFlags,
+    ) -> Result<()> {
+        let types: DatasetTypeMask = DatasetType::Bookmark.into();
+        let mark = ErrorMark::new(self.libzfs);
+        let handle = unsafe {
+            sys::zfs_open(self.libzfs, redaction_bookmark.as_ptr(), types.0 as c_int)
+        };
+        if handle.is_null() {
+            return Err(mark.error(self.libzfs));
+        }
+        let bookmark = Dataset { libzfs: self.libzfs, handle };
+        if bookmark.get_redact_snaps()?.is_empty() {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADTYPE,
+                format!("'{}' is not a redaction bookmark", redaction_bookmark),
+            )));
+        }
+
+        ztry!(unsafe {
+            sys::lzc_send_redacted(
+                sys::zfs_get_name(self.handle),
+                ptr::null(),
+                fd.as_raw_fd(),
+                flags,
+                redaction_bookmark.as_ptr(),
+            )
+        }, self.libzfs);
+        Ok(())
+    }
+
+    /// Whether this dataset was received from a redacted send stream (the `redacted`
+    /// property).
+    pub fn is_redacted(&self) -> Result<bool> {
+        self.get_bool_property("redacted").map(|p| p.value)
+    }
+
+    /// Get the GUIDs of the redaction snapshots, for a redaction bookmark or a dataset received
+    /// from a redacted send stream (the `redact_snaps` property). This is empty for anything
+    /// else.
+    pub fn get_redact_snaps(&self) -> Result<Vec<u64>> {
+        // libzfs fails to get the property if there are none, as well as on actual errors, but
+        // lists the GUIDs separated by commas if there are some.
+        let value = match self.get_property("redact_snaps") {
+            Ok(prop) => prop.value,
+            Err(_) => return Ok(vec![]),
+        };
+        AsRef::<str>::as_ref(&value)
+            .split(',')
+            .filter(|guid| !guid.is_empty())
+            .map(|guid| guid.parse().map_err(|_| Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADPROP,
+                format!("bad redaction snapshot GUID {:?}", guid),
+            ))))
+            .collect()
+    }
+}
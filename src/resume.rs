@@ -0,0 +1,173 @@
+use libzfs_sys as sys;
+
+use std::io;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::raw::c_int;
+
+use crate::nvlist::OwnedNvList;
+use crate::send::libzfs_send_flags;
+use crate::{
+    ztry, Dataset, Error, ErrorMark, LibZfs, Result, SafeString, ZfsError, ZfsSendFlags,
+};
+
+/// The contents of a receive resume token, which says where an interrupted receive left off, so
+/// that the sender can continue from there. Get one with [`LibZfs::parse_resume_token`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumeToken {
+    token: SafeString,
+    /// Full name of the snapshot being sent.
+    pub toname: SafeString,
+    /// GUID of the snapshot being sent.
+    pub toguid: u64,
+    /// GUID of the incremental source, or `None` for a full stream.
+    pub fromguid: Option<u64>,
+    /// The object the receive had got to.
+    pub object: u64,
+    /// The offset within that object.
+    pub offset: u64,
+    /// Bytes received so far.
+    pub bytes: u64,
+    /// Flags of the original stream, which the resumed stream must have too.
+    pub flags: ZfsSendFlags,
+}
+
+impl ResumeToken {
+    /// The token itself, as read from the `receive_resume_token` property.
+    pub fn token(&self) -> &SafeString {
+        &self.token
+    }
+}
+
+impl Dataset {
+    /// Get the token for resuming an interrupted receive into this dataset (its
+    /// `receive_resume_token` property), or `None` if there's no partially received state. There
+    /// is only partially received state if the receive was started with resuming enabled
+    /// (`zfs receive -s`).
+    pub fn receive_resume_token(&self) -> Result<Option<SafeString>> {
+        match self.get_property("receive_resume_token") {
+            Ok(p) if !matches!(AsRef::<str>::as_ref(&p.value), "" | "-") => Ok(Some(p.value)),
+            Ok(_) => Ok(None),
+            // libzfs fails to get the property when it has no value.
+            Err(Error::Zfs(e)) if e.code == sys::zfs_error::EZFS_BADPROP => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Check whether an interrupted receive left partially received state in this dataset,
+    /// which has to be resumed or [aborted](Dataset::abort_partial_receive) before anything
+    /// else can be received into it.
+    pub fn has_partial_receive(&self) -> Result<bool> {
+        Ok(self.receive_resume_token()?.is_some())
+    }
+
+    /// Throw away the partially received state left by an interrupted receive into this dataset
+    /// (like `zfs receive -A`), so that other streams can be received into it.
+    ///
+    /// For an interrupted incremental receive, the state is a hidden child clone, which is
+    /// destroyed. For an interrupted full receive, the state is this dataset itself, which is
+    /// destroyed, leaving this handle referring to nothing.
+    ///
+    /// Fails with these codes, among others:
+    ///
+    /// * [`Error::NoPartialReceive`]: there is no partially received state, so there's nothing
+    ///   to abort. When aborting just to make sure a receive can start, this can be treated as
+    ///   success.
+    /// * `EZFS_BUSY`: the partially received state can't be destroyed because it's in use, like
+    ///   a mounted filesystem with open files.
+    pub fn abort_partial_receive(&self) -> Result<()> {
+        let types = sys::zfs_type_t::ZFS_TYPE_FILESYSTEM | sys::zfs_type_t::ZFS_TYPE_VOLUME;
+        // recv_clone_name
+        let clone_name = SafeString::from(format!("{}/%recv", self.get_name()));
+        let clone = unsafe { sys::zfs_open(self.libzfs, clone_name.as_ptr(), types as c_int) };
+        if !clone.is_null() {
+            let clone = Dataset { libzfs: self.libzfs, handle: clone };
+            ztry!(unsafe { sys::zfs_destroy(clone.handle, 0) }, self.libzfs);
+            return Ok(());
+        }
+
+        let inconsistent = self.get_numeric_property("inconsistent").map(|p| p.value != 0);
+        if !inconsistent.unwrap_or(false) || !self.has_partial_receive()? {
+            return Err(Error::NoPartialReceive(self.get_name()));
+        }
+        ztry!(unsafe { sys::zfs_destroy(self.handle, 0) }, self.libzfs);
+        Ok(())
+    }
+}
+
+impl LibZfs {
+    /// Decode a receive resume token, such as one from [`Dataset::receive_resume_token`].
+    ///
+    /// Fails with `EZFS_FAULT`, and libzfs's description of the problem, if the token is
+    /// truncated or corrupt, or is from a newer version of ZFS.
+    pub fn parse_resume_token(&self, token: &str) -> Result<ResumeToken> {
+        let token = SafeString::from(token);
+        let nvl = unsafe { sys::zfs_send_resume_token_to_nvlist(self.handle, token.as_ptr()) };
+        if nvl.is_null() {
+            // libzfs only sets the description, not the error code.
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_FAULT,
+                ZfsError::last_error(self.handle).msg,
+            )));
+        }
+        let nvl = unsafe { OwnedNvList::from_raw(nvl) };
+        let nvl = nvl.borrow();
+
+        let missing = |what: &str| Error::Zfs(ZfsError::new(
+            sys::zfs_error::EZFS_FAULT,
+            format!("resume token is missing '{}'", what),
+        ));
+        let u64_field = |name: &str| nvl.lookup_u64(name).ok_or_else(|| missing(name));
+
+        // The flags are booleans which are present or not.
+        let mut flags = ZfsSendFlags(0);
+        for (name, flag) in [
+            ("embedok", ZfsSendFlags::LZC_SEND_FLAG_EMBED_DATA),
+            ("largeblockok", ZfsSendFlags::LZC_SEND_FLAG_LARGE_BLOCK),
+            ("compressok", ZfsSendFlags::LZC_SEND_FLAG_COMPRESS),
+            ("rawok", ZfsSendFlags::LZC_SEND_FLAG_RAW),
+            ("savedok", ZfsSendFlags::LZC_SEND_FLAG_SAVED),
+        ] {
+            if nvl.contains(name) {
+                flags |= flag;
+            }
+        }
+
+        Ok(ResumeToken {
+            toname: nvl.lookup_string("toname").ok_or_else(|| missing("toname"))?,
+            toguid: u64_field("toguid")?,
+            fromguid: nvl.lookup_u64("fromguid"),
+            object: u64_field("object")?,
+            offset: u64_field("offset")?,
+            bytes: u64_field("bytes")?,
+            flags,
+            token,
+        })
+    }
+
+    /// Send the rest of an interrupted stream to the given file descriptor (like
+    /// `zfs send -t`), and wait for it to finish. The receiver must resume the receive into the
+    /// same dataset it was interrupted in, with resuming enabled again.
+    ///
+    /// The token's own flags are always used; `flags` can only add to them. If the snapshot
+    /// being sent has since been destroyed, or replaced by another one with the same name, this
+    /// fails with `EZFS_BADPATH`. If the reader of the stream goes away before the end, this
+    /// fails with [`Error::Sys`] with `EPIPE`.
+    pub fn send_resume(&self, token: &ResumeToken, fd: OwnedFd, flags: ZfsSendFlags)
+        -> Result<()>
+    {
+        let mut sendflags = libzfs_send_flags(flags);
+        let mark = ErrorMark::new(self.handle);
+        let result = unsafe {
+            sys::zfs_send_resume(self.handle, &mut sendflags, fd.as_raw_fd(), token.token.as_ptr())
+        };
+        // libzfs reports write errors as a bad backup stream; report the receiver going away the
+        // same way lzc_send does.
+        let os_error = io::Error::last_os_error();
+        let error = (result != 0).then(|| mark.error(self.handle));
+        drop(fd);
+        if result != 0 && os_error.raw_os_error() == Some(libc::EPIPE) {
+            return Err(Error::Sys(os_error));
+        }
+        error.map_or(Ok(()), Err)
+    }
+}
@@ -0,0 +1,189 @@
+use libzfs_sys as sys;
+
+use std::mem;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{ztry, Result, ZPool};
+
+/// An action to take on a pool's scrub.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScrubCmd {
+    /// Start a new scrub (`zpool scrub`).
+    Start,
+    /// Pause the running scrub, remembering its progress (`zpool scrub -p`).
+    Pause,
+    /// Continue a paused scrub from where it left off (`zpool scrub`). If no scrub is paused,
+    /// this starts a new one.
+    Resume,
+    /// Cancel the running or paused scrub (`zpool scrub -s`).
+    Stop,
+}
+
+impl ZPool {
+    /// Start, pause, resume or stop a scrub of the pool.
+    ///
+    /// Conflicts with the pool's current state are reported with these codes:
+    /// * `EZFS_SCRUBBING`: starting a scrub while one is already running.
+    /// * `EZFS_RESILVERING`: starting or stopping a scrub while a resilver is running.
+    /// * `EZFS_NO_SCRUB`: pausing or stopping when no scrub is running.
+    /// * `EZFS_SCRUB_PAUSED`: pausing a scrub which is already paused.
+    pub fn scrub(&self, cmd: ScrubCmd) -> Result<()> {
+        use sys::pool_scan_func::*;
+        use sys::pool_scrub_cmd::*;
+        let (func, cmd) = match cmd {
+            ScrubCmd::Start | ScrubCmd::Resume => (POOL_SCAN_SCRUB, POOL_SCRUB_NORMAL),
+            ScrubCmd::Pause => (POOL_SCAN_SCRUB, POOL_SCRUB_PAUSE),
+            ScrubCmd::Stop => (POOL_SCAN_NONE, POOL_SCRUB_NORMAL),
+        };
+        ztry!(unsafe { sys::zpool_scan(self.handle, func, cmd) }, self.libzfs);
+        Ok(())
+    }
+}
+
+/// The kind of scan.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScanFunction {
+    Scrub,
+    Resilver,
+}
+
+/// The state of the most recent scan, or device removal.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScanState {
+    /// In progress, or paused.
+    Scanning,
+    Finished,
+    Canceled,
+}
+
+impl ScanState {
+    /// Translate a `dsl_scan_state_t`, which is `None` if nothing has run.
+    pub(crate) fn from_raw(state: u64) -> Option<Self> {
+        use sys::dsl_scan_state::*;
+        match state as sys::dsl_scan_state_t {
+            DSS_SCANNING => Some(ScanState::Scanning),
+            DSS_FINISHED => Some(ScanState::Finished),
+            DSS_CANCELED => Some(ScanState::Canceled),
+            _ => None,
+        }
+    }
+}
+
+/// Progress of the most recent scrub or resilver, as shown by `zpool status`.
+///
+/// Times are in seconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanStats {
+    pub function: ScanFunction,
+    pub state: ScanState,
+    pub start_time: u64,
+    /// When the scan finished or was canceled; `None` while it's still in progress.
+    pub end_time: Option<u64>,
+    /// Total bytes the scan needs to examine.
+    pub to_examine: u64,
+    /// Bytes the scan has located so far. Scans locate data ahead of verifying it, so this runs
+    /// ahead of `issued`.
+    pub examined: u64,
+    /// Bytes which have been read and verified so far.
+    pub issued: u64,
+    /// Errors found by the scan.
+    pub errors: u64,
+    /// When the scrub was paused; `None` if it isn't paused.
+    pub pause_time: Option<u64>,
+    /// When the current pass of the scan started. A scan is restarted in a new pass when the pool
+    /// is imported, or a paused scrub is resumed.
+    pub pass_start: u64,
+    /// Bytes verified during the current pass.
+    pub pass_issued: u64,
+    /// Seconds the scrub has spent paused during the current pass.
+    pub pass_paused: u64,
+}
+
+impl ScanStats {
+    /// How much of the scan is done, from 0 to 100, based on the bytes verified so far.
+    pub fn percent_complete(&self) -> f64 {
+        if self.state == ScanState::Finished {
+            100.0
+        } else if self.to_examine == 0 {
+            0.0
+        } else {
+            (self.issued as f64 / self.to_examine as f64 * 100.0).min(100.0)
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pause_time.is_some()
+    }
+
+    /// How fast data is being verified, in bytes per second, averaged over the current pass, as
+    /// `zpool status` shows it. `None` if the scan isn't running, or is paused.
+    pub fn issue_rate(&self) -> Option<u64> {
+        if self.state != ScanState::Scanning || self.is_paused() {
+            return None;
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let elapsed = now.saturating_sub(self.pass_start).saturating_sub(self.pass_paused).max(1);
+        Some(self.pass_issued / elapsed)
+    }
+
+    /// Estimate how long the scan will take to finish at its current [rate](Self::issue_rate).
+    /// `None` if the scan isn't running, or is paused, or the rate isn't known yet.
+    pub fn time_remaining(&self) -> Option<Duration> {
+        let rate = self.issue_rate().filter(|&rate| rate != 0)?;
+        Some(Duration::from_secs(self.to_examine.saturating_sub(self.issued) / rate))
+    }
+}
+
+impl ZPool {
+    /// Get the progress of the current scrub or resilver, or the results of the last one, or
+    /// `None` if no scan has ever run on this pool.
+    ///
+    /// This refreshes the pool's statistics first, so the numbers are current.
+    pub fn scan_stats(&self) -> Result<Option<ScanStats>> {
+        let mut missing: sys::boolean_t = 0;
+        ztry!(unsafe { sys::zpool_refresh_stats(self.handle, &mut missing) }, self.libzfs);
+
+        let stats = self.config()
+            .lookup_nvlist("vdev_tree")
+            .and_then(|tree| tree.lookup_u64_array("scan_stats"));
+        let stats = match stats {
+            Some(stats) if mem::size_of_val(stats) >= mem::size_of::<sys::pool_scan_stat_t>() => {
+                // The array is the pool_scan_stat_t struct itself, which is all u64s.
+                unsafe { &*(stats.as_ptr() as *const sys::pool_scan_stat_t) }
+            }
+            _ => return Ok(None),
+        };
+
+        let function = {
+            use sys::pool_scan_func::*;
+            match stats.pss_func as sys::pool_scan_func_t {
+                POOL_SCAN_SCRUB => ScanFunction::Scrub,
+                POOL_SCAN_RESILVER => ScanFunction::Resilver,
+                _ => return Ok(None),
+            }
+        };
+        let state = match ScanState::from_raw(stats.pss_state) {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+
+        Ok(Some(ScanStats {
+            function,
+            state,
+            start_time: stats.pss_start_time,
+            end_time: Some(stats.pss_end_time).filter(|_| state != ScanState::Scanning),
+            to_examine: stats.pss_to_examine,
+            examined: stats.pss_examined,
+            issued: stats.pss_issued,
+            errors: stats.pss_errors,
+            pause_time: Some(stats.pss_pass_scrub_pause)
+                .filter(|&t| t != 0 && state == ScanState::Scanning),
+            pass_start: stats.pss_pass_start,
+            pass_issued: stats.pss_pass_issued,
+            pass_paused: stats.pss_pass_scrub_spent_paused,
+        }))
+    }
+}
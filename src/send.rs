@@ -0,0 +1,684 @@
+use libzfs_sys as sys;
+
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::raw::c_int;
+use std::panic;
+use std::ptr;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::{
+    ztry, Dataset, DatasetType, Error, ErrorMark, Result, SafeString, ZPool, ZfsError, ZfsSend,
+    ZfsSendFlags,
+};
+
+type ProgressCallback = Box<dyn FnMut(u64, Option<u64>) + Send>;
+
+/// Where an incremental send stream starts from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IncrementalSource {
+    /// Send the changes since this snapshot or bookmark, given by its full name
+    /// (`zfs send -i`).
+    Since(SafeString),
+    /// Send every snapshot since this one, given by its full name, so that the receiver ends up
+    /// with all of them (`zfs send -I`). It must be a snapshot of the same dataset, not a
+    /// bookmark.
+    AllIntermediateSince(SafeString),
+}
+
+/// Configures a send stream from a snapshot. Create one with [`Dataset::send_builder`].
+pub struct SendBuilder<'a> {
+    dataset: &'a Dataset,
+    from: Option<SafeString>,
+    flags: ZfsSendFlags,
+    intermediates: bool,
+    props: bool,
+    holds: bool,
+    backup: bool,
+    progress: Option<(Duration, ProgressCallback)>,
+    // Dataset::send predates the check, and leaves a bad source for the kernel to report.
+    pub(crate) check_from: bool,
+}
+
+impl<'a> SendBuilder<'a> {
+    /// Send an incremental stream from the given snapshot or bookmark, which must be given by
+    /// its full name. [`SendBuilder::send`] fails with `EZFS_CROSSTARGET` if it wasn't created
+    /// before this snapshot.
+    pub fn from(mut self, from_fq: SafeString) -> Self {
+        self.from = Some(from_fq);
+        self
+    }
+
+    pub fn flags(mut self, flags: ZfsSendFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Send blocks compressed as they are on disk, instead of decompressing them first
+    /// (`zfs send -c`). This saves bandwidth and CPU time, and the receiver keeps them
+    /// compressed, so it's best when the receiving pool uses the same compression. The
+    /// receiving pool needs the features for the compression algorithms used, like
+    /// `lz4_compress`.
+    pub fn compressed(self, compressed: bool) -> Self {
+        self.set_flag(ZfsSendFlags::LZC_SEND_FLAG_COMPRESS, compressed)
+    }
+
+    /// Send blocks which are small enough to be embedded in their block pointers as they are,
+    /// instead of expanding them to full blocks (`zfs send -e`). The receiving pool needs the
+    /// `embedded_data` feature.
+    pub fn embed_data(self, embed_data: bool) -> Self {
+        self.set_flag(ZfsSendFlags::LZC_SEND_FLAG_EMBED_DATA, embed_data)
+    }
+
+    fn set_flag(mut self, flag: ZfsSendFlags, on: bool) -> Self {
+        if on {
+            self.flags |= flag;
+        } else {
+            self.flags = ZfsSendFlags(self.flags.0 & !flag.0);
+        }
+        self
+    }
+
+    /// Also send all of the snapshots between the incremental source given with
+    /// [`SendBuilder::from`] and this one, so that the receiver ends up with all of them
+    /// (`zfs send -I`). The source must be a snapshot of the same dataset, not a bookmark.
+    ///
+    /// Progress is only reported while the last snapshot is being sent.
+    pub fn intermediates(mut self, intermediates: bool) -> Self {
+        self.intermediates = intermediates;
+        self
+    }
+
+    /// Include the dataset's properties in the stream, so that the receiver sets them as
+    /// received properties (`zfs send -p`). The incremental source, if any, must be a snapshot
+    /// of the same dataset, not a bookmark.
+    pub fn props(mut self, props: bool) -> Self {
+        self.props = props;
+        self
+    }
+
+    /// Include the user holds on the snapshots in the stream, so that the receiver puts the same
+    /// holds on its copies (`zfs send -h`). The incremental source, if any, must be a snapshot
+    /// of the same dataset, not a bookmark.
+    pub fn holds(mut self, holds: bool) -> Self {
+        self.holds = holds;
+        self
+    }
+
+    /// Like [`SendBuilder::props`], but send the dataset's received property values instead of
+    /// its local ones, for backing up a dataset which was itself received (`zfs send -b`).
+    pub fn backup(mut self, backup: bool) -> Self {
+        self.backup = backup;
+        self
+    }
+
+    /// Call `callback` every `interval` while the send is running, with the number of bytes
+    /// written so far, and the estimated total size of the stream if it could be estimated.
+    ///
+    /// The callback runs on a separate thread. If it panics, it isn't called again, but the send
+    /// carries on regardless.
+    pub fn progress<F>(mut self, interval: Duration, callback: F) -> Self
+        where F: FnMut(u64, Option<u64>) + Send + 'static,
+    {
+        self.progress = Some((interval, Box::new(callback)));
+        self
+    }
+
+    /// Start sending the stream to the given file descriptor, on a separate thread. This can be
+    /// any kind of file descriptor, like a pipe, a regular file, or a socket.
+    ///
+    /// Problems with the options are reported here. Errors from the send itself, including a
+    /// missing snapshot, are reported by [`ZfsSend::wait`].
+    pub fn send(self, fd: OwnedFd) -> Result<ZfsSend<'a>> {
+        // Best-effort attempt to set a big buffer size in case fd is a pipe.
+        let _ = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETPIPE_SZ, 1_048_576_i32) };
+
+        let progress = self.progress.map(|(interval, callback)| {
+            let estimate = self.dataset.get_send_space(self.from.as_ref(), self.flags).ok();
+            (interval, callback, estimate)
+        });
+
+        // Safety is ensured by tying the lifetime of the ZfsSend holding the thread to the
+        // lifetime of the dataset, which holds the libzfs handle.
+        let libzfs = SendPtr(self.dataset.libzfs);
+        let zhp = SendPtr(self.dataset.handle);
+
+        let fqname = self.dataset.get_name();
+        if let Some(from) = self.from.as_ref().filter(|_| self.check_from) {
+            check_incremental_source(self.dataset, from)?;
+        }
+        // libzfs_core can only send a bare stream of one snapshot, so anything more goes
+        // through libzfs.
+        let libzfs_opts = if self.intermediates || self.props || self.holds || self.backup {
+            let (fs, from_snap, to_snap) =
+                libzfs_send_names(&fqname, self.from.as_ref(), self.intermediates)?;
+            let mut sendflags = libzfs_send_flags(self.flags);
+            sendflags.doall = self.intermediates as sys::boolean_t;
+            sendflags.props = self.props as sys::boolean_t;
+            sendflags.holds = self.holds as sys::boolean_t;
+            sendflags.backup = self.backup as sys::boolean_t;
+            Some((fs, from_snap, to_snap, sendflags))
+        } else {
+            None
+        };
+        let from_fq = self.from;
+        let flags = self.flags;
+        let thread = thread::spawn(move || {
+            let poller = progress.map(|(interval, mut callback, estimate)| {
+                let (stop_tx, stop_rx) = mpsc::channel::<()>();
+                let raw_fd = fd.as_raw_fd();
+                let poller = thread::spawn(move || {
+                    while let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(interval) {
+                        let mut written = 0u64;
+                        let mut blocks = 0u64;
+                        // This fails if the send hasn't started yet, or has already finished.
+                        if 0 == unsafe {
+                            sys::zfs_send_progress(zhp.ptr(), raw_fd, &mut written, &mut blocks)
+                        } {
+                            // A panicking callback mustn't take the send down with it; it just
+                            // stops being called.
+                            let call = panic::AssertUnwindSafe(|| callback(written, estimate));
+                            if panic::catch_unwind(call).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                });
+                (stop_tx, poller)
+            });
+
+            let result = match libzfs_opts {
+                Some((fs, from_snap, to_snap, mut sendflags)) => send_with_libzfs(
+                    libzfs.ptr(),
+                    &fs,
+                    from_snap.as_ref(),
+                    &to_snap,
+                    &mut sendflags,
+                    fd.as_raw_fd(),
+                ),
+                None => send_with_libzfs_core(
+                    libzfs.ptr(),
+                    &fqname,
+                    from_fq.as_ref(),
+                    flags,
+                    fd.as_raw_fd(),
+                ),
+            };
+
+            if let Some((stop_tx, poller)) = poller {
+                drop(stop_tx);
+                let _ = poller.join();
+            }
+            drop(fd);
+            result
+        });
+
+        Ok(ZfsSend {
+            _dataset: PhantomData,
+            thread,
+        })
+    }
+}
+
+/// Checks of [`ZfsSendFlags`] against the pool a stream is to be received into.
+///
+/// [`ZfsSendFlags`] comes from the bindings, so these are in a trait, which has to be in scope:
+/// `use libzfs::SendFlags;`.
+pub trait SendFlags {
+    /// Check that the destination pool has every feature enabled which a stream sent with these
+    /// flags can need, failing with `EZFS_BADVERSION` naming the first one that's missing:
+    ///
+    /// * [`LZC_SEND_FLAG_LARGE_BLOCK`] needs `large_blocks`.
+    /// * [`LZC_SEND_FLAG_EMBED_DATA`] needs `embedded_data`, and `lz4_compress`, which embedded
+    ///   blocks may be compressed with.
+    /// * [`LZC_SEND_FLAG_COMPRESS`] needs `lz4_compress` and `zstd_compress`, for data sent as
+    ///   it's compressed on disk. Other compression algorithms don't need a feature.
+    /// * [`LZC_SEND_FLAG_RAW`] needs all of the above, since raw streams have blocks as they are
+    ///   on disk, and `encryption`, for encrypted datasets.
+    ///
+    /// A stream only needs the features which its source actually uses, so this is pessimistic;
+    /// [`Dataset::check_send_compatible`] only checks the ones the source pool uses.
+    ///
+    /// [`LZC_SEND_FLAG_LARGE_BLOCK`]: ZfsSendFlags::LZC_SEND_FLAG_LARGE_BLOCK
+    /// [`LZC_SEND_FLAG_EMBED_DATA`]: ZfsSendFlags::LZC_SEND_FLAG_EMBED_DATA
+    /// [`LZC_SEND_FLAG_COMPRESS`]: ZfsSendFlags::LZC_SEND_FLAG_COMPRESS
+    /// [`LZC_SEND_FLAG_RAW`]: ZfsSendFlags::LZC_SEND_FLAG_RAW
+    fn check_compatible(&self, destination: &ZPool) -> Result<()>;
+}
+
+impl SendFlags for ZfsSendFlags {
+    fn check_compatible(&self, destination: &ZPool) -> Result<()> {
+        for feature in stream_features(*self) {
+            check_feature_enabled(destination, feature)?;
+        }
+        Ok(())
+    }
+}
+
+/// The pool features a stream sent with the given flags can need, depending on what its source
+/// uses. See [`SendFlags::check_compatible`].
+fn stream_features(flags: ZfsSendFlags) -> Vec<&'static str> {
+    let has = |flag: ZfsSendFlags| flags & flag == flag;
+    let raw = has(ZfsSendFlags::LZC_SEND_FLAG_RAW);
+    let mut features = vec![];
+    if raw || has(ZfsSendFlags::LZC_SEND_FLAG_LARGE_BLOCK) {
+        features.push("large_blocks");
+    }
+    if raw || has(ZfsSendFlags::LZC_SEND_FLAG_EMBED_DATA) {
+        features.push("embedded_data");
+    }
+    if raw || has(ZfsSendFlags::LZC_SEND_FLAG_EMBED_DATA)
+        || has(ZfsSendFlags::LZC_SEND_FLAG_COMPRESS)
+    {
+        features.push("lz4_compress");
+    }
+    if raw || has(ZfsSendFlags::LZC_SEND_FLAG_COMPRESS) {
+        features.push("zstd_compress");
+    }
+    if raw {
+        features.push("encryption");
+    }
+    features
+}
+
+fn check_feature_enabled(pool: &ZPool, feature: &str) -> Result<()> {
+    if pool.feature_refcount(feature)?.is_none() {
+        return Err(Error::Zfs(ZfsError::new(
+            sys::zfs_error::EZFS_BADVERSION,
+            format!("pool '{}' needs the '{}' feature enabled to receive this stream",
+                pool.get_name(), feature),
+        )));
+    }
+    Ok(())
+}
+
+/// Check that the source of an incremental stream exists and was created before the snapshot
+/// being sent, which the kernel only reports as an invalid cross-device link. Returns the
+/// source's `createtxg`.
+fn check_incremental_source(dataset: &Dataset, from_fq: &SafeString) -> Result<u64> {
+    let types = DatasetType::Snapshot | DatasetType::Bookmark;
+    let mark = ErrorMark::new(dataset.libzfs);
+    let handle = unsafe { sys::zfs_open(dataset.libzfs, from_fq.as_ptr(), types.0 as c_int) };
+    if handle.is_null() {
+        return Err(mark.error(dataset.libzfs));
+    }
+    let from = Dataset { libzfs: dataset.libzfs, handle };
+
+    let from_txg = from.get_numeric_property("createtxg")?.value;
+    let to_txg = dataset.get_numeric_property("createtxg")?.value;
+    if from_txg >= to_txg {
+        return Err(Error::Zfs(ZfsError::new(
+            sys::zfs_error::EZFS_CROSSTARGET,
+            format!("incremental source '{}' (createtxg {}) is not earlier than '{}' \
+                (createtxg {})", from_fq, from_txg, dataset.get_name(), to_txg),
+        )));
+    }
+    Ok(from_txg)
+}
+
+/// Split the names of the snapshots to send with libzfs into the dataset's name and the
+/// snapshots' short names, checking that the incremental source is a snapshot of the same
+/// dataset.
+fn libzfs_send_names(to_fq: &SafeString, from_fq: Option<&SafeString>, intermediates: bool)
+    -> Result<(SafeString, Option<SafeString>, SafeString)>
+{
+    let bad_type = |msg: &str| Error::Zfs(ZfsError::new(sys::zfs_error::EZFS_BADTYPE, msg));
+    let (fs, to_snap) = AsRef::<str>::as_ref(to_fq)
+        .split_once('@')
+        .ok_or_else(|| bad_type("only snapshots can be sent"))?;
+    let from_fq = match from_fq {
+        Some(from_fq) => from_fq,
+        None if intermediates => {
+            return Err(bad_type("intermediate snapshots can only be sent incrementally"));
+        }
+        None => return Ok((SafeString::from(fs), None, SafeString::from(to_snap))),
+    };
+    let (from_fs, from_snap) = AsRef::<str>::as_ref(from_fq)
+        .split_once('@')
+        .ok_or_else(|| bad_type("with these options, the incremental source must be a snapshot"))?;
+    if from_fs != fs {
+        return Err(Error::Zfs(ZfsError::new(
+            sys::zfs_error::EZFS_CROSSTARGET,
+            "with these options, the incremental source must be a snapshot of the same dataset",
+        )));
+    }
+    Ok((SafeString::from(fs), Some(SafeString::from(from_snap)), SafeString::from(to_snap)))
+}
+
+/// Translate the libzfs_core send flags to the equivalent libzfs ones.
+pub(crate) fn libzfs_send_flags(flags: ZfsSendFlags) -> sys::sendflags_t {
+    let has = |flag: ZfsSendFlags| (flags & flag == flag) as sys::boolean_t;
+    let mut sendflags: sys::sendflags_t = unsafe { mem::zeroed() };
+    sendflags.largeblock = has(ZfsSendFlags::LZC_SEND_FLAG_LARGE_BLOCK);
+    sendflags.embed_data = has(ZfsSendFlags::LZC_SEND_FLAG_EMBED_DATA);
+    sendflags.compress = has(ZfsSendFlags::LZC_SEND_FLAG_COMPRESS);
+    sendflags.raw = has(ZfsSendFlags::LZC_SEND_FLAG_RAW);
+    sendflags.saved = has(ZfsSendFlags::LZC_SEND_FLAG_SAVED);
+    sendflags
+}
+
+fn send_with_libzfs_core(
+    libzfs: *mut sys::libzfs_handle_t,
+    to_fq: &SafeString,
+    from_fq: Option<&SafeString>,
+    flags: ZfsSendFlags,
+    fd: c_int,
+) -> Result<()> {
+    ztry!(unsafe {
+        sys::lzc_send(
+            to_fq.as_ptr(),
+            from_fq.map(|s| s.as_ptr()).unwrap_or(ptr::null()),
+            fd,
+            flags,
+        )
+    }, libzfs);
+    Ok(())
+}
+
+/// Send with libzfs instead of libzfs_core, which can't send intermediate snapshots,
+/// properties or holds.
+fn send_with_libzfs(
+    libzfs: *mut sys::libzfs_handle_t,
+    fs: &SafeString,
+    from_snap: Option<&SafeString>,
+    to_snap: &SafeString,
+    sendflags: &mut sys::sendflags_t,
+    fd: c_int,
+) -> Result<()> {
+    let types = sys::zfs_type_t::ZFS_TYPE_FILESYSTEM | sys::zfs_type_t::ZFS_TYPE_VOLUME;
+    let mark = ErrorMark::new(libzfs);
+    let zhp = unsafe { sys::zfs_open(libzfs, fs.as_ptr(), types as c_int) };
+    if zhp.is_null() {
+        return Err(mark.error(libzfs));
+    }
+    let mark = ErrorMark::new(libzfs);
+    let result = unsafe {
+        sys::zfs_send(
+            zhp,
+            from_snap.map(|s| s.as_ptr()).unwrap_or(ptr::null()),
+            to_snap.as_ptr(),
+            sendflags,
+            fd,
+            None,
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )
+    };
+    // libzfs reports write errors as a bad backup stream; report the receiver going away the
+    // same way lzc_send does.
+    let os_error = io::Error::last_os_error();
+    let error = (result != 0).then(|| mark.error(libzfs));
+    unsafe { sys::zfs_close(zhp) };
+    if result != 0 && os_error.raw_os_error() == Some(libc::EPIPE) {
+        return Err(Error::Sys(os_error));
+    }
+    error.map_or(Ok(()), Err)
+}
+
+/// Make a pipe, returning its read and write ends.
+pub(crate) fn pipe() -> Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    if 0 != unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } {
+        return Err(Error::Sys(io::Error::last_os_error()));
+    }
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+pub(crate) struct SendPtr<T>(pub(crate) *mut T);
+unsafe impl<T> Send for SendPtr<T> {}
+impl<T> SendPtr<T> {
+    pub fn ptr(&self) -> *mut T {
+        self.0
+    }
+}
+
+impl Dataset {
+    /// Replicate this snapshot into another dataset on the same system, like
+    /// `zfs send snap | zfs receive target`, and wait for it to finish.
+    ///
+    /// `target` is the name of the snapshot to create, or of the dataset to receive into, in
+    /// which case the snapshot gets the same short name as this one. For an incremental stream
+    /// from `from` (given by its full name), the target must already have that snapshot, and
+    /// must not have been modified since.
+    ///
+    /// The stream is sent from a separate thread and received on this one. If either end fails,
+    /// the other is stopped too, and the error from the end which failed first is returned. If
+    /// the receive fails, the sending thread may get `SIGPIPE`, which Rust programs ignore by
+    /// default.
+    ///
+    /// Before anything is sent, the target's pool is checked for the features the stream needs,
+    /// as described by [`Dataset::check_send_compatible`].
+    pub fn send_to(&self, target: &SafeString, from: Option<&SafeString>, flags: ZfsSendFlags)
+        -> Result<()>
+    {
+        let name = self.get_name();
+        let snap = match AsRef::<str>::as_ref(&name).split_once('@') {
+            Some((_, snap)) => snap.to_owned(),
+            None => return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADTYPE,
+                "only snapshots can be sent",
+            ))),
+        };
+        let target: &str = target.as_ref();
+        let target_snap = if target.contains('@') {
+            SafeString::from(target)
+        } else {
+            SafeString::from(format!("{}@{}", target, snap))
+        };
+
+        // lzc_receive only says ENOTSUP if the target pool is missing a feature the stream uses,
+        // so check first, to say which one.
+        let pool_name = SafeString::from(target.split(&['/', '@'][..]).next().unwrap_or(target));
+        let mark = ErrorMark::new(self.libzfs);
+        let pool = unsafe { sys::zpool_open(self.libzfs, pool_name.as_ptr()) };
+        if pool.is_null() {
+            return Err(mark.error(self.libzfs));
+        }
+        self.check_send_compatible(flags, &ZPool { libzfs: self.libzfs, handle: pool })?;
+
+        let (read_fd, write_fd) = pipe()?;
+
+        let mut builder = self.send_builder().flags(flags);
+        if let Some(from) = from {
+            builder = builder.from(from.clone());
+        }
+        // The sending thread owns the write end, and closes it when it's done, which ends the
+        // stream for the receiver.
+        let send = builder.send(write_fd)?;
+
+        let raw = flags & ZfsSendFlags::LZC_SEND_FLAG_RAW == ZfsSendFlags::LZC_SEND_FLAG_RAW;
+        let err = unsafe {
+            sys::lzc_receive(
+                target_snap.as_ptr(),
+                ptr::null_mut(),
+                ptr::null(),
+                false as sys::boolean_t,
+                raw as sys::boolean_t,
+                read_fd.as_raw_fd(),
+            )
+        };
+        // Closing the read end makes the sender fail with EPIPE if it's not done yet.
+        drop(read_fd);
+        let send_result = send.wait();
+
+        if err == 0 {
+            return send_result;
+        }
+        match send_result {
+            // The sender only failed because the receiver stopped reading.
+            Err(Error::Sys(ref e)) if e.raw_os_error() == Some(libc::EPIPE) => {}
+            // The receiver most likely failed because the stream was cut short.
+            Err(e) => return Err(e),
+            Ok(()) => {}
+        }
+        Err(Error::Sys(io::Error::from_raw_os_error(err)))
+    }
+
+    /// Start sending an incremental stream of this snapshot to the given file descriptor, on a
+    /// separate thread. This is a shortcut for [`SendBuilder::from`] and
+    /// [`SendBuilder::intermediates`].
+    ///
+    /// The source must have been created before this snapshot; otherwise this fails with
+    /// `EZFS_CROSSTARGET` before anything is sent. The kernel checks that it's really an
+    /// earlier snapshot of this one's dataset (or of one it was cloned from), or a bookmark of
+    /// one, and fails the send otherwise.
+    pub fn send_incremental(&self, from: IncrementalSource, fd: OwnedFd, flags: ZfsSendFlags)
+        -> Result<ZfsSend<'_>>
+    {
+        let builder = self.send_builder().flags(flags);
+        match from {
+            IncrementalSource::Since(from) => builder.from(from),
+            IncrementalSource::AllIntermediateSince(from) => {
+                builder.from(from).intermediates(true)
+            }
+        }.send(fd)
+    }
+
+    /// Estimate the size in bytes of the stream that sending this snapshot would produce, like
+    /// `zfs send -nv`, without sending anything. Flags which change the size of the stream,
+    /// like [`ZfsSendFlags::LZC_SEND_FLAG_COMPRESS`], are taken into account.
+    ///
+    /// The source is checked the same way as for [`Dataset::send_incremental`]. For
+    /// [`IncrementalSource::AllIntermediateSince`], this is the sum of the estimates for each
+    /// snapshot in between.
+    pub fn send_estimate(&self, from: Option<IncrementalSource>, flags: ZfsSendFlags)
+        -> Result<u64>
+    {
+        match from {
+            None => self.get_send_space(None, flags),
+            Some(IncrementalSource::Since(from)) => {
+                check_incremental_source(self, &from)?;
+                self.get_send_space(Some(&from), flags)
+            }
+            Some(IncrementalSource::AllIntermediateSince(from)) => {
+                let name = self.get_name();
+                let (fs, _, _) = libzfs_send_names(&name, Some(&from), true)?;
+                let from_txg = check_incremental_source(self, &from)?;
+
+                let types = DatasetType::Filesystem | DatasetType::Volume;
+                let mark = ErrorMark::new(self.libzfs);
+                let handle = unsafe { sys::zfs_open(self.libzfs, fs.as_ptr(), types.0 as c_int) };
+                if handle.is_null() {
+                    return Err(mark.error(self.libzfs));
+                }
+                let fs = Dataset { libzfs: self.libzfs, handle };
+
+                let to_txg = self.get_numeric_property("createtxg")?.value;
+                let mut prev = from;
+                let mut total = 0;
+                for snap in fs.get_snapshots_ordered()? {
+                    let txg = snap.get_numeric_property("createtxg")?.value;
+                    if txg <= from_txg || txg > to_txg {
+                        continue;
+                    }
+                    total += snap.get_send_space(Some(&prev), flags)?;
+                    prev = snap.get_name();
+                }
+                Ok(total)
+            }
+        }
+    }
+
+    /// Check that a stream of this snapshot, sent with the given flags, could be received into
+    /// the given pool, and that it can be sent at all. This reports problems which the kernel
+    /// would otherwise only report partway through, with unhelpful errors:
+    ///
+    /// * `EZFS_BADVERSION`, naming the feature, if the destination pool doesn't have a feature
+    ///   enabled which the stream needs. These are the ones listed for
+    ///   [`SendFlags::check_compatible`], but only those which this snapshot's pool is using,
+    ///   and `encryption` only if this snapshot is encrypted.
+    /// * `EZFS_CRYPTOFAILED` if this snapshot is encrypted and its key isn't loaded, which
+    ///   only raw sends allow.
+    ///
+    /// A feature in use by the snapshot's pool may not be used by this snapshot itself, so this
+    /// can still be pessimistic. A raw send of an unencrypted dataset is allowed, and is the same
+    /// as sending with [`LZC_SEND_FLAG_LARGE_BLOCK`], [`LZC_SEND_FLAG_EMBED_DATA`] and
+    /// [`LZC_SEND_FLAG_COMPRESS`].
+    ///
+    /// [`LZC_SEND_FLAG_LARGE_BLOCK`]: ZfsSendFlags::LZC_SEND_FLAG_LARGE_BLOCK
+    /// [`LZC_SEND_FLAG_EMBED_DATA`]: ZfsSendFlags::LZC_SEND_FLAG_EMBED_DATA
+    /// [`LZC_SEND_FLAG_COMPRESS`]: ZfsSendFlags::LZC_SEND_FLAG_COMPRESS
+    pub fn check_send_compatible(&self, flags: ZfsSendFlags, destination: &ZPool) -> Result<()> {
+        let encrypted = AsRef::<str>::as_ref(&self.get_property("encryption")?.value) != "off";
+        let raw = flags & ZfsSendFlags::LZC_SEND_FLAG_RAW == ZfsSendFlags::LZC_SEND_FLAG_RAW;
+        if encrypted && !raw {
+            let keystatus = self.get_property("keystatus")?.value;
+            if AsRef::<str>::as_ref(&keystatus) != "available" {
+                return Err(Error::Zfs(ZfsError::new(
+                    sys::zfs_error::EZFS_CRYPTOFAILED,
+                    format!("'{}' is encrypted and its key isn't loaded; \
+                        only a raw send is possible", self.get_name()),
+                )));
+            }
+        }
+
+        // The pool handle libzfs caches for the dataset is its own, so open another one.
+        let source_name = self.get_pool_name();
+        let mark = ErrorMark::new(self.libzfs);
+        let handle = unsafe { sys::zpool_open(self.libzfs, source_name.as_ptr()) };
+        if handle.is_null() {
+            return Err(mark.error(self.libzfs));
+        }
+        let source = ZPool { libzfs: self.libzfs, handle };
+        for feature in stream_features(flags) {
+            // Only features the source is actually using end up in the stream.
+            let used = if feature == "encryption" {
+                encrypted
+            } else {
+                source.feature_refcount(feature)?.unwrap_or(0) != 0
+            };
+            if used {
+                check_feature_enabled(destination, feature)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Start configuring a send stream of this snapshot.
+    pub fn send_builder(&self) -> SendBuilder<'_> {
+        SendBuilder {
+            dataset: self,
+            from: None,
+            flags: ZfsSendFlags(0),
+            intermediates: false,
+            props: false,
+            holds: false,
+            backup: false,
+            progress: None,
+            check_from: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn features_for_each_flag() {
+        assert!(stream_features(ZfsSendFlags(0)).is_empty());
+        assert_eq!(stream_features(ZfsSendFlags::LZC_SEND_FLAG_LARGE_BLOCK), ["large_blocks"]);
+        assert_eq!(
+            stream_features(ZfsSendFlags::LZC_SEND_FLAG_EMBED_DATA),
+            ["embedded_data", "lz4_compress"],
+        );
+        assert_eq!(
+            stream_features(ZfsSendFlags::LZC_SEND_FLAG_COMPRESS),
+            ["lz4_compress", "zstd_compress"],
+        );
+        assert_eq!(
+            stream_features(ZfsSendFlags::LZC_SEND_FLAG_RAW),
+            ["large_blocks", "embedded_data", "lz4_compress", "zstd_compress", "encryption"],
+        );
+        assert_eq!(
+            stream_features(
+                ZfsSendFlags::LZC_SEND_FLAG_LARGE_BLOCK | ZfsSendFlags::LZC_SEND_FLAG_COMPRESS
+            ),
+            ["large_blocks", "lz4_compress", "zstd_compress"],
+        );
+    }
+}
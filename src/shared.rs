@@ -0,0 +1,72 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::{LibZfs, Result};
+
+/// A [`LibZfs`] handle which can be shared between threads, for long-running programs which
+/// don't want to pay for `libzfs_init` over and over. Cloning it gives another reference to the
+/// same handle.
+///
+/// libzfs handles aren't thread-safe: the handle, and every pool and dataset handle opened with
+/// it, share state like the last error and the mount table cache. So all use of the handle is
+/// serialized: [`SharedLibZfs::with`] holds a lock while it runs, and only one thread at a time
+/// gets to use the handle, and whatever it opens with it.
+///
+/// ```no_run
+/// # use libzfs::*;
+/// # fn main() -> Result<()> {
+/// let zfs = SharedLibZfs::new()?;
+/// let threads = (0..4).map(|_| {
+///     let zfs = zfs.clone();
+///     std::thread::spawn(move || {
+///         zfs.with(|libzfs| -> Result<Vec<SafeString>> {
+///             Ok(libzfs.get_zpools()?.iter().map(|pool| pool.get_name()).collect())
+///         })
+///     })
+/// }).collect::<Vec<_>>();
+/// for thread in threads {
+///     println!("{:?}", thread.join().unwrap()?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SharedLibZfs {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Debug)]
+struct Inner(LibZfs);
+
+// Safety: the handle is only ever used with the lock held, and nothing opened with it can leave
+// the closure passed to `with` (see there), so it's never used by two threads at once.
+unsafe impl Send for Inner {}
+
+impl SharedLibZfs {
+    pub fn new() -> Result<Self> {
+        Ok(SharedLibZfs::from(LibZfs::new()?))
+    }
+
+    /// Run `f` with the handle, while holding the lock which serializes all use of it. Other
+    /// threads calling this wait until `f` returns.
+    ///
+    /// Pools, datasets and other things opened with the handle must be dropped before `f`
+    /// returns, since using them outside of the lock would race with other threads. The `Send`
+    /// bounds ensure this: they can't be returned, or stored in anything `f` captures. (Don't
+    /// smuggle them out through a thread-local either.)
+    ///
+    /// If another thread panicked inside `f`, the handle is still usable, since a panic can't
+    /// happen in the middle of a libzfs call.
+    pub fn with<F, R>(&self, f: F) -> R
+        where F: FnOnce(&LibZfs) -> R + Send,
+              R: Send,
+    {
+        let guard = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        f(&guard.0)
+    }
+}
+
+impl From<LibZfs> for SharedLibZfs {
+    fn from(libzfs: LibZfs) -> Self {
+        SharedLibZfs { inner: Arc::new(Mutex::new(Inner(libzfs))) }
+    }
+}
@@ -0,0 +1,162 @@
+use libzfs_sys as sys;
+
+use std::ffi::CStr;
+use std::mem;
+
+use crate::checkpoint::checkpoint_stats;
+use crate::vdev::{top_level_class, vdev_stats};
+use crate::{ztry, Error, Result, SafeString, VdevClass, ZPool, ZfsError};
+
+/// Space usage of a pool, as shown by `zpool list`. Sizes are in bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolSpace {
+    /// Total size of the pool's normal, special and dedup vdevs. Log and cache devices don't
+    /// count towards the pool's size.
+    pub size: u64,
+    pub allocated: u64,
+    pub free: u64,
+    /// Space used by the pool's checkpoint, or `None` if there's no checkpoint.
+    pub checkpoint: Option<u64>,
+    /// Space which could be added to the pool by expanding its devices, such as after
+    /// replacing them with bigger ones, or `None` if there is none.
+    pub expand_size: Option<u64>,
+    /// Fragmentation of the free space of the normal vdevs, as a percentage, or `None` if it
+    /// isn't known (such as when the `spacemap_histogram` feature isn't enabled).
+    pub fragmentation: Option<u8>,
+    /// How full the pool is, as a percentage from 0 to 100.
+    pub capacity: u8,
+    /// The ratio of the space that deduplicated data would use without deduplication to the
+    /// space it does use. This is 1.0 if nothing is deduplicated.
+    pub dedup_ratio: f64,
+    /// Space usage of each top-level vdev, including log and cache devices, but not spares.
+    pub vdevs: Vec<VdevSpace>,
+}
+
+/// Space usage of a top-level vdev, as shown by `zpool list -v`. Sizes are in bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VdevSpace {
+    /// The name `zpool list` shows for the vdev, like `mirror-0`, or the name of the device.
+    pub name: SafeString,
+    pub guid: u64,
+    pub class: VdevClass,
+    pub size: u64,
+    pub allocated: u64,
+    pub free: u64,
+    /// Space which could be added by expanding the vdev's devices, or `None` if there is none.
+    pub expand_size: Option<u64>,
+    /// Fragmentation of the vdev's free space, as a percentage, or `None` if it isn't known.
+    pub fragmentation: Option<u8>,
+}
+
+impl ZPool {
+    /// Get the pool's space usage, in total and for each top-level vdev.
+    ///
+    /// This refreshes the pool's statistics first, so the numbers are current. Unlike the
+    /// properties, like [`ZPool::get_allocated`], everything is read from the same snapshot
+    /// of the statistics, so the numbers add up.
+    pub fn space(&self) -> Result<PoolSpace> {
+        let mut missing: sys::boolean_t = 0;
+        ztry!(unsafe { sys::zpool_refresh_stats(self.handle, &mut missing) }, self.libzfs);
+
+        let config = self.config();
+        let tree = config.lookup_nvlist("vdev_tree").ok_or_else(|| {
+            Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_INVALCONFIG,
+                "pool configuration has no vdev tree",
+            ))
+        })?;
+
+        let children = tree.lookup_nvlist_array("children")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|child| (child, top_level_class(child)));
+        let cache = tree.lookup_nvlist_array("l2cache")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|child| (child, VdevClass::Cache));
+
+        let mut vdevs = vec![];
+        for (child, class) in children.chain(cache) {
+            // Holes and removed vdevs have no space.
+            let stats = match vdev_stats(child) {
+                Some(stats) if stats.vs_space != 0 => stats,
+                _ => continue,
+            };
+            let name = unsafe {
+                let ptr = sys::zpool_vdev_name(self.libzfs, self.handle, child.as_ptr(), 0);
+                let name = SafeString::from(CStr::from_ptr(ptr).to_string_lossy().into_owned());
+                libc::free(ptr as *mut libc::c_void);
+                name
+            };
+            vdevs.push(VdevSpace {
+                name,
+                guid: child.lookup_u64("guid").unwrap_or(0),
+                class,
+                size: stats.vs_space,
+                allocated: stats.vs_alloc,
+                free: stats.vs_space.saturating_sub(stats.vs_alloc),
+                expand_size: Some(stats.vs_esize).filter(|&size| size != 0),
+                fragmentation: Some(stats.vs_fragmentation)
+                    .filter(|&frag| frag <= 100)
+                    .map(|frag| frag as u8),
+            });
+        }
+
+        // The pool's size and allocation are those of its normal, special and dedup classes,
+        // and its fragmentation is that of the normal class, weighted by size.
+        let counted = || vdevs.iter()
+            .filter(|vdev| matches!(vdev.class,
+                VdevClass::Normal | VdevClass::Special | VdevClass::Dedup));
+        let size = counted().map(|vdev| vdev.size).sum::<u64>();
+        let allocated = counted().map(|vdev| vdev.allocated).sum::<u64>();
+        let expand_size = counted().map(|vdev| vdev.expand_size.unwrap_or(0)).sum::<u64>();
+        let capacity = if size == 0 {
+            0
+        } else {
+            (allocated as u128 * 100 / size as u128).min(100) as u8
+        };
+
+        let mut frag_total = 0u128;
+        let mut frag_size = 0u128;
+        let mut frag_known = true;
+        for vdev in vdevs.iter().filter(|vdev| vdev.class == VdevClass::Normal) {
+            match vdev.fragmentation {
+                Some(frag) => {
+                    frag_total += frag as u128 * vdev.size as u128;
+                    frag_size += vdev.size as u128;
+                }
+                None => frag_known = false,
+            }
+        }
+        let fragmentation = if frag_known && frag_size != 0 {
+            Some((frag_total / frag_size) as u8)
+        } else {
+            None
+        };
+
+        let dedup_ratio = match config.lookup_u64_array("ddt_stats") {
+            Some(stats) if mem::size_of_val(stats) >= mem::size_of::<sys::ddt_stat_t>() => {
+                // The array is the ddt_stat_t struct itself, which is all u64s.
+                let stats = unsafe { &*(stats.as_ptr() as *const sys::ddt_stat_t) };
+                if stats.dds_dsize == 0 {
+                    1.0
+                } else {
+                    stats.dds_ref_dsize as f64 / stats.dds_dsize as f64
+                }
+            }
+            _ => 1.0,
+        };
+
+        Ok(PoolSpace {
+            size,
+            allocated,
+            free: size.saturating_sub(allocated),
+            checkpoint: checkpoint_stats(tree).map(|stats| stats.space),
+            expand_size: Some(expand_size).filter(|&size| size != 0),
+            fragmentation,
+            capacity,
+            dedup_ratio,
+            vdevs,
+        })
+    }
+}
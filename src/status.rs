@@ -0,0 +1,308 @@
+use libzfs_sys as sys;
+
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::nvlist::OwnedNvList;
+use crate::{ztry, Error, Result, SafeString, ZPool, ZfsError};
+
+// Like translate_enum!, but maps values added by newer libzfs versions to `Other` instead of
+// panicking.
+macro_rules! status_reasons {
+    ($($(#[$doc:meta])* $sys:ident => $new:ident,)*) => {
+        /// Why a pool needs attention, as explained by `zpool status`.
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        pub enum ZpoolStatusReason {
+            $($(#[$doc])* $new,)*
+            /// A status not known to this version of the library.
+            Other(u32),
+        }
+
+        impl From<u32> for ZpoolStatusReason {
+            fn from(raw: u32) -> Self {
+                use sys::zpool_status_t::*;
+                match raw {
+                    $($sys => ZpoolStatusReason::$new,)*
+                    _ => ZpoolStatusReason::Other(raw),
+                }
+            }
+        }
+
+        impl From<ZpoolStatusReason> for u32 {
+            fn from(reason: ZpoolStatusReason) -> u32 {
+                use sys::zpool_status_t::*;
+                match reason {
+                    $(ZpoolStatusReason::$new => $sys,)*
+                    ZpoolStatusReason::Other(raw) => raw,
+                }
+            }
+        }
+    }
+}
+
+status_reasons! {
+    /// The pool's entry in the cache file is corrupt.
+    ZPOOL_STATUS_CORRUPT_CACHE => CorruptCache,
+    /// A device is missing, but the pool has enough redundancy to continue.
+    ZPOOL_STATUS_MISSING_DEV_R => MissingDeviceReplicated,
+    /// A device is missing, and the pool doesn't have enough redundancy to continue.
+    ZPOOL_STATUS_MISSING_DEV_NR => MissingDeviceNotReplicated,
+    /// A device has a bad label, but the pool has enough redundancy to continue.
+    ZPOOL_STATUS_CORRUPT_LABEL_R => CorruptLabelReplicated,
+    /// A device has a bad label, and the pool doesn't have enough redundancy to continue.
+    ZPOOL_STATUS_CORRUPT_LABEL_NR => CorruptLabelNotReplicated,
+    /// The sum of the device GUIDs doesn't match; a device is missing from the configuration.
+    ZPOOL_STATUS_BAD_GUID_SUM => BadGuidSum,
+    /// The pool metadata is corrupted.
+    ZPOOL_STATUS_CORRUPT_POOL => CorruptPool,
+    /// Data errors were found in user data; see the pool's error log.
+    ZPOOL_STATUS_CORRUPT_DATA => CorruptData,
+    /// A device has experienced read, write or checksum errors.
+    ZPOOL_STATUS_FAILING_DEV => FailingDevice,
+    /// The pool's on-disk version is newer than this system supports.
+    ZPOOL_STATUS_VERSION_NEWER => VersionNewer,
+    /// The pool was last accessed by another system.
+    ZPOOL_STATUS_HOSTID_MISMATCH => HostIdMismatch,
+    /// Multihost protection detected the pool is in use by another system.
+    ZPOOL_STATUS_HOSTID_ACTIVE => HostIdActive,
+    /// Multihost is enabled on the pool, but this system has no host ID set.
+    ZPOOL_STATUS_HOSTID_REQUIRED => HostIdRequired,
+    /// I/O to the pool has been suspended after failures (`failmode=wait`).
+    ZPOOL_STATUS_IO_FAILURE_WAIT => IoFailureWait,
+    /// I/O failures are being returned to applications (`failmode=continue`).
+    ZPOOL_STATUS_IO_FAILURE_CONTINUE => IoFailureContinue,
+    /// I/O was suspended because multihost writes couldn't be completed.
+    ZPOOL_STATUS_IO_FAILURE_MMP => IoFailureMmp,
+    /// The intent log can't be replayed.
+    ZPOOL_STATUS_BAD_LOG => BadLog,
+    /// The pool is affected by a known on-disk format erratum.
+    ZPOOL_STATUS_ERRATA => Errata,
+    /// The pool uses features this system doesn't support, so it can't be opened.
+    ZPOOL_STATUS_UNSUP_FEAT_READ => UnsupportedFeatureRead,
+    /// The pool uses features this system doesn't support, so it can only be opened read-only.
+    ZPOOL_STATUS_UNSUP_FEAT_WRITE => UnsupportedFeatureWrite,
+    /// A device is faulted, but the pool has enough redundancy to continue.
+    ZPOOL_STATUS_FAULTED_DEV_R => FaultedDeviceReplicated,
+    /// A device is faulted, and the pool doesn't have enough redundancy to continue.
+    ZPOOL_STATUS_FAULTED_DEV_NR => FaultedDeviceNotReplicated,
+    /// The pool uses a legacy on-disk version and can be upgraded.
+    ZPOOL_STATUS_VERSION_OLDER => VersionOlder,
+    /// Some supported features are not enabled on the pool.
+    ZPOOL_STATUS_FEAT_DISABLED => FeaturesDisabled,
+    /// A device is being resilvered.
+    ZPOOL_STATUS_RESILVERING => Resilvering,
+    /// A device has been taken offline by the administrator.
+    ZPOOL_STATUS_OFFLINE_DEV => OfflineDevice,
+    /// A device has been removed from the system.
+    ZPOOL_STATUS_REMOVED_DEV => RemovedDevice,
+    /// A device is being sequentially rebuilt.
+    ZPOOL_STATUS_REBUILDING => Rebuilding,
+    /// A sequential rebuild finished, and a scrub is recommended to verify checksums.
+    ZPOOL_STATUS_REBUILD_SCRUB => RebuildScrub,
+    /// A device's sector size doesn't match its native block size, hurting performance.
+    ZPOOL_STATUS_NON_NATIVE_ASHIFT => NonNativeAshift,
+    /// The pool's `compatibility` property can't be read or is invalid.
+    ZPOOL_STATUS_COMPATIBILITY_ERR => CompatibilityError,
+    /// The pool has features enabled which its `compatibility` property doesn't allow.
+    ZPOOL_STATUS_INCOMPATIBLE_FEAT => IncompatibleFeature,
+    /// Nothing is wrong.
+    ZPOOL_STATUS_OK => Ok,
+}
+
+/// The overall health of a pool, as shown in the `HEALTH` column of `zpool list`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PoolHealth {
+    Online,
+    /// Functioning, but with reduced redundancy due to problems with some devices.
+    Degraded,
+    /// Too many devices are faulted for the pool to function.
+    Faulted,
+    Offline,
+    Removed,
+    /// The pool's devices can't be opened.
+    Unavail,
+    /// I/O to the pool has been suspended after device failures, and will resume once the
+    /// devices come back and the errors are cleared.
+    Suspended,
+    Unknown,
+}
+
+impl PoolHealth {
+    /// The word `zpool list` shows for this health.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PoolHealth::Online => "ONLINE",
+            PoolHealth::Degraded => "DEGRADED",
+            PoolHealth::Faulted => "FAULTED",
+            PoolHealth::Offline => "OFFLINE",
+            PoolHealth::Removed => "REMOVED",
+            PoolHealth::Unavail => "UNAVAIL",
+            PoolHealth::Suspended => "SUSPENDED",
+            PoolHealth::Unknown => "UNKNOWN",
+        }
+    }
+
+    fn from_str(s: &str) -> Self {
+        match s {
+            "ONLINE" => PoolHealth::Online,
+            "DEGRADED" => PoolHealth::Degraded,
+            "FAULTED" => PoolHealth::Faulted,
+            "OFFLINE" => PoolHealth::Offline,
+            "REMOVED" => PoolHealth::Removed,
+            "UNAVAIL" => PoolHealth::Unavail,
+            "SUSPENDED" => PoolHealth::Suspended,
+            _ => PoolHealth::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for PoolHealth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An object with a permanent error, which `zpool status -v` lists under "errors:". The data
+/// in it couldn't be read or repaired.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorLogEntry {
+    /// The object set number of the dataset containing the object, like
+    /// [`Dataset::get_objsetid`](crate::Dataset::get_objsetid). The pool's own metadata is 0.
+    pub dataset: u64,
+    /// The object number within the dataset.
+    pub object: u64,
+    /// What `zpool status -v` shows for the object: the path of the file if it can be found,
+    /// otherwise the dataset's name and the object number (like `pool/fs:<0x3>`), or just
+    /// numbers (like `<0x36>:<0x3>`) if the dataset is gone too.
+    pub path: SafeString,
+}
+
+impl ZPool {
+    /// Get the pool's overall health, from the state of its root vdev, or
+    /// [`PoolHealth::Suspended`] if I/O to it has been suspended.
+    ///
+    /// This is different from [`ZPool::get_state`], which is about whether the pool is imported,
+    /// and from [`ZPool::status`], which says why the pool needs attention.
+    pub fn health(&self) -> Result<PoolHealth> {
+        let cstr = unsafe { CStr::from_ptr(sys::zpool_get_state_str(self.handle)) };
+        Ok(PoolHealth::from_str(&cstr.to_string_lossy()))
+    }
+
+    /// Get the pool's overall health as the word `zpool list` shows for it, like "ONLINE" or
+    /// "SUSPENDED". This is the same as [`ZPool::health`], but keeps words this library doesn't
+    /// know about, which [`ZPool::health`] reports as [`PoolHealth::Unknown`].
+    pub fn health_str(&self) -> Result<SafeString> {
+        let cstr = unsafe { CStr::from_ptr(sys::zpool_get_state_str(self.handle)) };
+        Ok(SafeString::from(cstr.to_string_lossy().into_owned()))
+    }
+
+    /// Get the main reason the pool needs attention, as `zpool status` explains it, along with
+    /// the ID of the message describing it (like "ZFS-8000-2Q") if there is one.
+    ///
+    /// Only the most severe problem is reported; anything other than [`ZpoolStatusReason::Ok`]
+    /// merits a look.
+    pub fn status(&self) -> Result<(ZpoolStatusReason, Option<SafeString>)> {
+        let mut msgid: *const c_char = ptr::null();
+        let mut errata: sys::zpool_errata_t = 0;
+        let status = unsafe { sys::zpool_get_status(self.handle, &mut msgid, &mut errata) };
+        let msgid = if msgid.is_null() {
+            None
+        } else {
+            let cstr = unsafe { CStr::from_ptr(msgid) };
+            Some(SafeString::from(cstr.to_string_lossy().into_owned()))
+        };
+        Ok((ZpoolStatusReason::from(status), msgid))
+    }
+
+    /// List the objects in the pool with permanent errors (like `zpool status -v`). The list is
+    /// kept until the errors have been cleared and the pool scrubbed twice.
+    ///
+    /// This refreshes the pool's statistics first, so the list is current.
+    pub fn get_errors(&self) -> Result<Vec<ErrorLogEntry>> {
+        let mut missing: sys::boolean_t = 0;
+        ztry!(unsafe { sys::zpool_refresh_stats(self.handle, &mut missing) }, self.libzfs);
+
+        let mut nverrlist: *mut sys::nvlist_t = ptr::null_mut();
+        ztry!(unsafe { sys::zpool_get_errlog(self.handle, &mut nverrlist) }, self.libzfs);
+        // There's no list if there are no errors.
+        if nverrlist.is_null() {
+            return Ok(vec![]);
+        }
+        let nverrlist = unsafe { OwnedNvList::from_raw(nverrlist) };
+
+        let mut buf = vec![0 as c_char; libc::PATH_MAX as usize * 2];
+        nverrlist.borrow()
+            .nvlists()
+            .map(|(_, entry)| {
+                // ZPOOL_ERR_DATASET, ZPOOL_ERR_OBJECT
+                let ids = (entry.lookup_u64("dataset"), entry.lookup_u64("object"));
+                let (dataset, object) = match ids {
+                    (Some(dataset), Some(object)) => (dataset, object),
+                    _ => return Err(Error::Zfs(ZfsError::new(
+                        sys::zfs_error::EZFS_INVALCONFIG,
+                        "error log entry has no dataset or object",
+                    ))),
+                };
+                let path = unsafe {
+                    sys::zpool_obj_to_path(
+                        self.handle,
+                        dataset,
+                        object,
+                        buf.as_mut_ptr(),
+                        buf.len(),
+                    );
+                    CStr::from_ptr(buf.as_ptr())
+                };
+                Ok(ErrorLogEntry {
+                    dataset,
+                    object,
+                    path: SafeString::from(path.to_string_lossy().into_owned()),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_reasons_round_trip() {
+        // The statuses are numbered consecutively, with ZPOOL_STATUS_OK last.
+        for raw in 0..=sys::zpool_status_t::ZPOOL_STATUS_OK {
+            let reason = ZpoolStatusReason::from(raw);
+            assert!(!matches!(reason, ZpoolStatusReason::Other(_)), "{} isn't mapped", raw);
+            assert_eq!(u32::from(reason), raw);
+        }
+    }
+
+    #[test]
+    fn health_strings_round_trip() {
+        let all = [
+            PoolHealth::Online,
+            PoolHealth::Degraded,
+            PoolHealth::Faulted,
+            PoolHealth::Offline,
+            PoolHealth::Removed,
+            PoolHealth::Unavail,
+            PoolHealth::Suspended,
+            PoolHealth::Unknown,
+        ];
+        for health in all {
+            assert_eq!(PoolHealth::from_str(health.as_str()), health);
+            assert_eq!(health.to_string(), health.as_str());
+        }
+        assert_eq!(PoolHealth::from_str("online"), PoolHealth::Unknown);
+        assert_eq!(PoolHealth::from_str("SPLIT"), PoolHealth::Unknown);
+    }
+
+    #[test]
+    fn unknown_status_reason_is_other() {
+        let raw = sys::zpool_status_t::ZPOOL_STATUS_OK + 1;
+        assert_eq!(ZpoolStatusReason::from(raw), ZpoolStatusReason::Other(raw));
+        assert_eq!(u32::from(ZpoolStatusReason::Other(raw)), raw);
+    }
+}
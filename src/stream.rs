@@ -0,0 +1,166 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::thread;
+
+use crate::receive::receive_with_handle;
+use crate::send::{pipe, SendPtr};
+use crate::{Dataset, Error, LibZfs, ReceiveOpts, Result, SafeString, ZfsSendFlags};
+
+// Same as the pipe buffer size the senders ask for.
+const BUF_SIZE: usize = 1_048_576;
+
+impl Dataset {
+    /// Send a full stream of this snapshot to something which isn't a file descriptor, like a
+    /// TLS connection, and wait for it to finish. Returns the number of bytes written.
+    ///
+    /// The stream is sent through a pipe from a separate thread, and copied to `writer` on this
+    /// one. If writing fails, the send is stopped, and the writer's error is returned. If the
+    /// send fails, whatever was written so far is left in `writer`, and the send's error is
+    /// returned. Stopping the send may make the sending thread get `SIGPIPE`, which Rust programs
+    /// ignore by default.
+    ///
+    /// For incremental streams and other options, see [`Dataset::send_builder`].
+    pub fn send_to_writer(&self, writer: &mut dyn Write, flags: ZfsSendFlags) -> Result<u64> {
+        let (read_fd, write_fd) = pipe()?;
+        let send = self.send_builder().flags(flags).send(write_fd)?;
+
+        let mut reader = File::from(read_fd);
+        let copy_result = copy_stream(&mut reader, writer);
+        // Closing the read end makes the sender fail with EPIPE if it's not done yet, so it
+        // can't block forever on a full pipe.
+        drop(reader);
+        let send_result = send.wait();
+
+        match copy_result {
+            Err(e) => Err(Error::Sys(e)),
+            Ok(total) => send_result.map(|()| total),
+        }
+    }
+}
+
+/// Copy `reader` to `writer` until `reader` ends, returning the number of bytes copied. Stops at
+/// the first error from either side, without reading any more.
+fn copy_stream(reader: &mut dyn Read, writer: &mut dyn Write) -> io::Result<u64> {
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut total = 0u64;
+    loop {
+        let len = match reader.read(&mut buf) {
+            Ok(0) => return Ok(total),
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buf[..len])?;
+        total += len as u64;
+    }
+}
+
+impl LibZfs {
+    /// Receive a send stream from something which isn't a file descriptor, like a TLS
+    /// connection, into `target`, and wait for it to finish. Returns the number of bytes read.
+    ///
+    /// The stream is copied from `reader` into a pipe on this thread, and received from the pipe
+    /// on a separate one, the same way as [`LibZfs::receive`]. If reading fails, the receive is
+    /// stopped, and the reader's error is returned. If the receive fails, reading stops, and the
+    /// receive's error is returned.
+    ///
+    /// Reading continues until `reader` reaches its end, so if more follows the stream, like on a
+    /// connection which stays open, limit it to the stream's length first (with
+    /// [`Read::take`]).
+    pub fn receive_from_reader(
+        &self,
+        target: &SafeString,
+        reader: &mut dyn Read,
+        opts: ReceiveOpts,
+    ) -> Result<u64> {
+        let (read_fd, write_fd) = pipe()?;
+        let libzfs = SendPtr(self.handle);
+
+        thread::scope(|scope| {
+            let receive = scope.spawn(move || {
+                // The receiving thread owns the read end, and closes it when it's done, which
+                // makes writes to the pipe fail with EPIPE instead of blocking forever.
+                receive_with_handle(libzfs.ptr(), target, read_fd, &opts)
+            });
+
+            let mut writer = File::from(write_fd);
+            let mut buf = vec![0u8; BUF_SIZE];
+            let mut total = 0u64;
+            let copy_result = loop {
+                let len = match reader.read(&mut buf) {
+                    Ok(0) => break Ok(()),
+                    Ok(len) => len,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => break Err(e),
+                };
+                if let Err(e) = writer.write_all(&buf[..len]) {
+                    // The receiver stopped reading, so its error says why.
+                    if e.raw_os_error() == Some(libc::EPIPE) {
+                        break Ok(());
+                    }
+                    break Err(e);
+                }
+                total += len as u64;
+            };
+            // Closing the write end ends the stream; if reading failed, the receiver fails on
+            // the truncated stream.
+            drop(writer);
+            let receive_result = receive.join().unwrap_or_else(|_| {
+                Err(Error::Sys(io::Error::other("zfs receive thread panicked")))
+            });
+
+            match copy_result {
+                Err(e) => Err(Error::Sys(e)),
+                Ok(()) => receive_result.map(|()| total),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Accepts `limit` bytes, then fails.
+    struct FailingWriter {
+        written: usize,
+        limit: usize,
+    }
+
+    impl Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.written == self.limit {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "connection closed"));
+            }
+            let len = buf.len().min(self.limit - self.written);
+            self.written += len;
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn copy_whole_stream() {
+        let len = 2 * BUF_SIZE as u64 + 123;
+        let mut reader = io::repeat(0xa5).take(len);
+        let mut writer = vec![];
+        assert_eq!(copy_stream(&mut reader, &mut writer).unwrap(), len);
+        assert_eq!(writer.len() as u64, len);
+        assert!(writer.iter().all(|&b| b == 0xa5));
+    }
+
+    #[test]
+    fn copy_stops_when_writer_fails() {
+        let len = 4 * BUF_SIZE as u64;
+        let mut reader = io::repeat(0).take(len);
+        let mut writer = FailingWriter { written: 0, limit: BUF_SIZE + BUF_SIZE / 2 };
+        let e = copy_stream(&mut reader, &mut writer).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(writer.written, writer.limit);
+        // Nothing is read after the chunk which couldn't be written.
+        assert_eq!(reader.limit(), len - 2 * BUF_SIZE as u64);
+    }
+}
@@ -1,5 +1,21 @@
 use std::ffi::{CStr, CString};
 use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{Error, ZfsError, ZfsErrorCode};
+
+static LOSSY_NAMES: AtomicBool = AtomicBool::new(false);
+
+/// Choose what happens when a pool or dataset name isn't valid UTF-8: by default, accessors like
+/// [`Dataset::get_name`](crate::Dataset::get_name) panic. With `lossy` set, invalid sequences are
+/// replaced with U+FFFD instead.
+///
+/// A name converted lossily no longer refers to the pool or dataset it came from, so it can't be
+/// used to open it again. This applies to the whole process.
+pub fn set_lossy_names(lossy: bool) {
+    LOSSY_NAMES.store(lossy, Ordering::Relaxed);
+}
 
 /// A FFI-friendly string: null-terminated, no internal nulls, well-formed UTF-8. Lets us skip
 /// checks and reallocations when passing around between functions.
@@ -14,6 +30,29 @@ impl SafeString {
     pub fn as_ptr(&self) -> *const ::std::os::raw::c_char {
         self.inner.as_ptr()
     }
+
+    /// Convert a pool or dataset name from libzfs, following the policy set by
+    /// [`set_lossy_names`].
+    pub(crate) fn from_name(cstr: &CStr, what: &str) -> SafeString {
+        if LOSSY_NAMES.load(Ordering::Relaxed) {
+            SafeString::from(cstr.to_string_lossy().into_owned())
+        } else {
+            let utf8_verified = cstr.to_str()
+                .unwrap_or_else(|_| panic!("invalid UTF8 in {} name", what));
+            SafeString::from(utf8_verified.to_owned())
+        }
+    }
+
+    /// Convert a path, which libzfs requires to be UTF-8.
+    pub(crate) fn from_path(path: &Path) -> crate::Result<SafeString> {
+        match path.to_str() {
+            Some(s) => Ok(SafeString::from(s)),
+            None => Err(Error::Zfs(ZfsError::new(
+                ZfsErrorCode::EZFS_BADPATH,
+                format!("path {:?} is not valid UTF-8", path),
+            ))),
+        }
+    }
 }
 
 impl From<SafeString> for String {
@@ -0,0 +1,156 @@
+use libzfs_sys as sys;
+
+use std::io;
+use std::os::raw::c_int;
+use std::ptr;
+
+use crate::{Error, Result, VdevId, ZPool, ZfsError};
+
+/// An action to take on the TRIM of a pool's devices.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TrimCmd {
+    /// Start trimming, or resume a suspended TRIM (`zpool trim`).
+    Start,
+    /// Pause trimming, remembering its progress (`zpool trim -s`).
+    Suspend,
+    /// Stop trimming and forget its progress (`zpool trim -c`).
+    Cancel,
+}
+
+/// Options for [`ZPool::trim`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TrimOpts {
+    /// Limit trimming to this many bytes per second on each device (`zpool trim -r`). By
+    /// default, there's no limit.
+    pub rate: Option<u64>,
+    /// Use secure TRIM, which guarantees the data is erased, failing on devices which don't
+    /// support it (`zpool trim -d`).
+    pub secure: bool,
+    /// Only act on these devices. By default, all of the pool's leaf devices are used, except
+    /// for cache devices and spares, and devices which don't support TRIM are skipped.
+    pub vdevs: Option<Vec<VdevId>>,
+}
+
+/// The state of the most recent manual TRIM of a device.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TrimState {
+    Active,
+    Suspended,
+    Canceled,
+    Complete,
+}
+
+impl TrimState {
+    /// Translate a `vdev_trim_state_t`, which is `None` if the device has never been trimmed.
+    fn from_raw(state: u64) -> Option<Self> {
+        use sys::vdev_trim_state::*;
+        match state as sys::vdev_trim_state_t {
+            VDEV_TRIM_ACTIVE => Some(TrimState::Active),
+            VDEV_TRIM_SUSPENDED => Some(TrimState::Suspended),
+            VDEV_TRIM_CANCELED => Some(TrimState::Canceled),
+            VDEV_TRIM_COMPLETE => Some(TrimState::Complete),
+            _ => None,
+        }
+    }
+}
+
+/// Progress of the most recent manual TRIM of a leaf device, as shown by `zpool status -t`.
+///
+/// Times are in seconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrimProgress {
+    pub state: TrimState,
+    /// Bytes trimmed so far.
+    pub bytes_done: u64,
+    /// Estimated total bytes to trim.
+    pub bytes_est: u64,
+    /// When the TRIM was started, or last suspended, canceled or completed.
+    pub action_time: u64,
+}
+
+impl TrimProgress {
+    /// Read the progress from a `vdev_stat_t`, or `None` if the device has never been trimmed.
+    pub(crate) fn from_stats(stats: &sys::vdev_stat_t) -> Option<Self> {
+        Some(TrimProgress {
+            state: TrimState::from_raw(stats.vs_trim_state)?,
+            bytes_done: stats.vs_trim_bytes_done,
+            bytes_est: stats.vs_trim_bytes_est,
+            action_time: stats.vs_trim_action_time,
+        })
+    }
+
+    pub fn bytes_remaining(&self) -> u64 {
+        self.bytes_est.saturating_sub(self.bytes_done)
+    }
+
+    /// How much of the TRIM is done, from 0 to 100.
+    pub fn percent_complete(&self) -> f64 {
+        if self.state == TrimState::Complete {
+            100.0
+        } else if self.bytes_est == 0 {
+            0.0
+        } else {
+            (self.bytes_done as f64 / self.bytes_est as f64 * 100.0).min(100.0)
+        }
+    }
+}
+
+impl ZPool {
+    /// Start, suspend or cancel a manual TRIM of the pool's devices, which tells them which
+    /// blocks are no longer in use. Each device's progress is in its
+    /// [`Vdev::trim`](crate::Vdev::trim).
+    ///
+    /// If any devices fail, the others are still acted on, and the failures are reported
+    /// together as [`Error::Vdevs`], with these codes:
+    /// * `EZFS_TRIM_NOTSUP`: the device doesn't support TRIM, or secure TRIM.
+    /// * `EZFS_TRIMMING`: starting a TRIM while one is already running.
+    /// * `EZFS_NO_TRIM`: suspending or canceling when no TRIM is running.
+    /// * `EZFS_BADDEV`: the device isn't a writable leaf device.
+    /// * `EZFS_NODEVICE`: the device is offline or missing.
+    pub fn trim(&self, cmd: TrimCmd, opts: TrimOpts) -> Result<()> {
+        let vdevs = self.vdev_list(opts.vdevs.as_deref())?;
+        let func = match cmd {
+            TrimCmd::Start => sys::pool_trim_func::POOL_TRIM_START,
+            TrimCmd::Suspend => sys::pool_trim_func::POOL_TRIM_SUSPEND,
+            TrimCmd::Cancel => sys::pool_trim_func::POOL_TRIM_CANCEL,
+        };
+
+        // This goes straight to libzfs_core, because zpool_trim only reports the last failure.
+        let mut errlist: *mut sys::nvlist_t = ptr::null_mut();
+        let err = unsafe {
+            sys::lzc_trim(
+                self.get_name().as_ptr(),
+                func,
+                opts.rate.unwrap_or(0),
+                opts.secure as sys::boolean_t,
+                vdevs.as_ptr(),
+                &mut errlist,
+            )
+        };
+        // ZPOOL_TRIM_VDEVS
+        let mut errors = vdevs.errors(err, errlist, "trim_vdevs", trim_error)?;
+        // Like the CLI, don't complain about devices which weren't asked for specifically.
+        if opts.vdevs.is_none() {
+            errors.retain(|_, e| e.code != sys::zfs_error::EZFS_TRIM_NOTSUP);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Vdevs(errors))
+        }
+    }
+}
+
+fn trim_error(errno: c_int) -> ZfsError {
+    use sys::zfs_error::*;
+    let code = match errno {
+        libc::ENODEV => EZFS_NODEVICE,
+        libc::EINVAL | libc::EROFS => EZFS_BADDEV,
+        libc::EBUSY => EZFS_TRIMMING,
+        libc::ESRCH => EZFS_NO_TRIM,
+        libc::EOPNOTSUPP => EZFS_TRIM_NOTSUP,
+        _ => EZFS_UNKNOWN,
+    };
+    ZfsError::new(code, io::Error::from_raw_os_error(errno).to_string())
+}
@@ -0,0 +1,159 @@
+use libzfs_sys as sys;
+
+use std::collections::BTreeSet;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::nvlist::NvList;
+use crate::{ztry, Error, Result, SafeString, ZPool, ZfsError};
+
+// SPA_VERSION_FEATURES: the version of pools which use feature flags instead of version numbers.
+const VERSION_FEATURES: u64 = 5000;
+
+/// What [`ZPool::upgrade`] changed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UpgradeReport {
+    /// The pool's legacy on-disk version, if it had one and was upgraded to use feature flags.
+    pub old_version: Option<u64>,
+    /// Features which were enabled, by their short names (like `async_destroy`).
+    pub enabled_features: Vec<SafeString>,
+}
+
+impl UpgradeReport {
+    /// Whether nothing changed, because the pool was already up to date.
+    pub fn is_empty(&self) -> bool {
+        self.old_version.is_none() && self.enabled_features.is_empty()
+    }
+}
+
+impl ZPool {
+    /// Upgrade the pool to use feature flags if it has a legacy version, and enable all features
+    /// supported by this system and allowed by the pool's `compatibility` property (like
+    /// `zpool upgrade`).
+    ///
+    /// Once enabled, features can't be disabled, and the pool may no longer be importable by
+    /// older software.
+    pub fn upgrade(&self) -> Result<UpgradeReport> {
+        let mut report = UpgradeReport::default();
+
+        let version = self.get_config().version().unwrap_or(0);
+        if version < VERSION_FEATURES {
+            ztry!(unsafe { sys::zpool_upgrade(self.handle, VERSION_FEATURES) }, self.libzfs);
+            report.old_version = Some(version);
+        }
+
+        let requested = self.compatible_features()?;
+        let before = self.enabled_feature_guids()?;
+        for info in feature_table() {
+            if info.fi_zfs_mod_supported == 0 || requested[info.fi_feature as usize] == 0 {
+                continue;
+            }
+            let guid = unsafe { CStr::from_ptr(info.fi_guid) }.to_string_lossy();
+            if before.contains(guid.as_ref()) {
+                continue;
+            }
+            let uname = unsafe { CStr::from_ptr(info.fi_uname) }.to_string_lossy();
+            self.set_property(&format!("feature@{}", uname), "enabled")?;
+        }
+
+        // Enabling a feature also enables the ones it depends on, so compare before and after
+        // rather than reporting only the ones set above.
+        let after = self.enabled_feature_guids()?;
+        for info in feature_table() {
+            let guid = unsafe { CStr::from_ptr(info.fi_guid) }.to_string_lossy();
+            if after.contains(guid.as_ref()) && !before.contains(guid.as_ref()) {
+                let uname = unsafe { CStr::from_ptr(info.fi_uname) }.to_string_lossy();
+                report.enabled_features.push(SafeString::from(uname.into_owned()));
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Enable a single feature on the pool, by its short name (like `async_destroy`), along with
+    /// any features it depends on. This is the same as setting the `feature@name` property to
+    /// `enabled`, and does nothing if the feature is already enabled.
+    ///
+    /// Fails with `EZFS_BADPROP`, listing the known features, if the name isn't one of them.
+    /// Pools with a legacy version need to be upgraded with [`ZPool::upgrade`] first.
+    pub fn enable_feature(&self, name: &str) -> Result<()> {
+        let cname = SafeString::from(name);
+        let mut feature: sys::spa_feature_t = sys::spa_feature::SPA_FEATURE_NONE;
+        if 0 != unsafe { sys::zfeature_lookup_name(cname.as_ptr(), &mut feature) } {
+            let known = feature_table()
+                .map(|info| unsafe { CStr::from_ptr(info.fi_uname) }.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADPROP,
+                format!("unknown feature '{}'; supported features are: {}", name, known),
+            )));
+        }
+        self.set_property(&format!("feature@{}", name), "enabled")
+    }
+
+    /// Get a feature's reference count, by its short name: `None` if it's disabled (or unknown),
+    /// 0 if it's enabled, and more than 0 if it's active, meaning something on disk uses it.
+    pub(crate) fn feature_refcount(&self, name: &str) -> Result<Option<u64>> {
+        let cname = SafeString::from(name);
+        let mut feature: sys::spa_feature_t = sys::spa_feature::SPA_FEATURE_NONE;
+        if 0 != unsafe { sys::zfeature_lookup_name(cname.as_ptr(), &mut feature) } {
+            return Ok(None);
+        }
+        let info = unsafe { &(*ptr::addr_of!(sys::spa_feature_table))[feature as usize] };
+        let guid = unsafe { CStr::from_ptr(info.fi_guid) }.to_string_lossy();
+
+        let mut missing: sys::boolean_t = 0;
+        ztry!(unsafe { sys::zpool_refresh_stats(self.handle, &mut missing) }, self.libzfs);
+        let features = unsafe { sys::zpool_get_features(self.handle) };
+        if features.is_null() {
+            return Ok(None);
+        }
+        Ok(unsafe { NvList::from_ptr(features) }.lookup_u64(guid.as_ref()))
+    }
+
+    /// Which features the pool's `compatibility` property allows, indexed by `spa_feature_t`.
+    fn compatible_features(&self) -> Result<Vec<sys::boolean_t>> {
+        let compat = self.get_property("compatibility")?.value;
+        let mut features = vec![0 as sys::boolean_t; sys::spa_feature::SPA_FEATURES as usize];
+        let mut report = vec![0 as c_char; 1024];
+        let status = unsafe {
+            sys::zpool_load_compat(
+                compat.as_ptr(),
+                features.as_mut_ptr(),
+                report.as_mut_ptr(),
+                report.len(),
+            )
+        };
+        use sys::zpool_compat_status_t::*;
+        match status {
+            // Unrecognized feature names are ignored, as with the CLI.
+            ZPOOL_COMPATIBILITY_OK | ZPOOL_COMPATIBILITY_WARNTOKEN => Ok(features),
+            _ => {
+                let report = unsafe { CStr::from_ptr(report.as_ptr()) };
+                Err(Error::Zfs(ZfsError::new(
+                    sys::zfs_error::EZFS_BADPROP,
+                    report.to_string_lossy().into_owned(),
+                )))
+            }
+        }
+    }
+
+    /// The GUIDs (like `com.delphix:async_destroy`) of the features enabled on the pool.
+    fn enabled_feature_guids(&self) -> Result<BTreeSet<String>> {
+        let mut missing: sys::boolean_t = 0;
+        ztry!(unsafe { sys::zpool_refresh_stats(self.handle, &mut missing) }, self.libzfs);
+        let features = unsafe { sys::zpool_get_features(self.handle) };
+        if features.is_null() {
+            // Pools with a legacy version have no features.
+            return Ok(BTreeSet::new());
+        }
+        let features = unsafe { NvList::from_ptr(features) };
+        Ok(features.names().map(String::from).collect())
+    }
+}
+
+fn feature_table() -> impl Iterator<Item = &'static sys::zfeature_info_t> {
+    unsafe { (*ptr::addr_of!(sys::spa_feature_table)).iter() }
+}
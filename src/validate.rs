@@ -0,0 +1,156 @@
+use libzfs_sys as sys;
+
+use std::collections::BTreeMap;
+use std::os::raw::c_int;
+
+use crate::nvlist::OwnedNvList;
+use crate::{Dataset, DatasetType, Error, ErrorMark, LibZfs, Result, SafeString, ZfsError};
+
+impl LibZfs {
+    /// Check whether a name is valid for a dataset of the given type, without checking whether
+    /// it exists:
+    /// * A filesystem or volume name is a pool name followed by any number of `/`-separated
+    ///   components, each made of letters, digits, and `_-.: `. It can't contain `@` or `#`.
+    /// * A snapshot name is a filesystem or volume name, `@`, and a component.
+    /// * A bookmark name is a filesystem or volume name, `#`, and a component.
+    /// * A pool name must start with a letter, and can't be a word like `mirror` or `log` which
+    ///   `zpool create` uses to describe vdevs.
+    ///
+    /// Names can be at most 255 bytes long.
+    pub fn is_valid_name(name: &SafeString, dataset_type: DatasetType) -> bool {
+        0 != unsafe { sys::zfs_name_valid(name.as_ptr(), dataset_type.into()) }
+    }
+
+    /// Check that a filesystem or volume could be created with the given name and properties,
+    /// like `zfs create -n`, without creating anything. Volumes need a `volsize` property.
+    ///
+    /// This checks the name's syntax, that its parent is an existing filesystem, that nothing
+    /// already has the name, and that each property is valid for the new dataset's type and has
+    /// a valid value. Failures which only the kernel finds, like a lack of space or permission,
+    /// aren't caught.
+    pub fn validate_create(
+        &self,
+        name: &SafeString,
+        dataset_type: DatasetType,
+        props: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        if !matches!(dataset_type, DatasetType::Filesystem | DatasetType::Volume) {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADTYPE,
+                "only filesystems and volumes can be created",
+            )));
+        }
+        if dataset_type == DatasetType::Volume && !props.contains_key("volsize") {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADPROP,
+                format!("cannot create '{}': missing volume size", name),
+            )));
+        }
+        validate_new_dataset(self.handle, name, dataset_type, props)
+    }
+}
+
+impl Dataset {
+    /// Check that this snapshot could be cloned to a new dataset with the given name and
+    /// properties, without creating anything. The clone is a filesystem or volume, like the
+    /// snapshot's dataset, and must be in the same pool.
+    ///
+    /// The same checks are done as for [`LibZfs::validate_create`].
+    pub fn validate_clone(&self, target: &SafeString, props: &BTreeMap<String, String>)
+        -> Result<()>
+    {
+        let name = self.get_name();
+        let origin_fs = match AsRef::<str>::as_ref(&name).split_once('@') {
+            Some((fs, _)) => SafeString::from(fs),
+            None => return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BADTYPE,
+                "only snapshots can be cloned",
+            ))),
+        };
+        let pool = self.get_pool_name();
+        let target_pool = AsRef::<str>::as_ref(target).split('/').next().unwrap_or_default();
+        if target_pool != AsRef::<str>::as_ref(&pool) {
+            return Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_CROSSTARGET,
+                format!("cannot create '{}': source and target pools differ", target),
+            )));
+        }
+
+        let types = DatasetType::Filesystem | DatasetType::Volume;
+        let mark = ErrorMark::new(self.libzfs);
+        let handle = unsafe { sys::zfs_open(self.libzfs, origin_fs.as_ptr(), types.0 as c_int) };
+        if handle.is_null() {
+            return Err(mark.error(self.libzfs));
+        }
+        let origin_type = Dataset { libzfs: self.libzfs, handle }.get_type();
+        validate_new_dataset(self.libzfs, target, origin_type, props)
+    }
+}
+
+/// Check the name and properties of a new filesystem or volume, and its parent, the same way
+/// libzfs does before creating it.
+fn validate_new_dataset(
+    libzfs: *mut sys::libzfs_handle_t,
+    name: &SafeString,
+    dataset_type: DatasetType,
+    props: &BTreeMap<String, String>,
+) -> Result<()> {
+    let sys_type: u32 = dataset_type.into();
+    let invalid = |msg: String| Error::Zfs(ZfsError::new(sys::zfs_error::EZFS_INVALIDNAME, msg));
+    if !LibZfs::is_valid_name(name, dataset_type) {
+        return Err(invalid(format!("cannot create '{}': invalid dataset name", name)));
+    }
+    let parent = match AsRef::<str>::as_ref(name).rsplit_once('/') {
+        Some((parent, _)) => SafeString::from(parent),
+        None => return Err(invalid(format!("cannot create '{}': missing dataset name", name))),
+    };
+
+    let all_types = DatasetType::Filesystem | DatasetType::Volume | DatasetType::Snapshot;
+    if 0 != unsafe { sys::zfs_dataset_exists(libzfs, name.as_ptr(), all_types.0) } {
+        return Err(Error::Zfs(ZfsError::new(
+            sys::zfs_error::EZFS_EXISTS,
+            format!("cannot create '{}': dataset already exists", name),
+        )));
+    }
+
+    let types = DatasetType::Filesystem | DatasetType::Volume;
+    let handle = unsafe { sys::zfs_open(libzfs, parent.as_ptr(), types.0 as c_int) };
+    if handle.is_null() {
+        return Err(Error::Zfs(ZfsError::new(
+            sys::zfs_error::EZFS_NOENT,
+            format!("cannot create '{}': parent does not exist", name),
+        )));
+    }
+    let parent = Dataset { libzfs, handle };
+    if parent.get_type() != DatasetType::Filesystem {
+        return Err(Error::Zfs(ZfsError::new(
+            sys::zfs_error::EZFS_BADTYPE,
+            format!("cannot create '{}': parent is not a filesystem", name),
+        )));
+    }
+
+    let mut nvl = OwnedNvList::new();
+    for (k, v) in props {
+        nvl.add_string(k, v);
+    }
+    let errbuf = SafeString::from(format!("cannot create '{}'", name));
+    let mark = ErrorMark::new(libzfs);
+    let valid = unsafe {
+        let zoned = sys::zfs_prop_get_int(parent.handle, sys::zfs_prop_t::ZFS_PROP_ZONED);
+        sys::zfs_valid_proplist(
+            libzfs,
+            sys_type,
+            nvl.as_ptr(),
+            zoned,
+            std::ptr::null_mut(),
+            sys::zfs_get_pool_handle(parent.handle),
+            1, // key_params_ok
+            errbuf.as_ptr(),
+        )
+    };
+    if valid.is_null() {
+        return Err(mark.error(libzfs));
+    }
+    drop(unsafe { OwnedNvList::from_raw(valid) });
+    Ok(())
+}
@@ -0,0 +1,742 @@
+use libzfs_sys as sys;
+
+use std::collections::BTreeMap;
+use std::io;
+use std::mem;
+use std::os::raw::{c_int, c_void};
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use crate::nvlist::{NvList, OwnedNvList};
+use crate::{
+    ztry, AddOpts, Error, InitializeProgress, LibZfs, Result, SafeString, ScanState, TrimProgress,
+    VdevDevice, VdevGroup, VdevSpec, ZPool, ZfsError,
+};
+
+/// A node in a pool's tree of virtual devices, as shown by `zpool status`.
+///
+/// This is a snapshot of the pool's configuration at the time it was read, and doesn't change
+/// along with the pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vdev {
+    pub vdev_type: VdevType,
+    pub guid: u64,
+    /// Path to the device or file, for leaf vdevs.
+    pub path: Option<PathBuf>,
+    /// Persistent device identifier, for leaf vdevs which are disks.
+    pub devid: Option<SafeString>,
+    pub state: VdevState,
+    /// Read errors seen since the pool was imported or the errors were cleared.
+    pub read_errors: u64,
+    /// Write errors seen since the pool was imported or the errors were cleared.
+    pub write_errors: u64,
+    /// Checksum errors seen since the pool was imported or the errors were cleared.
+    pub checksum_errors: u64,
+    /// Progress of the most recent initialization, for leaf vdevs which have been initialized.
+    pub initialize: Option<InitializeProgress>,
+    /// Progress of the most recent manual TRIM, for leaf vdevs which have been trimmed.
+    pub trim: Option<TrimProgress>,
+    /// What the vdev is used for. Children have the same class as their parent; the root vdev
+    /// is [`VdevClass::Normal`].
+    pub class: VdevClass,
+    pub children: Vec<Vdev>,
+}
+
+/// The kind of a virtual device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VdevType {
+    /// The root of the tree, whose children are the pool's top-level vdevs.
+    Root,
+    Mirror,
+    RaidZ1,
+    RaidZ2,
+    RaidZ3,
+    /// An in-progress replacement of one device by another.
+    Replacing,
+    /// A device which has been swapped out for a hot spare, along with the spare.
+    Spare,
+    Disk,
+    File,
+    /// A placeholder left where a top-level log device was removed.
+    Hole,
+    /// A placeholder for a top-level vdev which is missing from the configuration.
+    Missing,
+    /// A removed top-level vdev whose data has been remapped to other vdevs.
+    Indirect,
+    /// A vdev type not known to this version of the library.
+    Other(SafeString),
+}
+
+/// The state of a virtual device.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VdevState {
+    Online,
+    /// Functioning, but with reduced redundancy due to problems with some children.
+    Degraded,
+    /// Too many errors, or too many children are faulted, to function.
+    Faulted,
+    /// Taken offline by the administrator.
+    Offline,
+    /// Physically removed from the system.
+    Removed,
+    /// Can't be opened, or has been closed.
+    Unavail,
+    Unknown,
+}
+
+impl VdevState {
+    fn from_raw(state: sys::vdev_state_t) -> Self {
+        use sys::vdev_state::*;
+        match state {
+            VDEV_STATE_HEALTHY => VdevState::Online,
+            VDEV_STATE_DEGRADED => VdevState::Degraded,
+            VDEV_STATE_FAULTED => VdevState::Faulted,
+            VDEV_STATE_OFFLINE => VdevState::Offline,
+            VDEV_STATE_REMOVED => VdevState::Removed,
+            VDEV_STATE_CANT_OPEN | VDEV_STATE_CLOSED => VdevState::Unavail,
+            _ => VdevState::Unknown,
+        }
+    }
+}
+
+/// What a vdev is used for within its pool.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VdevClass {
+    /// Regular pool storage.
+    Normal,
+    /// Separate intent log.
+    Log,
+    /// L2ARC cache device.
+    Cache,
+    /// Hot spare.
+    Spare,
+    /// Allocation class for metadata and small blocks.
+    Special,
+    /// Allocation class for the deduplication table.
+    Dedup,
+}
+
+/// How far back to roll a damaged pool's state when clearing its errors, discarding the most
+/// recent transactions to get back to a consistent state.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RewindPolicy {
+    /// Roll back if needed to make the pool usable again (`zpool clear -F`).
+    Rewind,
+    /// Check whether rolling back would make the pool usable, without doing it
+    /// (`zpool clear -Fn`).
+    DryRun,
+    /// Roll back as far as needed, trying every possible transaction group. This is a last
+    /// resort, and can take a very long time (`zpool clear -FX`).
+    Extreme,
+}
+
+/// Identifies a vdev within a pool, for operations on individual devices.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VdevId {
+    /// The device's path, or its name as shown by `zpool status` (like `sda`).
+    Path(PathBuf),
+    Guid(u64),
+}
+
+/// Options for attaching or replacing a device.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AttachOpts {
+    /// Copy data onto the new device with a sequential rebuild instead of a resilver, which is
+    /// faster but doesn't verify checksums (`zpool attach -s`). A scrub is started afterwards.
+    pub rebuild: bool,
+}
+
+/// A hot spare device of a pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpareInfo {
+    pub vdev: Vdev,
+    pub status: SpareStatus,
+}
+
+/// Whether a hot spare can be used.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SpareStatus {
+    /// Ready to replace a failed device.
+    Available,
+    /// Currently replacing a failed device, in this pool or another one sharing the spare. It
+    /// shouldn't be removed until the failed device has been replaced.
+    InUse,
+    /// Not usable, for the reason given by its state.
+    Unavailable(VdevState),
+}
+
+/// Progress of the most recent removal of a top-level vdev, as shown by `zpool status`.
+///
+/// Times are in seconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemovalStats {
+    pub state: ScanState,
+    /// The index of the vdev being removed among the pool's top-level vdevs.
+    pub removing_vdev: u64,
+    pub start_time: u64,
+    /// When the removal finished or was canceled; `None` while it's still in progress.
+    pub end_time: Option<u64>,
+    /// Total bytes which need to be copied off the vdev.
+    pub to_copy: u64,
+    /// Bytes copied so far.
+    pub copied: u64,
+    /// Memory used to map the data of all removed vdevs to its new location, in bytes.
+    pub mapping_memory: u64,
+}
+
+impl RemovalStats {
+    /// How much of the removal is done, from 0 to 100.
+    pub fn percent_complete(&self) -> f64 {
+        if self.state == ScanState::Finished {
+            100.0
+        } else if self.to_copy == 0 {
+            0.0
+        } else {
+            (self.copied as f64 / self.to_copy as f64 * 100.0).min(100.0)
+        }
+    }
+}
+
+impl VdevId {
+    /// Whether this identifies the given vdev, by GUID, path, or device name.
+    fn matches(&self, vdev: &Vdev) -> bool {
+        match self {
+            VdevId::Guid(guid) => vdev.guid == *guid,
+            VdevId::Path(path) => vdev.path.as_ref().is_some_and(|vdev_path| {
+                vdev_path == path || vdev_path.file_name() == Some(path.as_os_str())
+            }),
+        }
+    }
+
+    // libzfs looks vdevs up by either path or GUID, given as a string.
+    fn to_safe_string(&self) -> Result<SafeString> {
+        match self {
+            VdevId::Path(path) => SafeString::from_path(path),
+            VdevId::Guid(guid) => Ok(SafeString::from(guid.to_string())),
+        }
+    }
+}
+
+/// Get the `vdev_stat_t` from a vdev's configuration, if it has one which is the right size.
+pub(crate) fn vdev_stats<'a>(config: NvList<'a>) -> Option<&'a sys::vdev_stat_t> {
+    config.lookup_u64_array("vdev_stats")
+        .filter(|stats| mem::size_of_val(*stats) >= mem::size_of::<sys::vdev_stat_t>())
+        .map(|stats| {
+            // The array is the vdev_stat_t struct itself, which is all u64s.
+            unsafe { &*(stats.as_ptr() as *const sys::vdev_stat_t) }
+        })
+}
+
+/// Get the class of a top-level vdev (other than cache devices and spares, which are listed
+/// separately) from its configuration.
+pub(crate) fn top_level_class(config: NvList<'_>) -> VdevClass {
+    if config.lookup_u64("is_log") == Some(1) {
+        VdevClass::Log
+    } else {
+        match config.lookup_string("alloc_bias").as_ref().map(AsRef::as_ref) {
+            Some("special") => VdevClass::Special,
+            Some("dedup") => VdevClass::Dedup,
+            _ => VdevClass::Normal,
+        }
+    }
+}
+
+impl Vdev {
+    pub(crate) fn from_config(config: NvList<'_>, class: VdevClass) -> Vdev {
+        let vdev_type = match config.lookup_string("type").as_ref().map(AsRef::as_ref) {
+            Some("root") => VdevType::Root,
+            Some("mirror") => VdevType::Mirror,
+            Some("raidz") => match config.lookup_u64("nparity") {
+                Some(3) => VdevType::RaidZ3,
+                Some(2) => VdevType::RaidZ2,
+                _ => VdevType::RaidZ1,
+            },
+            Some("replacing") => VdevType::Replacing,
+            Some("spare") => VdevType::Spare,
+            Some("disk") => VdevType::Disk,
+            Some("file") => VdevType::File,
+            Some("hole") => VdevType::Hole,
+            Some("missing") => VdevType::Missing,
+            Some("indirect") => VdevType::Indirect,
+            Some(other) => VdevType::Other(SafeString::from(other)),
+            None => VdevType::Other(SafeString::from("")),
+        };
+
+        let state = match config.lookup_u64_array("vdev_stats") {
+            // vdev_stat_t starts with: timestamp, state, aux
+            Some(stats) if stats.len() > 1 => VdevState::from_raw(stats[1] as sys::vdev_state_t),
+            _ => VdevState::Unknown,
+        };
+
+        let stats = vdev_stats(config);
+        let errors = stats
+            .map(|stats| (stats.vs_read_errors, stats.vs_write_errors, stats.vs_checksum_errors))
+            .unwrap_or((0, 0, 0));
+
+        let is_root = vdev_type == VdevType::Root;
+        let children = config.lookup_nvlist_array("children")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|child| {
+                let class = if is_root { top_level_class(child) } else { class };
+                Vdev::from_config(child, class)
+            });
+
+        // Cache devices and spares are listed separately from the root's other children.
+        let extra = [("l2cache", VdevClass::Cache), ("spares", VdevClass::Spare)]
+            .into_iter()
+            .flat_map(|(name, class)| {
+                config.lookup_nvlist_array(name)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(move |child| Vdev::from_config(child, class))
+            });
+
+        Vdev {
+            vdev_type,
+            guid: config.lookup_u64("guid").unwrap_or(0),
+            path: config.lookup_string("path").map(|p| PathBuf::from(String::from(p))),
+            devid: config.lookup_string("devid"),
+            state,
+            read_errors: errors.0,
+            write_errors: errors.1,
+            checksum_errors: errors.2,
+            initialize: stats.and_then(InitializeProgress::from_stats),
+            trim: stats.and_then(TrimProgress::from_stats),
+            class,
+            children: children.chain(extra).collect(),
+        }
+    }
+
+    /// Description of the redundancy of a top-level vdev, in the same terms as
+    /// `VdevGroup::redundancy`.
+    pub(crate) fn redundancy(&self) -> Option<String> {
+        let width = self.children.len();
+        match self.vdev_type {
+            VdevType::Disk | VdevType::File => Some("disk".to_owned()),
+            VdevType::Mirror => Some(format!("{}-way mirror", width)),
+            VdevType::RaidZ1 => Some(format!("{}-wide raidz1", width)),
+            VdevType::RaidZ2 => Some(format!("{}-wide raidz2", width)),
+            VdevType::RaidZ3 => Some(format!("{}-wide raidz3", width)),
+            _ => None,
+        }
+    }
+
+    /// Find the vdev with the given identity in the tree under this one.
+    pub(crate) fn find(&self, id: &VdevId) -> Option<&Vdev> {
+        if id.matches(self) {
+            Some(self)
+        } else {
+            self.children.iter().find_map(|child| child.find(id))
+        }
+    }
+
+    /// The name to report the vdev by: its path, or its GUID if it has no path.
+    pub(crate) fn name(&self) -> SafeString {
+        match &self.path {
+            Some(path) => SafeString::from(path.to_string_lossy().into_owned()),
+            None => SafeString::from(self.guid.to_string()),
+        }
+    }
+
+    /// Iterate over the leaf devices (disks and files) under this vdev, including itself if it's
+    /// a leaf.
+    pub fn leaves(&self) -> Box<dyn Iterator<Item = &Vdev> + '_> {
+        if self.children.is_empty() {
+            Box::new(std::iter::once(self))
+        } else {
+            Box::new(self.children.iter().flat_map(Vdev::leaves))
+        }
+    }
+}
+
+impl ZPool {
+    /// Get the pool's tree of virtual devices from its configuration.
+    pub fn vdev_tree(&self) -> Result<Vdev> {
+        let tree = self.config().lookup_nvlist("vdev_tree").ok_or_else(|| {
+            Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_INVALCONFIG,
+                "pool configuration has no vdev tree",
+            ))
+        })?;
+        Ok(Vdev::from_config(tree, VdevClass::Normal))
+    }
+
+    /// Find a device in the pool, including log and cache devices and spares, and get its
+    /// current state and error counts.
+    ///
+    /// Devices can be given by GUID, by path, or by the name `zpool status` shows for them. Like
+    /// the `zpool` commands, names which aren't paths are looked for under `/dev`, and whole
+    /// disks can be named without the partition ZFS created on them (like `sda` for
+    /// `/dev/sda1`). Fails with `EZFS_NODEVICE` if there's no such device.
+    pub fn find_vdev(&self, dev: &VdevId) -> Result<Vdev> {
+        let tree = self.vdev_tree()?;
+        if let Some(vdev) = tree.find(dev) {
+            return Ok(vdev.clone());
+        }
+
+        // libzfs knows the rules for short names and whole disks, so let it resolve those.
+        let path = dev.to_safe_string()?;
+        let mut spare: sys::boolean_t = 0;
+        let mut l2cache: sys::boolean_t = 0;
+        let mut log: sys::boolean_t = 0;
+        let config = unsafe {
+            sys::zpool_find_vdev(self.handle, path.as_ptr(), &mut spare, &mut l2cache, &mut log)
+        };
+        (!config.is_null())
+            .then(|| unsafe { NvList::from_ptr(config) }.lookup_u64("guid"))
+            .flatten()
+            .and_then(|guid| tree.find(&VdevId::Guid(guid)))
+            .cloned()
+            .ok_or_else(|| Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_NODEVICE,
+                format!("no such device in pool: {}", path),
+            )))
+    }
+}
+
+impl ZPool {
+    /// Bring a device back online (like `zpool online`), returning its resulting state. A device
+    /// which needs resilvering comes back [`VdevState::Degraded`] until the resilver finishes.
+    ///
+    /// With `expand`, the device is grown to use all of its space, if it has grown (`-e`).
+    pub fn vdev_online(&self, dev: &VdevId, expand: bool) -> Result<VdevState> {
+        let path = dev.to_safe_string()?;
+        let flags = if expand { sys::ZFS_ONLINE_EXPAND } else { 0 };
+        let mut state: sys::vdev_state_t = sys::vdev_state::VDEV_STATE_UNKNOWN;
+        ztry!(unsafe {
+            sys::zpool_vdev_online(self.handle, path.as_ptr(), flags as c_int, &mut state)
+        }, self.libzfs);
+        Ok(VdevState::from_raw(state))
+    }
+
+    /// Take a device offline (like `zpool offline`). With `temporary`, the device comes back
+    /// online when the pool is next imported (`-t`).
+    ///
+    /// Fails with `EZFS_NOREPLICAS` if the pool doesn't have enough other devices to keep
+    /// working without this one.
+    pub fn vdev_offline(&self, dev: &VdevId, temporary: bool) -> Result<()> {
+        let path = dev.to_safe_string()?;
+        ztry!(unsafe {
+            sys::zpool_vdev_offline(self.handle, path.as_ptr(), temporary as sys::boolean_t)
+        }, self.libzfs);
+        Ok(())
+    }
+
+    /// Reopen all of the pool's devices (like `zpool reopen`), such as to pick up devices which
+    /// have come back after going away. Unless `scrub_restart` is set, a scrub or resilver in
+    /// progress carries on where it left off instead of restarting (`-n`).
+    ///
+    /// The pool's cached configuration is refreshed afterwards, so that [`ZPool::status`] and
+    /// [`ZPool::vdev_tree`] show the devices' new states.
+    ///
+    /// Fails if the pool is no longer imported, or if it's suspended or otherwise can't be
+    /// written to.
+    pub fn reopen(&self, scrub_restart: bool) -> Result<()> {
+        let mut scrub_restart = scrub_restart as sys::boolean_t;
+        ztry!(unsafe {
+            sys::zpool_reopen_one(self.handle, &mut scrub_restart as *mut _ as *mut c_void)
+        }, self.libzfs);
+        let mut missing: sys::boolean_t = 0;
+        ztry!(unsafe { sys::zpool_refresh_stats(self.handle, &mut missing) }, self.libzfs);
+        Ok(())
+    }
+
+    /// Attach a new device to an existing one, making it a mirror (like `zpool attach`). If the
+    /// existing device is already part of a mirror, the new device is added to the mirror.
+    ///
+    /// The new device is a disk if it's a block device, or a file otherwise, and is used as-is:
+    /// no partitioning or labeling is done. Failures specific to the new device are reported with
+    /// these codes:
+    /// * `EZFS_DEVOVERFLOW`: the new device is smaller than the existing one.
+    /// * `EZFS_BADDEV`: the new device is in use, such as by another pool, or has a different
+    ///   optimal sector size.
+    pub fn vdev_attach(&self, existing: &VdevId, new_device: &Path, opts: AttachOpts)
+        -> Result<()>
+    {
+        self.attach(existing, new_device, opts, false)
+    }
+
+    /// Replace a device with a new one (like `zpool replace`). The old device is detached once
+    /// the new one has been resilvered.
+    ///
+    /// The same errors as [`ZPool::vdev_attach`] apply.
+    pub fn vdev_replace(&self, old: &VdevId, new_device: &Path, opts: AttachOpts) -> Result<()> {
+        self.attach(old, new_device, opts, true)
+    }
+
+    /// Detach a device from a mirror, or cancel a replacement (like `zpool detach`).
+    ///
+    /// Fails with `EZFS_NOREPLICAS` if the device holds the only valid copy of some data, such as
+    /// when the other side of the mirror is still resilvering.
+    pub fn vdev_detach(&self, dev: &VdevId) -> Result<()> {
+        let path = dev.to_safe_string()?;
+        ztry!(unsafe { sys::zpool_vdev_detach(self.handle, path.as_ptr()) }, self.libzfs);
+        Ok(())
+    }
+
+    /// Clear the error counts of all devices in the pool, or just the given one, and resume I/O
+    /// if the pool was suspended (like `zpool clear`).
+    ///
+    /// Clearing a suspended pool fails, typically with an I/O error, if its missing devices
+    /// still aren't available. A pool which is faulted because of damaged metadata can be
+    /// recovered by rolling it back according to `rewind_policy`.
+    pub fn clear(&self, dev: Option<&VdevId>, rewind_policy: Option<RewindPolicy>) -> Result<()> {
+        let path = dev.map(VdevId::to_safe_string).transpose()?;
+        let mut policy = OwnedNvList::new();
+        policy.add_u32("load-rewind-policy", match rewind_policy {
+            None => sys::ZPOOL_NO_REWIND,
+            Some(RewindPolicy::Rewind) => sys::ZPOOL_DO_REWIND,
+            Some(RewindPolicy::DryRun) => sys::ZPOOL_TRY_REWIND,
+            Some(RewindPolicy::Extreme) => sys::ZPOOL_DO_REWIND | sys::ZPOOL_EXTREME_REWIND,
+        });
+        ztry!(unsafe {
+            sys::zpool_clear(
+                self.handle,
+                path.as_ref().map(|p| p.as_ptr()).unwrap_or(ptr::null()),
+                policy.as_ptr(),
+            )
+        }, self.libzfs);
+        Ok(())
+    }
+
+    /// Get the pool's hot spares, and whether each one is available.
+    pub fn spares(&self) -> Result<Vec<SpareInfo>> {
+        let config = self.config();
+        let spares = config.lookup_nvlist("vdev_tree")
+            .and_then(|tree| tree.lookup_nvlist_array("spares"))
+            .unwrap_or_default();
+        Ok(spares.into_iter()
+            .map(|spare| {
+                let vdev = Vdev::from_config(spare, VdevClass::Spare);
+                // vdev_stat_t starts with: timestamp, state, aux
+                let aux = spare.lookup_u64_array("vdev_stats")
+                    .and_then(|stats| stats.get(2).copied());
+                let status = if aux == Some(sys::vdev_aux::VDEV_AUX_SPARED as u64) {
+                    SpareStatus::InUse
+                } else if vdev.state == VdevState::Online {
+                    SpareStatus::Available
+                } else {
+                    SpareStatus::Unavailable(vdev.state)
+                };
+                SpareInfo { vdev, status }
+            })
+            .collect())
+    }
+
+    /// Get the pool's cache (L2ARC) devices.
+    pub fn cache_devices(&self) -> Result<Vec<Vdev>> {
+        Ok(self.vdev_tree()?
+            .children
+            .into_iter()
+            .filter(|vdev| vdev.class == VdevClass::Cache)
+            .collect())
+    }
+
+    /// Add a hot spare device (like `zpool add pool spare device`). The same spare can be added to
+    /// multiple pools.
+    pub fn add_spare(&self, path: &Path) -> Result<()> {
+        let spec = VdevSpec::new().spare(VdevDevice::from_path(path)?);
+        self.add_vdevs(&spec, AddOpts::default()).map(|_| ())
+    }
+
+    /// Add a cache (L2ARC) device (like `zpool add pool cache device`).
+    pub fn add_cache(&self, path: &Path) -> Result<()> {
+        let spec = VdevSpec::new().cache(VdevDevice::from_path(path)?);
+        self.add_vdevs(&spec, AddOpts::default()).map(|_| ())
+    }
+
+    /// Remove a hot spare. Fails with `EZFS_NODEVICE` if `dev` isn't one of the pool's spares,
+    /// and with `EZFS_BUSY` if the spare is in use.
+    pub fn remove_spare(&self, dev: &VdevId) -> Result<()> {
+        let spares = self.spares()?;
+        match spares.iter().find(|spare| dev.matches(&spare.vdev)) {
+            Some(spare) if spare.status == SpareStatus::InUse => Err(Error::Zfs(ZfsError::new(
+                sys::zfs_error::EZFS_BUSY,
+                "hot spare is currently in use",
+            ))),
+            Some(_) => self.remove_vdev(dev),
+            None => Err(not_found("hot spare")),
+        }
+    }
+
+    /// Remove a cache device. Fails with `EZFS_NODEVICE` if `dev` isn't one of the pool's cache
+    /// devices.
+    pub fn remove_cache(&self, dev: &VdevId) -> Result<()> {
+        if self.cache_devices()?.iter().any(|vdev| dev.matches(vdev)) {
+            self.remove_vdev(dev)
+        } else {
+            Err(not_found("cache device"))
+        }
+    }
+
+    /// Remove a device from the pool (like `zpool remove`).
+    ///
+    /// Hot spares, cache devices and log devices are removed right away. Top-level data vdevs
+    /// (single devices and mirrors, in pools without raidz vdevs) have their data copied to the
+    /// other vdevs first, which continues in the background; see [`ZPool::remove_status`].
+    /// Devices which can't be removed, like members of a raidz vdev, fail with the error libzfs
+    /// reports for them, such as `EZFS_INVALCONFIG` or `EZFS_NOTSUP`. Pools lacking the
+    /// `device_removal` feature fail with `EZFS_BADVERSION`.
+    pub fn remove_vdev(&self, dev: &VdevId) -> Result<()> {
+        let path = dev.to_safe_string()?;
+        ztry!(unsafe { sys::zpool_vdev_remove(self.handle, path.as_ptr()) }, self.libzfs);
+        Ok(())
+    }
+
+    /// Stop the removal of a top-level vdev which is in progress (like `zpool remove -s`).
+    pub fn cancel_remove(&self) -> Result<()> {
+        ztry!(unsafe { sys::zpool_vdev_remove_cancel(self.handle) }, self.libzfs);
+        Ok(())
+    }
+
+    /// Get the progress of the current removal of a top-level vdev, or the results of the last
+    /// one, or `None` if no top-level vdev has ever been removed from this pool.
+    ///
+    /// This refreshes the pool's statistics first, so the numbers are current.
+    pub fn remove_status(&self) -> Result<Option<RemovalStats>> {
+        let mut missing: sys::boolean_t = 0;
+        ztry!(unsafe { sys::zpool_refresh_stats(self.handle, &mut missing) }, self.libzfs);
+
+        let stats = self.config()
+            .lookup_nvlist("vdev_tree")
+            .and_then(|tree| tree.lookup_u64_array("removal_stats"));
+        let stats = match stats {
+            Some(stats) if mem::size_of_val(stats) >= mem::size_of::<sys::pool_removal_stat_t>() => {
+                // The array is the pool_removal_stat_t struct itself, which is all u64s.
+                unsafe { &*(stats.as_ptr() as *const sys::pool_removal_stat_t) }
+            }
+            _ => return Ok(None),
+        };
+        let state = match ScanState::from_raw(stats.prs_state) {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+
+        Ok(Some(RemovalStats {
+            state,
+            removing_vdev: stats.prs_removing_vdev,
+            start_time: stats.prs_start_time,
+            end_time: Some(stats.prs_end_time).filter(|_| state != ScanState::Scanning),
+            to_copy: stats.prs_to_copy,
+            copied: stats.prs_copied,
+            mapping_memory: stats.prs_mapping_memory,
+        }))
+    }
+
+    fn attach(&self, old: &VdevId, new_device: &Path, opts: AttachOpts, replacing: bool)
+        -> Result<()>
+    {
+        let old = old.to_safe_string()?;
+        let new = SafeString::from_path(new_device)?;
+        // libzfs takes the new device as a vdev tree with just that one device.
+        let nvroot = VdevSpec::new()
+            .data(VdevGroup::Single(VdevDevice::from_path(new_device)?))
+            .to_nvlist()?;
+        ztry!(unsafe {
+            sys::zpool_vdev_attach(
+                self.handle,
+                old.as_ptr(),
+                new.as_ptr(),
+                nvroot.as_ptr(),
+                replacing as c_int,
+                opts.rebuild as sys::boolean_t,
+            )
+        }, self.libzfs);
+        Ok(())
+    }
+}
+
+impl LibZfs {
+    /// Reopen the devices of every imported pool, as with [`ZPool::reopen`].
+    ///
+    /// All pools are tried even if some fail; the first failure is returned.
+    pub fn reopen_all_pools(&self, scrub_restart: bool) -> Result<()> {
+        let mut result = Ok(());
+        for pool in self.get_zpools()? {
+            let r = pool.reopen(scrub_restart);
+            if result.is_ok() {
+                result = r;
+            }
+        }
+        result
+    }
+}
+
+/// Devices to act on with a libzfs_core call which takes a list of vdevs and reports errors for
+/// each one, like TRIM and initialize.
+pub(crate) struct VdevList {
+    nvl: OwnedNvList,
+    // The kernel reports errors under the same names the devices were given by, so they're
+    // given by GUID, and translated back to names for the errors.
+    names: BTreeMap<String, SafeString>,
+}
+
+impl VdevList {
+    pub fn as_ptr(&self) -> *mut sys::nvlist_t {
+        self.nvl.as_ptr()
+    }
+
+    /// Collect the errors for each device from the `errlist` returned by the call, where they
+    /// are under `key`, translating their errno values with `translate`. If the call failed
+    /// without errors for individual devices, that's the error returned instead.
+    pub fn errors(
+        &self,
+        err: c_int,
+        errlist: *mut sys::nvlist_t,
+        key: &str,
+        translate: fn(c_int) -> ZfsError,
+    ) -> Result<BTreeMap<SafeString, ZfsError>> {
+        let errlist = if errlist.is_null() {
+            None
+        } else {
+            Some(unsafe { OwnedNvList::from_raw(errlist) })
+        };
+        if err == 0 {
+            return Ok(BTreeMap::new());
+        }
+        let vdev_errors = match errlist.as_ref().and_then(|l| l.borrow().lookup_nvlist(key)) {
+            Some(vdev_errors) => vdev_errors,
+            None => return Err(Error::Sys(io::Error::from_raw_os_error(err))),
+        };
+        Ok(vdev_errors.names()
+            .map(|key| {
+                let e = translate(vdev_errors.lookup_i64(key.as_ref()).unwrap_or(0) as c_int);
+                let name = self.names.get(AsRef::<str>::as_ref(&key)).cloned().unwrap_or(key);
+                (name, e)
+            })
+            .collect())
+    }
+}
+
+impl ZPool {
+    /// Look up the given devices, or by default all leaf devices except cache devices and
+    /// spares, which are the ones `zpool trim` and `zpool initialize` act on.
+    pub(crate) fn vdev_list(&self, ids: Option<&[VdevId]>) -> Result<VdevList> {
+        let tree = self.vdev_tree()?;
+        let vdevs: Vec<&Vdev> = match ids {
+            Some(ids) => ids.iter()
+                .map(|id| tree.find(id).ok_or_else(|| not_found("device")))
+                .collect::<Result<_>>()?,
+            None => tree.leaves()
+                .filter(|vdev| !matches!(vdev.class, VdevClass::Cache | VdevClass::Spare))
+                .filter(|vdev| !matches!(
+                    vdev.vdev_type,
+                    VdevType::Hole | VdevType::Missing | VdevType::Indirect))
+                .collect(),
+        };
+
+        let mut nvl = OwnedNvList::new();
+        let mut names = BTreeMap::new();
+        for vdev in vdevs {
+            let key = vdev.guid.to_string();
+            nvl.add_u64(&key, vdev.guid);
+            names.insert(key, vdev.name());
+        }
+        Ok(VdevList { nvl, names })
+    }
+}
+
+fn not_found(what: &str) -> Error {
+    Error::Zfs(ZfsError::new(sys::zfs_error::EZFS_NODEVICE, format!("no such {} in pool", what)))
+}
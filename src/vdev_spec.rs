@@ -0,0 +1,372 @@
+use libzfs_sys as sys;
+
+use std::collections::BTreeSet;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+
+use crate::nvlist::OwnedNvList;
+use crate::{Error, Result, ZfsError};
+
+/// A leaf device to build a vdev out of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VdevDevice {
+    /// A disk or partition, given by its absolute path (e.g. `/dev/disk/by-id/...`). It is used
+    /// as-is: no partitioning or labeling is done.
+    Disk(PathBuf),
+    /// A regular file, given by its absolute path. It must already exist and be at least 64 MiB.
+    File(PathBuf),
+}
+
+impl VdevDevice {
+    /// Describe the device at the given path: a [`VdevDevice::Disk`] if it's a block device, or
+    /// a [`VdevDevice::File`] otherwise.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let metadata = std::fs::metadata(path).map_err(Error::Sys)?;
+        if metadata.file_type().is_block_device() {
+            Ok(VdevDevice::Disk(path.to_owned()))
+        } else {
+            Ok(VdevDevice::File(path.to_owned()))
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            VdevDevice::Disk(p) | VdevDevice::File(p) => p,
+        }
+    }
+
+    fn to_nvlist(&self) -> Result<OwnedNvList> {
+        let path = self.path().to_str()
+            .filter(|_| self.path().is_absolute())
+            .ok_or_else(|| invalid_spec(
+                format!("device path {:?} must be an absolute UTF-8 path", self.path())))?;
+        let mut nvl = OwnedNvList::new();
+        match self {
+            VdevDevice::Disk(_) => {
+                nvl.add_string("type", "disk");
+                nvl.add_u64("whole_disk", 0);
+            }
+            VdevDevice::File(_) => nvl.add_string("type", "file"),
+        }
+        nvl.add_string("path", path);
+        Ok(nvl)
+    }
+}
+
+/// A top-level vdev: a single device, or a group of devices with redundancy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VdevGroup {
+    Single(VdevDevice),
+    Mirror(Vec<VdevDevice>),
+    RaidZ1(Vec<VdevDevice>),
+    RaidZ2(Vec<VdevDevice>),
+    RaidZ3(Vec<VdevDevice>),
+}
+
+impl VdevGroup {
+    pub fn devices(&self) -> &[VdevDevice] {
+        match self {
+            VdevGroup::Single(dev) => std::slice::from_ref(dev),
+            VdevGroup::Mirror(devs)
+                | VdevGroup::RaidZ1(devs)
+                | VdevGroup::RaidZ2(devs)
+                | VdevGroup::RaidZ3(devs) => devs,
+        }
+    }
+
+    /// Description of the redundancy, in the same terms the CLI uses when complaining about
+    /// mismatched replication levels.
+    fn redundancy(&self) -> String {
+        match self {
+            VdevGroup::Single(_) => "disk".to_owned(),
+            VdevGroup::Mirror(devs) => format!("{}-way mirror", devs.len()),
+            VdevGroup::RaidZ1(devs) => format!("{}-wide raidz1", devs.len()),
+            VdevGroup::RaidZ2(devs) => format!("{}-wide raidz2", devs.len()),
+            VdevGroup::RaidZ3(devs) => format!("{}-wide raidz3", devs.len()),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let (min, what) = match self {
+            VdevGroup::Single(_) => return Ok(()),
+            VdevGroup::Mirror(_) => (2, "mirror"),
+            VdevGroup::RaidZ1(_) => (2, "raidz1"),
+            VdevGroup::RaidZ2(_) => (3, "raidz2"),
+            VdevGroup::RaidZ3(_) => (4, "raidz3"),
+        };
+        if self.devices().len() < min {
+            return Err(invalid_spec(format!("{} requires at least {} devices", what, min)));
+        }
+        Ok(())
+    }
+
+    fn to_nvlist(&self, bias: Option<&str>) -> Result<OwnedNvList> {
+        let mut nvl = match self {
+            VdevGroup::Single(dev) => dev.to_nvlist()?,
+            VdevGroup::Mirror(devs) => group_nvlist("mirror", None, devs)?,
+            VdevGroup::RaidZ1(devs) => group_nvlist("raidz", Some(1), devs)?,
+            VdevGroup::RaidZ2(devs) => group_nvlist("raidz", Some(2), devs)?,
+            VdevGroup::RaidZ3(devs) => group_nvlist("raidz", Some(3), devs)?,
+        };
+        if let Some(bias) = bias {
+            nvl.add_u64("is_log", (bias == "log") as u64);
+            nvl.add_string("alloc_bias", bias);
+        }
+        Ok(nvl)
+    }
+}
+
+fn group_nvlist(typ: &str, nparity: Option<u64>, devs: &[VdevDevice]) -> Result<OwnedNvList> {
+    let children = devs.iter().map(VdevDevice::to_nvlist).collect::<Result<Vec<_>>>()?;
+    let mut nvl = OwnedNvList::new();
+    nvl.add_string("type", typ);
+    if let Some(nparity) = nparity {
+        nvl.add_u64("nparity", nparity);
+    }
+    nvl.add_nvlist_array("children", &children);
+    Ok(nvl)
+}
+
+/// A specification of the vdevs making up a pool, or to be added to one.
+///
+/// ```no_run
+/// # use libzfs::{VdevSpec, VdevGroup, VdevDevice};
+/// let spec = VdevSpec::new()
+///     .data(VdevGroup::Mirror(vec![
+///         VdevDevice::Disk("/dev/sda".into()),
+///         VdevDevice::Disk("/dev/sdb".into()),
+///     ]))
+///     .log(VdevGroup::Single(VdevDevice::Disk("/dev/nvme0n1p1".into())))
+///     .cache(VdevDevice::Disk("/dev/nvme0n1p2".into()));
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct VdevSpec {
+    data: Vec<VdevGroup>,
+    log: Vec<VdevGroup>,
+    special: Vec<VdevGroup>,
+    dedup: Vec<VdevGroup>,
+    cache: Vec<VdevDevice>,
+    spares: Vec<VdevDevice>,
+}
+
+impl VdevSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a top-level data vdev.
+    pub fn data(mut self, vdev: VdevGroup) -> Self {
+        self.data.push(vdev);
+        self
+    }
+
+    /// Add a separate intent log (SLOG) vdev. Logs can't be raidz.
+    pub fn log(mut self, vdev: VdevGroup) -> Self {
+        self.log.push(vdev);
+        self
+    }
+
+    /// Add a vdev to the special allocation class, for metadata and small blocks.
+    pub fn special(mut self, vdev: VdevGroup) -> Self {
+        self.special.push(vdev);
+        self
+    }
+
+    /// Add a vdev to the dedup allocation class, for dedup tables.
+    pub fn dedup(mut self, vdev: VdevGroup) -> Self {
+        self.dedup.push(vdev);
+        self
+    }
+
+    /// Add an L2ARC cache device.
+    pub fn cache(mut self, dev: VdevDevice) -> Self {
+        self.cache.push(dev);
+        self
+    }
+
+    /// Add a hot spare device.
+    pub fn spare(mut self, dev: VdevDevice) -> Self {
+        self.spares.push(dev);
+        self
+    }
+
+    pub(crate) fn has_data_vdevs(&self) -> bool {
+        !self.data.is_empty()
+    }
+
+    /// The redundancy of each data, special and dedup vdev.
+    pub(crate) fn redundancies(&self) -> impl Iterator<Item = String> + '_ {
+        self.data.iter().chain(&self.special).chain(&self.dedup).map(VdevGroup::redundancy)
+    }
+
+    fn all_devices(&self) -> impl Iterator<Item = &VdevDevice> {
+        self.data.iter()
+            .chain(&self.log)
+            .chain(&self.special)
+            .chain(&self.dedup)
+            .flat_map(VdevGroup::devices)
+            .chain(&self.cache)
+            .chain(&self.spares)
+    }
+
+    /// Check the spec for mistakes that libzfs would reject, or that the `zpool` command would
+    /// refuse without `-f`.
+    ///
+    /// All data vdevs (and special and dedup class vdevs) are required to have the same
+    /// redundancy unless `force` is set. Duplicate devices are never allowed.
+    pub(crate) fn validate(&self, force: bool) -> Result<()> {
+        let mut seen = BTreeSet::new();
+        for dev in self.all_devices() {
+            if !seen.insert(dev.path()) {
+                return Err(invalid_spec(format!("{:?} is specified more than once", dev.path())));
+            }
+        }
+
+        for vdev in self.data.iter().chain(&self.log).chain(&self.special).chain(&self.dedup) {
+            vdev.validate()?;
+        }
+
+        if self.log.iter().any(|vdev| !matches!(vdev, VdevGroup::Single(_) | VdevGroup::Mirror(_))) {
+            return Err(invalid_spec("log devices can only be single devices or mirrors".to_owned()));
+        }
+
+        if !force {
+            let mut groups = self.data.iter().chain(&self.special).chain(&self.dedup);
+            if let Some(first) = groups.next() {
+                let expected = first.redundancy();
+                if let Some(other) = groups.find(|vdev| vdev.redundancy() != expected) {
+                    return Err(invalid_spec(format!(
+                        "mismatched replication level: both {} and {} vdevs are present",
+                        expected, other.redundancy())));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Build the nvlist of vdevs, rooted at a "root" vdev, as `zpool_create` and `zpool_add`
+    /// expect.
+    pub(crate) fn to_nvlist(&self) -> Result<OwnedNvList> {
+        let mut children = vec![];
+        for vdev in &self.data {
+            children.push(vdev.to_nvlist(None)?);
+        }
+        for vdev in &self.log {
+            children.push(vdev.to_nvlist(Some("log"))?);
+        }
+        for vdev in &self.special {
+            children.push(vdev.to_nvlist(Some("special"))?);
+        }
+        for vdev in &self.dedup {
+            children.push(vdev.to_nvlist(Some("dedup"))?);
+        }
+
+        let mut root = OwnedNvList::new();
+        root.add_string("type", "root");
+        root.add_nvlist_array("children", &children);
+        if !self.cache.is_empty() {
+            let cache = self.cache.iter().map(VdevDevice::to_nvlist).collect::<Result<Vec<_>>>()?;
+            root.add_nvlist_array("l2cache", &cache);
+        }
+        if !self.spares.is_empty() {
+            let spares = self.spares.iter().map(VdevDevice::to_nvlist).collect::<Result<Vec<_>>>()?;
+            root.add_nvlist_array("spares", &spares);
+        }
+        Ok(root)
+    }
+}
+
+fn invalid_spec(msg: String) -> Error {
+    Error::Zfs(ZfsError::new(
+        sys::zfs_error::EZFS_INVALCONFIG,
+        format!("invalid vdev specification: {}", msg),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disk(n: u32) -> VdevDevice {
+        VdevDevice::Disk(PathBuf::from(format!("/dev/sd{}", n)))
+    }
+
+    fn disks(range: std::ops::Range<u32>) -> Vec<VdevDevice> {
+        range.map(disk).collect()
+    }
+
+    fn assert_invalid(result: Result<()>, expected: &str) {
+        match result {
+            Err(Error::Zfs(e)) => {
+                assert_eq!(e.code, sys::zfs_error::EZFS_INVALCONFIG);
+                assert!(e.msg.contains(expected), "{:?} doesn't mention {:?}", e.msg, expected);
+            }
+            other => panic!("expected an invalid spec error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn valid_specs() {
+        let spec = VdevSpec::new()
+            .data(VdevGroup::Mirror(disks(0..2)))
+            .data(VdevGroup::Mirror(disks(2..4)))
+            .log(VdevGroup::Mirror(disks(4..6)))
+            .special(VdevGroup::Mirror(disks(6..8)))
+            .cache(disk(8))
+            .spare(disk(9));
+        spec.validate(false).unwrap();
+
+        VdevSpec::new().data(VdevGroup::RaidZ3(disks(0..4))).validate(false).unwrap();
+    }
+
+    #[test]
+    fn duplicate_devices() {
+        let spec = VdevSpec::new()
+            .data(VdevGroup::Mirror(disks(0..2)))
+            .spare(disk(1));
+        assert_invalid(spec.validate(true), "more than once");
+    }
+
+    #[test]
+    fn too_few_devices() {
+        let cases = [
+            (VdevGroup::Mirror(disks(0..1)), "mirror requires at least 2"),
+            (VdevGroup::RaidZ1(disks(0..1)), "raidz1 requires at least 2"),
+            (VdevGroup::RaidZ2(disks(0..2)), "raidz2 requires at least 3"),
+            (VdevGroup::RaidZ3(disks(0..3)), "raidz3 requires at least 4"),
+        ];
+        for (group, expected) in cases {
+            assert_invalid(VdevSpec::new().data(group).validate(true), expected);
+        }
+    }
+
+    #[test]
+    fn raidz_log() {
+        let spec = VdevSpec::new()
+            .data(VdevGroup::Single(disk(0)))
+            .log(VdevGroup::RaidZ1(disks(1..4)));
+        assert_invalid(spec.validate(true), "log devices can only be");
+    }
+
+    #[test]
+    fn mixed_redundancy() {
+        let spec = VdevSpec::new()
+            .data(VdevGroup::Mirror(disks(0..2)))
+            .data(VdevGroup::RaidZ1(disks(2..5)));
+        assert_invalid(spec.validate(false), "both 2-way mirror and 3-wide raidz1");
+        spec.validate(true).unwrap();
+
+        let spec = VdevSpec::new()
+            .data(VdevGroup::Mirror(disks(0..2)))
+            .special(VdevGroup::Single(disk(2)));
+        assert_invalid(spec.validate(false), "mismatched replication level");
+        spec.validate(true).unwrap();
+
+        // Logs don't count.
+        let spec = VdevSpec::new()
+            .data(VdevGroup::Mirror(disks(0..2)))
+            .log(VdevGroup::Single(disk(2)));
+        spec.validate(false).unwrap();
+    }
+}
@@ -0,0 +1,61 @@
+use libzfs_sys as sys;
+
+use std::ffi::CStr;
+use std::path::PathBuf;
+
+use crate::{Dataset, DatasetType, Property, Result};
+
+/// A ZFS volume (zvol): a dataset which appears as a block device instead of being mounted.
+///
+/// Get one from a [`Dataset`] with `Volume::try_from`, which gives the dataset back if it isn't a
+/// volume.
+#[derive(Debug, Clone)]
+pub struct Volume(Dataset);
+
+impl TryFrom<Dataset> for Volume {
+    type Error = Dataset;
+
+    fn try_from(dataset: Dataset) -> std::result::Result<Self, Dataset> {
+        if dataset.get_type() == DatasetType::Volume {
+            Ok(Volume(dataset))
+        } else {
+            Err(dataset)
+        }
+    }
+}
+
+impl Volume {
+    pub fn as_dataset(&self) -> &Dataset {
+        &self.0
+    }
+
+    pub fn into_dataset(self) -> Dataset {
+        self.0
+    }
+
+    /// Get the `volsize` property: the size of the volume, in bytes.
+    pub fn get_volsize(&self) -> Result<Property<u64>> {
+        self.0.get_numeric_property("volsize")
+    }
+
+    /// Resize the volume. The size must be a multiple of the volume's block size, and shrinking
+    /// a volume discards the data past the new end.
+    pub fn set_volsize(&self, size: u64) -> Result<()> {
+        self.0.set_property("volsize", &size.to_string())
+    }
+
+    /// Get the `volblocksize` property: the block size of the volume, in bytes. This can only be
+    /// set when the volume is created.
+    pub fn get_volblocksize(&self) -> Result<Property<u64>> {
+        self.0.get_numeric_property("volblocksize")
+    }
+
+    /// The path of the volume's block device, like `/dev/zvol/pool/volume`.
+    ///
+    /// The device node is created asynchronously by udev, so it may not exist yet right after the
+    /// volume is created, and it isn't created at all if the `volmode` property is `none`.
+    pub fn device_path(&self) -> PathBuf {
+        let dir = CStr::from_bytes_with_nul(sys::ZVOL_DIR).expect("bad ZVOL_DIR");
+        PathBuf::from(dir.to_string_lossy().into_owned()).join(String::from(self.0.get_name()))
+    }
+}
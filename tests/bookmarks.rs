@@ -0,0 +1,58 @@
+//! Bookmarks on a scratch pool. See `common` for how to run these.
+
+mod common;
+
+use std::fs::File;
+
+use libzfs::{Error, IncrementalSource, ReceiveOpts, ZfsErrorCode, ZfsSendFlags};
+
+use common::TestPool;
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn incremental_send_from_bookmark() {
+    let tp = TestPool::new("bookmarksend");
+    let copy = tp.full_name("copy");
+
+    // Send the first snapshot, and keep only a bookmark of it.
+    let first = tp.zfs.snapshot(&tp.full_name("@first"), false).unwrap();
+    let mut stream = vec![];
+    first.send_to_writer(&mut stream, ZfsSendFlags(0)).unwrap();
+    tp.zfs.receive_from_reader(&copy, &mut &stream[..], ReceiveOpts::default()).unwrap();
+    let bookmark = first.bookmark("first").unwrap();
+    assert_eq!(bookmark.get_name(), tp.full_name("#first"));
+    tp.zfs.destroy_snapshots([tp.full_name("@first")].iter()).unwrap();
+    assert!(!tp.exists("@first"));
+
+    // The bookmark is enough to send the next snapshot incrementally.
+    let second = tp.zfs.snapshot(&tp.full_name("@second"), false).unwrap();
+    let path = tp.dir.join("incremental");
+    let from = IncrementalSource::Since(tp.full_name("#first"));
+    let file = File::create(&path).unwrap();
+    second.send_incremental(from, file.into(), ZfsSendFlags(0)).unwrap().wait().unwrap();
+    let mut stream = File::open(&path).unwrap();
+    tp.zfs.receive_from_reader(&copy, &mut stream, ReceiveOpts::default()).unwrap();
+    assert!(tp.exists("copy@second"));
+}
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn destroy_bookmarks_in_a_batch() {
+    let tp = TestPool::new("bookmarks");
+    let snap = tp.zfs.snapshot(&tp.full_name("@snap"), false).unwrap();
+    for name in ["a", "b", "c"] {
+        snap.bookmark(name).unwrap();
+    }
+
+    let missing = tp.full_name("#missing");
+    let names = [tp.full_name("#a"), tp.full_name("#b"), missing.clone()];
+    let errors = tp.zfs.destroy_bookmarks(&names).unwrap();
+    assert_eq!(errors.len(), 1);
+    match &errors[&missing] {
+        Error::Zfs(e) => assert_eq!(e.code, ZfsErrorCode::EZFS_NOENT),
+        other => panic!("expected EZFS_NOENT, got {:?}", other),
+    }
+    assert!(!tp.exists("#a"));
+    assert!(!tp.exists("#b"));
+    assert!(tp.exists("#c"));
+}
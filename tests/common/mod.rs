@@ -0,0 +1,133 @@
+//! Scratch pools backed by sparse files, for tests which need a real pool.
+//!
+//! These need root and the ZFS kernel module, so the tests using them are ignored by default.
+//! Run them with `cargo test -- --ignored --test-threads=1`.
+
+#![allow(dead_code)]
+
+use std::fs::{self, File};
+use std::os::fd::{FromRawFd, OwnedFd};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use libzfs::{
+    Dataset, DatasetType, DatasetTypeMask, LibZfs, PoolCreateOpts, SafeString, VdevDevice,
+    VdevGroup, VdevSpec, ZPool,
+};
+
+/// Files smaller than this can't be vdevs.
+const VDEV_SIZE: u64 = 128 << 20;
+
+/// A pool made just for one test, under a directory of its own in the system's temporary
+/// directory, which is destroyed along with the directory when dropped.
+pub struct TestPool {
+    pub zfs: LibZfs,
+    pub name: SafeString,
+    /// Holds the backing files, and the pool's altroot, `mnt`.
+    pub dir: PathBuf,
+    pub files: Vec<PathBuf>,
+}
+
+impl TestPool {
+    /// A pool on a single file.
+    pub fn new(test: &str) -> TestPool {
+        TestPool::create(test, 1)
+    }
+
+    /// A pool on a two-way mirror of files.
+    pub fn mirror(test: &str) -> TestPool {
+        TestPool::create(test, 2)
+    }
+
+    fn create(test: &str, count: usize) -> TestPool {
+        let zfs = LibZfs::new().expect("libzfs_init failed; these tests need root");
+        let name = format!("libzfs_rs_{}_{}", test, process::id());
+        let dir = std::env::temp_dir().join(&name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("mnt")).unwrap();
+
+        let files: Vec<PathBuf> = (0..count)
+            .map(|i| {
+                let path = dir.join(format!("vdev{}", i));
+                File::create(&path).unwrap().set_len(VDEV_SIZE).unwrap();
+                path
+            })
+            .collect();
+        let mut devices: Vec<VdevDevice> = files.iter().cloned().map(VdevDevice::File).collect();
+        let group = if devices.len() == 1 {
+            VdevGroup::Single(devices.remove(0))
+        } else {
+            VdevGroup::Mirror(devices)
+        };
+
+        let opts = PoolCreateOpts { altroot: Some(dir.join("mnt")), ..Default::default() };
+        let name = SafeString::from(name);
+        zfs.create_pool(&name, &VdevSpec::new().data(group), opts).unwrap();
+        TestPool { zfs, name, dir, files }
+    }
+
+    pub fn pool(&self) -> ZPool {
+        self.zfs.pool_by_name(&self.name).unwrap()
+    }
+
+    /// The full name of a dataset in the pool, given relative to it like
+    /// [`ZPool::dataset_by_name`] takes: `a/b`, `@snap`, or empty for the root filesystem.
+    pub fn full_name(&self, relative: &str) -> SafeString {
+        if relative.is_empty() || relative.starts_with(['@', '#']) {
+            SafeString::from(format!("{}{}", self.name, relative))
+        } else {
+            SafeString::from(format!("{}/{}", self.name, relative))
+        }
+    }
+
+    pub fn dataset(&self, relative: &str) -> Dataset {
+        self.zfs.dataset_by_name(&self.full_name(relative), DatasetTypeMask::all()).unwrap()
+    }
+
+    pub fn root(&self) -> Dataset {
+        self.zfs.dataset_by_name(&self.name, DatasetType::Filesystem.into()).unwrap()
+    }
+
+    /// Whether a dataset exists, given relative to the pool.
+    pub fn exists(&self, relative: &str) -> bool {
+        self.zfs.dataset_by_name(&self.full_name(relative), DatasetTypeMask::all()).is_ok()
+    }
+
+    pub fn altroot(&self) -> PathBuf {
+        self.dir.join("mnt")
+    }
+}
+
+impl Drop for TestPool {
+    fn drop(&mut self) {
+        if let Ok(pool) = self.zfs.pool_by_name(&self.name) {
+            if let Err(e) = pool.destroy(true) {
+                eprintln!("couldn't destroy test pool {}: {}", self.name, e);
+                return;
+            }
+        }
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Write `len` bytes which don't compress, so streams of them are about as large.
+pub fn write_data(path: &Path, len: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let data: Vec<u8> = (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    fs::write(path, &data).unwrap();
+    data
+}
+
+/// Make a pipe, returning its read and write ends.
+pub fn pipe() -> (OwnedFd, OwnedFd) {
+    let mut fds = [0; 2];
+    assert_eq!(0, unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) });
+    unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) }
+}
@@ -0,0 +1,51 @@
+//! `zfs diff` on a scratch pool. See `common` for how to run these.
+
+mod common;
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use libzfs::{DiffChange, DiffOpts, MountOpts};
+
+use common::TestPool;
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn diff_between_snapshots() {
+    let tp = TestPool::new("diff");
+    let root = tp.root();
+    root.mount(&MountOpts::new()).unwrap();
+    let dir = root.mount_path().unwrap();
+    fs::write(dir.join("modified"), b"before").unwrap();
+    fs::write(dir.join("removed"), b"data").unwrap();
+    fs::write(dir.join("renamed"), b"data").unwrap();
+    let from = tp.zfs.snapshot(&tp.full_name("@from"), false).unwrap();
+
+    fs::write(dir.join("added"), b"data").unwrap();
+    fs::write(dir.join("modified"), b"after").unwrap();
+    fs::remove_file(dir.join("removed")).unwrap();
+    fs::rename(dir.join("renamed"), dir.join("new name\nwith newline")).unwrap();
+    let to = tp.zfs.snapshot(&tp.full_name("@to"), false).unwrap();
+
+    let renamed_to = dir.join("new name\nwith newline");
+    let expected: HashSet<(DiffChange, PathBuf)> = [
+        (DiffChange::Modified, dir.clone()),
+        (DiffChange::Added, dir.join("added")),
+        (DiffChange::Modified, dir.join("modified")),
+        (DiffChange::Removed, dir.join("removed")),
+        (
+            DiffChange::Renamed { from: dir.join("renamed"), to: renamed_to.clone() },
+            renamed_to,
+        ),
+    ].into_iter().collect();
+    let changes = |other| -> HashSet<_> {
+        from.diff(other, DiffOpts::default()).unwrap()
+            .into_iter()
+            .map(|entry| (entry.change, entry.path))
+            .collect()
+    };
+    assert_eq!(changes(&to), expected);
+    // Against the live filesystem, which hasn't changed since.
+    assert_eq!(changes(&root), expected);
+}
@@ -0,0 +1,18 @@
+//! These tests need root and the ZFS kernel module, so they're ignored by default. Run them with
+//! `cargo test -- --ignored`.
+
+use libzfs::{DatasetType, Error, LibZfs, SafeString, ZfsErrorCode};
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn missing_dataset_is_noent_every_time() {
+    let zfs = LibZfs::new().unwrap();
+    let name = SafeString::from("libzfs-rs-no-such-pool/no-such-dataset");
+    // The second failure must be reported the same way, not mistaken for a leftover error.
+    for _ in 0..2 {
+        match zfs.dataset_by_name(&name, DatasetType::Filesystem.into()) {
+            Err(Error::Zfs(e)) => assert_eq!(e.code, ZfsErrorCode::EZFS_NOENT),
+            other => panic!("expected EZFS_NOENT, got {:?}", other),
+        }
+    }
+}
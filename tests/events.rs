@@ -0,0 +1,46 @@
+//! Reading events on a scratch pool. See `common` for how to run these.
+
+mod common;
+
+use std::thread;
+use std::time::Duration;
+
+use libzfs::{EventOpts, ZfsEvent};
+
+use common::TestPool;
+
+fn is_pool_create(event: &ZfsEvent, guid: u64) -> bool {
+    AsRef::<str>::as_ref(&event.class) == "sysevent.fs.zfs.pool_create"
+        && event.pool_guid == Some(guid)
+}
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn queued_events() {
+    let tp = TestPool::new("events");
+    let guid = tp.pool().guid().unwrap();
+    let events = tp.zfs.events(EventOpts::default()).unwrap()
+        .collect::<libzfs::Result<Vec<_>>>()
+        .unwrap();
+    assert!(events.iter().any(|event| is_pool_create(event, guid)));
+    assert!(events.windows(2).all(|pair| pair[0].eid < pair[1].eid));
+}
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn interrupt_following() {
+    let tp = TestPool::new("eventsfollow");
+    let guid = tp.pool().guid().unwrap();
+    let opts = EventOpts { follow: true };
+    let stream = tp.zfs.events(opts).unwrap();
+
+    // Once the queued events are read, the stream waits for more until it's interrupted.
+    let interrupter = stream.interrupter();
+    let interrupting = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(500));
+        interrupter.interrupt();
+    });
+    let events = stream.collect::<libzfs::Result<Vec<_>>>().unwrap();
+    interrupting.join().unwrap();
+    assert!(events.iter().any(|event| is_pool_create(event, guid)));
+}
@@ -0,0 +1,116 @@
+//! Snapshot holds on a scratch pool. See `common` for how to run these.
+
+mod common;
+
+use std::env;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{self, Command, Stdio};
+use std::time::{Duration, SystemTime};
+
+use libzfs::{DatasetType, Error, LibZfs, SafeString, ZfsErrorCode};
+
+use common::TestPool;
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn hold_defers_destroy_until_release() {
+    let tp = TestPool::new("holddefer");
+    let name = tp.full_name("@held");
+    let snap = tp.zfs.snapshot(&name, false).unwrap();
+    snap.hold("keep", false).unwrap();
+
+    // A held snapshot can't be destroyed outright, only marked for destruction.
+    tp.zfs.destroy_snapshots([&name].iter()).unwrap_err();
+    assert!(tp.exists("@held"));
+    tp.zfs.destroy_snapshots_deferred([&name].iter()).unwrap();
+    assert!(tp.exists("@held"));
+
+    // Releasing its last hold destroys it.
+    snap.release("keep", false).unwrap();
+    assert!(!tp.exists("@held"));
+}
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn list_holds() {
+    let tp = TestPool::new("holdlist");
+    let snap = tp.zfs.snapshot(&tp.full_name("@held"), false).unwrap();
+    assert!(snap.holds().unwrap().is_empty());
+
+    let before = SystemTime::now() - Duration::from_secs(1);
+    snap.hold("first", false).unwrap();
+    snap.hold("second", false).unwrap();
+    let after = SystemTime::now() + Duration::from_secs(1);
+
+    let holds = snap.holds().unwrap();
+    let tags: Vec<&str> = holds.keys().map(AsRef::as_ref).collect();
+    assert_eq!(tags, ["first", "second"]);
+    assert!(holds.values().all(|&time| before <= time && time <= after));
+
+    match snap.hold("first", false) {
+        Err(Error::Zfs(e)) => assert_eq!(e.code, ZfsErrorCode::EZFS_REFTAG_HOLD),
+        other => panic!("expected EZFS_REFTAG_HOLD, got {:?}", other),
+    }
+    snap.release("first", false).unwrap();
+    snap.release("second", false).unwrap();
+}
+
+// Set in the child process of `temp_hold_released_on_exit` to the snapshot it should hold.
+const CHILD_SNAPSHOT_VAR: &str = "LIBZFS_RS_TEMP_HOLD_SNAPSHOT";
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn temp_hold_released_on_exit() {
+    if let Ok(name) = env::var(CHILD_SNAPSHOT_VAR) {
+        // Take the hold, and exit without dropping it once the parent has seen it, so that only
+        // the kernel closing the cleanup fd can release it.
+        let zfs = LibZfs::new().unwrap();
+        let snap = zfs.dataset_by_name(&SafeString::from(name), DatasetType::Snapshot.into())
+            .unwrap();
+        let hold = snap.hold_temporary("temp").unwrap();
+        println!("held");
+        io::stdout().flush().unwrap();
+        io::stdin().read_to_end(&mut vec![]).unwrap();
+        std::mem::forget(hold);
+        process::exit(0);
+    }
+
+    let tp = TestPool::new("holdexit");
+    let name = tp.full_name("@held");
+    let snap = tp.zfs.snapshot(&name, false).unwrap();
+
+    let mut child = Command::new(env::current_exe().unwrap())
+        .args(["temp_hold_released_on_exit", "--exact", "--include-ignored", "--nocapture"])
+        .env(CHILD_SNAPSHOT_VAR, name.as_ref() as &str)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    let held = stdout.lines().map(Result::unwrap).any(|line| line == "held");
+    assert!(held, "the child process didn't take the hold");
+    assert!(snap.holds().unwrap().contains_key(&SafeString::from("temp")));
+
+    // Closing its stdin lets it exit.
+    drop(child.stdin.take());
+    assert!(child.wait().unwrap().success());
+    assert!(snap.holds().unwrap().is_empty());
+    tp.zfs.destroy_snapshots([&name].iter()).unwrap();
+}
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn temp_hold_tag_length() {
+    let tp = TestPool::new("holdtag");
+    let snap = tp.zfs.snapshot(&tp.full_name("@held"), false).unwrap();
+
+    // The kernel prefixes temporary tags, leaving less room than for other holds.
+    match snap.hold_temporary(&"t".repeat(239)) {
+        Err(Error::Zfs(e)) => assert_eq!(e.code, ZfsErrorCode::EZFS_TAGTOOLONG),
+        other => panic!("expected EZFS_TAGTOOLONG, got {:?}", other.map(|_| ())),
+    }
+    drop(snap.hold_temporary(&"t".repeat(238)).unwrap());
+
+    snap.hold(&"t".repeat(255), false).unwrap();
+    snap.release(&"t".repeat(255), false).unwrap();
+}
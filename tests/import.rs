@@ -0,0 +1,36 @@
+//! Exporting and importing a scratch pool. See `common` for how to run these.
+
+mod common;
+
+use libzfs::{ExportOpts, ImportOpts, ImportSearch};
+
+use common::TestPool;
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn export_scan_import() {
+    let tp = TestPool::new("import");
+    let guid = tp.pool().guid().unwrap();
+    tp.pool().export(ExportOpts::default()).unwrap();
+    assert!(tp.zfs.pool_by_name(&tp.name).is_err());
+
+    let search = ImportSearch { dirs: vec![tp.dir.clone()], ..Default::default() };
+    let found = tp.zfs.find_importable_pools(&search).unwrap();
+    let entry = found.iter().find(|pool| pool.name == tp.name).expect("exported pool not found");
+    assert_eq!(entry.guid, guid);
+    assert_eq!(entry.health.as_ref() as &str, "ONLINE");
+
+    let opts = ImportOpts {
+        altroot: Some(tp.altroot()),
+        no_mount: true,
+        ..Default::default()
+    };
+    let pool = tp.zfs.import(entry, opts).unwrap();
+    assert_eq!(pool.get_name(), tp.name);
+    assert_eq!(pool.guid().unwrap(), guid);
+
+    let root = tp.root();
+    assert!(!root.is_mounted());
+    let mountpoint = root.get_property("mountpoint").unwrap().value;
+    assert!(AsRef::<str>::as_ref(&mountpoint).starts_with(tp.altroot().to_str().unwrap()));
+}
@@ -0,0 +1,57 @@
+//! Mounting a scratch pool's filesystems. See `common` for how to run these.
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use libzfs::{DatasetType, DatasetTypeMask, MountOpts, PropSource};
+
+use common::TestPool;
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn readonly_mount_is_temporary() {
+    let tp = TestPool::new("mountro");
+    let root = tp.root();
+    root.mount(&MountOpts::new().readonly(true)).unwrap();
+    let path = root.mount_path().unwrap().join("file");
+    let e = fs::write(&path, b"data").unwrap_err();
+    assert_eq!(e.raw_os_error(), Some(libc::EROFS));
+
+    let readonly = tp.root().get_readonly().unwrap();
+    assert!(readonly.value);
+    assert_eq!(readonly.source, PropSource::Temporary);
+
+    root.unmount(false).unwrap();
+    let readonly = tp.root().get_readonly().unwrap();
+    assert!(!readonly.value);
+    assert_ne!(readonly.source, PropSource::Temporary);
+
+    root.mount(&MountOpts::new()).unwrap();
+    fs::write(&path, b"data").unwrap();
+}
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn dataset_for_path() {
+    let tp = TestPool::new("forpath");
+    let root = tp.root();
+    root.mount(&MountOpts::new()).unwrap();
+    let dir = root.mount_path().unwrap();
+    fs::write(dir.join("file"), b"data").unwrap();
+    tp.zfs.snapshot(&tp.full_name("@snap"), false).unwrap();
+
+    let found = tp.zfs.dataset_for_path(&dir.join("file"), DatasetTypeMask::all()).unwrap();
+    assert_eq!(found.unwrap().get_name(), tp.name);
+
+    assert!(tp.zfs.dataset_for_path(Path::new("/proc/self"), DatasetTypeMask::all())
+        .unwrap()
+        .is_none());
+
+    let in_snapshot = dir.join(".zfs/snapshot/snap/file");
+    let found = tp.zfs.dataset_for_path(&in_snapshot, DatasetTypeMask::all()).unwrap();
+    assert_eq!(found.unwrap().get_name(), tp.full_name("@snap"));
+    let found = tp.zfs.dataset_for_path(&in_snapshot, DatasetType::Filesystem.into()).unwrap();
+    assert_eq!(found.unwrap().get_name(), tp.name);
+}
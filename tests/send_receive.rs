@@ -0,0 +1,105 @@
+//! Sending and receiving on a scratch pool. See `common` for how to run these.
+
+mod common;
+
+use std::fs::{self, File};
+
+use libzfs::{Error, MountOpts, ReceiveOpts, ZfsSendFlags};
+
+use common::{pipe, write_data, TestPool};
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn send_receive_round_trip() {
+    let tp = TestPool::new("sendrecv");
+    let root = tp.root();
+    root.mount(&MountOpts::new()).unwrap();
+    let data = write_data(&root.mount_path().unwrap().join("data"), 1 << 20);
+    let snap = tp.zfs.snapshot(&tp.full_name("@a"), false).unwrap();
+
+    // Send on another thread, and receive on this one, through a pipe.
+    let (read_fd, write_fd) = pipe();
+    let send = snap.send_builder().send(write_fd).unwrap();
+    tp.zfs.receive(&tp.full_name("copy"), read_fd, ReceiveOpts::default()).unwrap();
+    send.wait().unwrap();
+
+    assert!(tp.exists("copy@a"));
+    let copy = tp.dataset("copy");
+    if !copy.is_mounted() {
+        copy.mount(&MountOpts::new()).unwrap();
+    }
+    assert_eq!(fs::read(copy.mount_path().unwrap().join("data")).unwrap(), data);
+}
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn interrupted_receive_resumes() {
+    let tp = TestPool::new("resume");
+    let root = tp.root();
+    root.mount(&MountOpts::new()).unwrap();
+    let data = write_data(&root.mount_path().unwrap().join("data"), 8 << 20);
+    let snap = tp.zfs.snapshot(&tp.full_name("@a"), false).unwrap();
+
+    let mut stream = vec![];
+    snap.send_to_writer(&mut stream, ZfsSendFlags(0)).unwrap();
+
+    // Cut the stream off halfway, as if the connection dropped.
+    let target = tp.full_name("copy");
+    let resumable = ReceiveOpts { resumable: true, ..Default::default() };
+    let mut half = &stream[..stream.len() / 2];
+    tp.zfs.receive_from_reader(&target, &mut half, resumable.clone()).unwrap_err();
+
+    let partial = tp.dataset("copy");
+    assert!(partial.has_partial_receive().unwrap());
+    let token = partial.receive_resume_token().unwrap().unwrap();
+    let token = tp.zfs.parse_resume_token(token.as_ref()).unwrap();
+    assert!(token.bytes > 0);
+
+    // Only the rest of the stream can be received into it now.
+    let force = ReceiveOpts { force: true, ..Default::default() };
+    match tp.zfs.receive_from_reader(&target, &mut &stream[..], force) {
+        Err(Error::PartialReceiveExists(name)) => assert_eq!(name, target),
+        other => panic!("expected PartialReceiveExists, got {:?}", other),
+    }
+
+    let rest_path = tp.dir.join("rest");
+    let rest = File::create(&rest_path).unwrap();
+    tp.zfs.send_resume(&token, rest.into(), ZfsSendFlags(0)).unwrap();
+    let mut rest = File::open(&rest_path).unwrap();
+    tp.zfs.receive_from_reader(&target, &mut rest, resumable).unwrap();
+
+    assert!(tp.exists("copy@a"));
+    let copy = tp.dataset("copy");
+    assert!(!copy.has_partial_receive().unwrap());
+    if !copy.is_mounted() {
+        copy.mount(&MountOpts::new()).unwrap();
+    }
+    assert_eq!(fs::read(copy.mount_path().unwrap().join("data")).unwrap(), data);
+}
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn abort_interrupted_receive() {
+    let tp = TestPool::new("abortrecv");
+    let root = tp.root();
+    root.mount(&MountOpts::new()).unwrap();
+    write_data(&root.mount_path().unwrap().join("data"), 8 << 20);
+    let snap = tp.zfs.snapshot(&tp.full_name("@a"), false).unwrap();
+
+    let mut stream = vec![];
+    snap.send_to_writer(&mut stream, ZfsSendFlags(0)).unwrap();
+    let target = tp.full_name("copy");
+    let resumable = ReceiveOpts { resumable: true, ..Default::default() };
+    let mut half = &stream[..stream.len() / 2];
+    tp.zfs.receive_from_reader(&target, &mut half, resumable).unwrap_err();
+
+    tp.dataset("copy").abort_partial_receive().unwrap();
+    assert!(!tp.exists("copy"));
+
+    match tp.root().abort_partial_receive() {
+        Err(Error::NoPartialReceive(name)) => assert_eq!(name, tp.name),
+        other => panic!("expected NoPartialReceive, got {:?}", other),
+    }
+    tp.zfs.receive_from_reader(&target, &mut &stream[..], ReceiveOpts::default()).unwrap();
+    assert!(tp.exists("copy@a"));
+}
@@ -0,0 +1,49 @@
+//! Devices of a scratch pool. See `common` for how to run these.
+
+mod common;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use libzfs::{PoolHealth, ScanState, VdevId, VdevState, VdevType};
+
+use common::TestPool;
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn mirror_vdev_tree() {
+    let tp = TestPool::mirror("mirrortree");
+    let tree = tp.pool().vdev_tree().unwrap();
+    assert_eq!(tree.vdev_type, VdevType::Root);
+    assert_eq!(tree.children.len(), 1);
+
+    let mirror = &tree.children[0];
+    assert_eq!(mirror.vdev_type, VdevType::Mirror);
+    assert_eq!(mirror.state, VdevState::Online);
+    let leaves: Vec<_> = mirror.leaves().map(|leaf| leaf.path.clone().unwrap()).collect();
+    assert_eq!(leaves, tp.files);
+    assert!(mirror.leaves().all(|leaf| leaf.vdev_type == VdevType::File));
+}
+
+#[test]
+#[ignore = "needs root and the ZFS kernel module"]
+fn offline_and_online() {
+    let tp = TestPool::mirror("offline");
+    let dev = VdevId::Path(tp.files[0].clone());
+
+    tp.pool().vdev_offline(&dev, false).unwrap();
+    assert_eq!(tp.pool().health().unwrap(), PoolHealth::Degraded);
+    assert_eq!(tp.pool().find_vdev(&dev).unwrap().state, VdevState::Offline);
+
+    // The other side is the last one left, so it can't go offline too.
+    tp.pool().vdev_offline(&VdevId::Path(tp.files[1].clone()), false).unwrap_err();
+
+    tp.pool().vdev_online(&dev, false).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(60);
+    while tp.pool().scan_stats().unwrap().is_some_and(|s| s.state == ScanState::Scanning) {
+        assert!(Instant::now() < deadline, "resilver didn't finish");
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(tp.pool().health().unwrap(), PoolHealth::Online);
+    assert_eq!(tp.pool().find_vdev(&dev).unwrap().state, VdevState::Online);
+}